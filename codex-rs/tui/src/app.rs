@@ -159,6 +159,7 @@ impl App {
                 app.handle_tui_event(tui, event).await?
             }
         } {}
+        crate::bottom_pane::remove_session_trash();
         tui.terminal.clear()?;
         Ok(AppExitInfo {
             token_usage: app.token_usage(),
@@ -186,8 +187,16 @@ impl App {
                     let pasted = pasted.replace("\r", "\n");
                     self.chat_widget.handle_paste(pasted);
                 }
+                TuiEvent::FocusChanged(focused) => {
+                    self.chat_widget.on_focus_changed(focused);
+                }
+                TuiEvent::Mouse(mouse_event) => {
+                    self.chat_widget
+                        .handle_mouse_event(mouse_event, tui.terminal.viewport_area);
+                }
                 TuiEvent::Draw => {
                     self.chat_widget.maybe_post_pending_notification(tui);
+                    self.chat_widget.on_tick();
                     if self
                         .chat_widget
                         .handle_paste_burst_tick(tui.frame_requester())
@@ -203,6 +212,8 @@ impl App {
                             }
                         },
                     )?;
+                    tui.set_cursor_shape(self.chat_widget.cursor_shape())?;
+                    tui.set_mouse_capture(self.chat_widget.wants_mouse())?;
                 }
             }
         }
@@ -298,6 +309,10 @@ impl App {
                 ));
                 tui.frame_requester().schedule_frame();
             }
+            AppEvent::RunExternalCommand(command) => {
+                let status = tui.run_external(&mut command.into()).await;
+                self.chat_widget.on_external_command_finished(status);
+            }
             AppEvent::StartFileSearch(query) => {
                 if !query.is_empty() {
                     self.file_search.on_user_query(query);
@@ -76,4 +76,8 @@ pub(crate) enum AppEvent {
 
     /// Open the custom prompt option from the review popup.
     OpenReviewCustomPrompt,
+
+    /// Hand the terminal to a child process, such as the user's `$EDITOR`,
+    /// and tell the active bottom pane view how it exited.
+    RunExternalCommand(std::process::Command),
 }
@@ -1,7 +1,11 @@
 use crate::bottom_pane::ApprovalRequest;
+use crate::tui::CursorShape;
 use crossterm::event::KeyEvent;
+use crossterm::event::MouseEvent;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
+use std::io;
+use std::process::ExitStatus;
 
 use super::CancellationEvent;
 
@@ -11,6 +15,18 @@ pub(crate) trait BottomPaneView {
     /// scheduled after this call.
     fn handle_key_event(&mut self, _key_event: KeyEvent) {}
 
+    /// Handle a mouse event while the view is active; `area` is where the
+    /// view was last rendered. Return true if the view needs a redraw.
+    fn handle_mouse_event(&mut self, _mouse_event: MouseEvent, _area: Rect) -> bool {
+        false
+    }
+
+    /// Whether the terminal should report mouse events while this view is
+    /// active. Capturing the mouse disables the terminal's own selection.
+    fn wants_mouse(&self) -> bool {
+        false
+    }
+
     /// Return `true` if the view has finished and should be removed.
     fn is_complete(&self) -> bool {
         false
@@ -21,6 +37,22 @@ pub(crate) trait BottomPaneView {
         CancellationEvent::NotHandled
     }
 
+    /// Called before each draw so timers such as debounces can fire.
+    fn on_tick(&mut self) {}
+
+    /// Called when the view gains or loses focus, either with the terminal
+    /// window or because another view was pushed on top of it.
+    fn on_focus_changed(&mut self, _focused: bool) {}
+
+    /// Called with the outcome of a command the view asked the app to run
+    /// through `AppEvent::RunExternalCommand`.
+    fn on_external_command_finished(&mut self, _status: io::Result<ExitStatus>) {}
+
+    /// Handle Esc while this view is active. Defaults to the Ctrl-C handling.
+    fn on_esc(&mut self) -> CancellationEvent {
+        self.on_ctrl_c()
+    }
+
     /// Return the desired height of the view.
     fn desired_height(&self, width: u16) -> u16;
 
@@ -38,6 +70,12 @@ pub(crate) trait BottomPaneView {
         None
     }
 
+    /// Terminal cursor shape to request while this view is active. `None`
+    /// leaves the user's default shape in place.
+    fn cursor_shape(&self) -> Option<CursorShape> {
+        None
+    }
+
     /// Try to handle approval request; return the original value if not
     /// consumed.
     fn try_consume_approval_request(
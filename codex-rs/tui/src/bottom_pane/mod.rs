@@ -2,17 +2,21 @@
 use std::path::PathBuf;
 
 use crate::app_event_sender::AppEventSender;
+use crate::tui::CursorShape;
 use crate::tui::FrameRequester;
 use bottom_pane_view::BottomPaneView;
 use codex_core::protocol::TokenUsageInfo;
 use codex_file_search::FileMatch;
 use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
+use crossterm::event::MouseEvent;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Constraint;
 use ratatui::layout::Layout;
 use ratatui::layout::Rect;
 use ratatui::widgets::WidgetRef;
+use std::io;
+use std::process::ExitStatus;
 use std::time::Duration;
 
 mod approval_overlay;
@@ -38,6 +42,10 @@ mod textarea;
 pub(crate) enum CancellationEvent {
     Handled,
     NotHandled,
+    /// The view consumed the key but the user wants to go past it, e.g. a
+    /// second Ctrl-C at a view's own "discard changes?" prompt. The host
+    /// treats it like `NotHandled` (interrupt or quit) and the view stays.
+    Escalate,
 }
 
 pub(crate) use chat_composer::ChatComposer;
@@ -48,6 +56,7 @@ use crate::status_indicator_widget::StatusIndicatorWidget;
 pub(crate) use list_selection_view::SelectionAction;
 pub(crate) use list_selection_view::SelectionItem;
 pub(crate) use preferences_editor_view::PreferencesEditorView;
+pub(crate) use preferences_editor_view::remove_session_trash;
 
 /// Pane displayed in the lower half of the chat UI.
 pub(crate) struct BottomPane {
@@ -114,10 +123,21 @@ impl BottomPane {
     }
 
     fn push_view(&mut self, view: Box<dyn BottomPaneView>) {
+        if let Some(covered) = self.view_stack.last_mut() {
+            covered.on_focus_changed(false);
+        }
         self.view_stack.push(view);
         self.request_redraw();
     }
 
+    /// Remove the active view and hand focus back to the view it covered.
+    fn pop_view(&mut self) {
+        self.view_stack.pop();
+        if let Some(exposed) = self.view_stack.last_mut() {
+            exposed.on_focus_changed(true);
+        }
+    }
+
     pub fn desired_height(&self, width: u16) -> u16 {
         // Always reserve one blank row above the pane for visual spacing.
         let top_margin = 1;
@@ -177,16 +197,62 @@ impl BottomPane {
         }
     }
 
+    /// Forward a mouse event to the active view, given the area the bottom
+    /// pane was rendered into.
+    pub(crate) fn handle_mouse_event(&mut self, mouse_event: MouseEvent, area: Rect) {
+        let [_, content] = self.layout(area);
+        if let Some(view) = self.view_stack.last_mut()
+            && view.handle_mouse_event(mouse_event, content)
+        {
+            self.request_redraw();
+        }
+    }
+
+    /// Whether the active view wants mouse events reported.
+    pub(crate) fn wants_mouse(&self) -> bool {
+        self.active_view().is_some_and(BottomPaneView::wants_mouse)
+    }
+
+    /// Let the active view run its timers before a draw.
+    pub(crate) fn on_tick(&mut self) {
+        if let Some(view) = self.view_stack.last_mut() {
+            view.on_tick();
+        }
+    }
+
+    /// Tell the active view how a command it asked the app to run exited.
+    pub(crate) fn on_external_command_finished(&mut self, status: io::Result<ExitStatus>) {
+        if let Some(view) = self.view_stack.last_mut() {
+            view.on_external_command_finished(status);
+            self.request_redraw();
+        }
+    }
+
+    /// Tell the active view that the terminal gained or lost focus.
+    pub(crate) fn on_focus_changed(&mut self, focused: bool) {
+        if let Some(view) = self.view_stack.last_mut() {
+            view.on_focus_changed(focused);
+            self.request_redraw();
+        }
+    }
+
+    /// Cursor shape requested by the active view, if any.
+    pub(crate) fn cursor_shape(&self) -> Option<CursorShape> {
+        self.active_view().and_then(BottomPaneView::cursor_shape)
+    }
+
     /// Forward a key event to the active view or the composer.
     pub fn handle_key_event(&mut self, key_event: KeyEvent) -> InputResult {
         // If a modal/view is active, handle it here; otherwise forward to composer.
         if let Some(view) = self.view_stack.last_mut() {
-            if key_event.code == KeyCode::Esc
-                && matches!(view.on_ctrl_c(), CancellationEvent::Handled)
-                && view.is_complete()
+            if key_event.code == KeyCode::Esc && matches!(view.on_esc(), CancellationEvent::Handled)
             {
-                self.view_stack.pop();
-                self.on_active_view_complete();
+                // The view dealt with Esc, e.g. by closing a prompt of its
+                // own; it must not see the key a second time.
+                if view.is_complete() {
+                    self.pop_view();
+                    self.on_active_view_complete();
+                }
             } else {
                 view.handle_key_event(key_event);
                 if view.is_complete() {
@@ -226,7 +292,7 @@ impl BottomPane {
             let event = view.on_ctrl_c();
             if matches!(event, CancellationEvent::Handled) {
                 if view.is_complete() {
-                    self.view_stack.pop();
+                    self.pop_view();
                     self.on_active_view_complete();
                 }
                 self.show_ctrl_c_quit_hint();
@@ -390,6 +456,14 @@ impl BottomPane {
         self.push_view(view);
     }
 
+    /// Show `editor`, letting it schedule its own redraws so status messages
+    /// and spinners update without waiting for a key.
+    pub(crate) fn show_preferences_editor(&mut self, mut editor: PreferencesEditorView) {
+        editor.set_frame_requester(self.frame_requester.clone());
+        editor.set_app_event_sender(self.app_event_tx.clone());
+        self.push_view(Box::new(editor));
+    }
+
     /// Called when the agent requests user approval.
     pub fn push_approval_request(&mut self, request: ApprovalRequest) {
         let request = if let Some(view) = self.view_stack.last_mut() {
@@ -514,6 +588,8 @@ mod tests {
     use crate::app_event::AppEvent;
     use ratatui::buffer::Buffer;
     use ratatui::layout::Rect;
+    use std::cell::Cell;
+    use std::rc::Rc;
     use tokio::sync::mpsc::unbounded_channel;
 
     fn exec_request() -> ApprovalRequest {
@@ -773,4 +849,98 @@ mod tests {
             "expected composer with no padding: {only:?}"
         );
     }
+
+    /// Records what reaches it; Esc closes an inner prompt the first time
+    /// and completes the view the second.
+    struct EscRecorder {
+        escs: usize,
+        keys: Rc<Cell<usize>>,
+        focused: Rc<Cell<bool>>,
+    }
+
+    impl BottomPaneView for EscRecorder {
+        fn handle_key_event(&mut self, _key_event: KeyEvent) {
+            self.keys.set(self.keys.get() + 1);
+        }
+
+        fn is_complete(&self) -> bool {
+            self.escs >= 2
+        }
+
+        fn on_esc(&mut self) -> CancellationEvent {
+            self.escs += 1;
+            CancellationEvent::Handled
+        }
+
+        fn on_focus_changed(&mut self, focused: bool) {
+            self.focused.set(focused);
+        }
+
+        fn desired_height(&self, _width: u16) -> u16 {
+            1
+        }
+
+        fn render(&self, _area: Rect, _buf: &mut Buffer) {}
+    }
+
+    #[test]
+    fn esc_handled_by_a_view_is_not_delivered_again() {
+        let (tx_raw, _rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx_raw);
+        let mut pane = BottomPane::new(BottomPaneParams {
+            app_event_tx: tx,
+            frame_requester: FrameRequester::test_dummy(),
+            has_input_focus: true,
+            enhanced_keys_supported: false,
+            placeholder_text: "Ask Codex to do anything".to_string(),
+            disable_paste_burst: false,
+        });
+        let keys = Rc::new(Cell::new(0));
+        pane.show_view(Box::new(EscRecorder {
+            escs: 0,
+            keys: keys.clone(),
+            focused: Rc::new(Cell::new(true)),
+        }));
+
+        let esc = KeyEvent::new(KeyCode::Esc, crossterm::event::KeyModifiers::NONE);
+        pane.handle_key_event(esc);
+        assert_eq!(keys.get(), 0);
+        assert_eq!(pane.view_stack.len(), 1);
+
+        pane.handle_key_event(esc);
+        assert_eq!(keys.get(), 0);
+        assert!(pane.view_stack.is_empty());
+    }
+
+    #[test]
+    fn popping_a_view_gives_focus_back_to_the_one_below() {
+        let (tx_raw, _rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx_raw);
+        let mut pane = BottomPane::new(BottomPaneParams {
+            app_event_tx: tx,
+            frame_requester: FrameRequester::test_dummy(),
+            has_input_focus: true,
+            enhanced_keys_supported: false,
+            placeholder_text: "Ask Codex to do anything".to_string(),
+            disable_paste_burst: false,
+        });
+        let focused = Rc::new(Cell::new(true));
+        pane.show_view(Box::new(EscRecorder {
+            escs: 0,
+            keys: Rc::default(),
+            focused: focused.clone(),
+        }));
+        pane.show_view(Box::new(EscRecorder {
+            escs: 0,
+            keys: Rc::default(),
+            focused: Rc::new(Cell::new(true)),
+        }));
+        assert!(!focused.get());
+
+        let esc = KeyEvent::new(KeyCode::Esc, crossterm::event::KeyModifiers::NONE);
+        pane.handle_key_event(esc);
+        pane.handle_key_event(esc);
+        assert_eq!(pane.view_stack.len(), 1);
+        assert!(focused.get());
+    }
 }
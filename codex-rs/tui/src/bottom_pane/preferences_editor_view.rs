@@ -1,12 +1,31 @@
+use std::cell::Cell;
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::fs;
+use std::io;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
 use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::mpsc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 
+use chrono::DateTime;
+use chrono::Local;
 use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
 use crossterm::event::KeyModifiers;
+use crossterm::event::MouseButton;
+use crossterm::event::MouseEvent;
+use crossterm::event::MouseEventKind;
 use ratatui::buffer::Buffer;
+use ratatui::layout::Position;
 use ratatui::layout::Rect;
+use ratatui::style::Modifier;
+use ratatui::style::Style;
 use ratatui::style::Stylize;
 use ratatui::text::Line;
 use ratatui::text::Span;
@@ -14,341 +33,7608 @@ use ratatui::widgets::Clear;
 use ratatui::widgets::Paragraph;
 use ratatui::widgets::StatefulWidgetRef;
 use ratatui::widgets::Widget;
+use regex_lite::Regex;
+use tokio::sync::mpsc::UnboundedSender;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use uuid::Uuid;
 
 use super::CancellationEvent;
 use super::bottom_pane_view::BottomPaneView;
+use super::textarea::Fold;
 use super::textarea::TextArea;
 use super::textarea::TextAreaState;
+use crate::app_event::AppEvent;
+use crate::app_event_sender::AppEventSender;
+use crate::clipboard_copy;
+use crate::tui::CursorShape;
+use crate::tui::FrameRequester;
+
+mod completion;
+mod confirm_close;
+mod conflicts;
+mod events;
+mod export;
+mod external_editor;
+mod file_format;
+mod folding;
+mod front_matter;
+mod includes;
+mod keymap;
+mod occurrences;
+mod overlay;
+mod prompt;
+mod shell;
+mod solo;
+mod status;
+mod text_ops;
+mod theme;
+mod trash;
+mod validation;
+mod window;
+
+use completion::KeyCompletion;
+use confirm_close::CloseChoice;
+use confirm_close::ConfirmClose;
+use conflicts::Conflict;
+use conflicts::Resolution;
+pub(crate) use events::PreferencesEditorEvent;
+pub(crate) use events::SessionStats;
+use export::HtmlExport;
+use external_editor::ExternalEdit;
+use file_format::FileFormat;
+pub(crate) use keymap::EditorKeymap;
+pub(crate) use keymap::KeyBinding;
+use occurrences::OccurrenceEdit;
+use occurrences::Occurrences;
+use overlay::Overlay;
+use prompt::Prompt;
+use prompt::PromptKind;
+use prompt::PromptOutcome;
+use shell::ShellRun;
+use solo::Solo;
+use status::Busy;
+use status::SPINNER_INTERVAL;
+use status::StatusMessage;
+pub(crate) use status::StatusPersistence;
+use status::relative_time;
+use status::spinner_frame;
+use text_ops::AutoPair;
+pub(crate) use theme::EditorTheme;
+use trash::Trash;
+pub(crate) use trash::remove_session_trash;
+pub(crate) use validation::ValidationError;
+use validation::ValidationRun;
+pub(crate) use validation::Validator;
+use window::FileWindow;
 
 pub(crate) struct PreferencesEditorView {
     path: PathBuf,
     display_path: String,
     textarea: TextArea,
     textarea_state: RefCell<TextAreaState>,
+    /// Where the next render should put the cursor row, set by Ctrl+L.
+    recenter: Cell<Option<Recenter>>,
+    /// The last Ctrl+L position, so repeated presses cycle.
+    recenter_cycle: Option<Recenter>,
+    /// Text rows of the focused pane and the cursor's row in it, as last
+    /// drawn; PageUp/PageDown move by a screen and keep that row.
+    page_rows: Cell<u16>,
+    cursor_row: Cell<u16>,
+    /// Row the next render scrolls the cursor to after a page move.
+    page_scroll_row: Cell<Option<u16>>,
+    /// Second viewport onto the buffer, toggled with Alt+T.
+    split: Option<SplitPane>,
+    /// Center the view on each search match.
+    center_on_search: bool,
+    /// Where the last Ctrl+F match put the cursor, so Enter moves past it.
+    search_match: Option<usize>,
+    /// Shown after the search query, e.g. "2 of 5" or "no matches".
+    search_status: Option<String>,
     last_saved_text: String,
     dirty: bool,
     complete: bool,
     status_message: Option<StatusMessage>,
-    confirm_discard: bool,
+    /// Operation in progress, shown with a spinner until `end_busy`.
+    busy: Option<Busy>,
+    /// Save / Discard / Cancel choice, open after closing with unsaved
+    /// changes.
+    confirm_close: Option<ConfirmClose>,
+    /// Set after a save was held back because it would blank a non-empty file.
+    confirm_empty_save: bool,
+    /// Set after a save was held back by validation problems; the next
+    /// Ctrl+S saves anyway.
+    confirm_invalid_save: bool,
+    /// The file as it was on disk when loaded or last saved, so a save can
+    /// tell that another process rewrote it in the meantime.
+    disk_stamp: Option<DiskStamp>,
+    /// Set after a save was held back because the file changed on disk; the
+    /// next Ctrl+S overwrites it.
+    confirm_external_overwrite: bool,
+    /// Set after Ctrl+R was held back by unsaved changes; the next Ctrl+R
+    /// reloads anyway.
+    confirm_reload: bool,
+    /// Existing file Ctrl+Shift+S would replace; Enter confirms.
+    confirm_save_as: Option<PathBuf>,
+    /// Matches references to other files, with the path as its first
+    /// capture group; Ctrl+O opens the one under the cursor.
+    include_pattern: Option<Regex>,
+    /// Set after Ctrl+O was held back by unsaved changes; the next Ctrl+O
+    /// opens the referenced file anyway.
+    confirm_follow_include: bool,
+    text_direction: TextDirection,
+    uuid_format: UuidFormat,
+    glyphs: GutterGlyphs,
+    theme: EditorTheme,
+    cursor_shape: Option<CursorShape>,
+    /// Clicks place the cursor and the wheel scrolls, at the cost of the
+    /// terminal's own text selection. Off unless the host opts in.
+    mouse: bool,
+    /// Type brackets and quotes in pairs.
+    auto_pair: bool,
+    /// Fixed end of the selection; the cursor is the moving end.
+    selection_anchor: Option<usize>,
+    /// Separator used by the align command; `None` picks `=` or `:` per block.
+    align_separator: Option<char>,
+    /// Whether squeezing the whole buffer also drops leading/trailing blank lines.
+    trim_blank_edges: bool,
+    load_state: LoadState,
+    frame_requester: Option<FrameRequester>,
+    /// Where to ask the app to run `$EDITOR`; without it Ctrl+E is unavailable.
+    app_event_tx: Option<AppEventSender>,
+    /// The buffer's copy out with `$EDITOR`, until the app reports back.
+    external_edit: Option<ExternalEdit>,
+    ctrl_c_behavior: CtrlCBehavior,
+    keymap: EditorKeymap,
+    /// Typed characters replace the one under the cursor instead of inserting.
+    overtype: bool,
+    /// Most recent editing command, replayed by Alt+.
+    last_command: Option<EditorCommand>,
+    status_persistence: StatusPersistence,
+    /// Unsaved notes shown under the editor while toggled on.
+    scratch: Option<ScratchPane>,
+    scratch_focused: bool,
+    /// Heading lines of folded sections. Keyed by text so folds survive edits
+    /// elsewhere in the buffer; editing a heading drops its fold.
+    folded: Vec<String>,
+    /// Alt+Z was pressed and the next key picks a fold command.
+    fold_prefix: bool,
+    /// Ctrl+X was pressed with nothing selected (with a selection it cuts);
+    /// Ctrl+T next transposes lines, `i` fixes
+    /// indentation, `e` copies a validation error, `,` converts between a
+    /// list and a comma-separated line and `p` previews the parse.
+    ctrl_x_prefix: bool,
+    /// Style Ctrl+X i rewrites mixed indentation to.
+    indent_style: IndentStyle,
+    /// Leading whitespace of lines mixing tabs and spaces, refreshed when
+    /// the buffer changes.
+    mixed_indent: Vec<Range<usize>>,
+    /// The other sections are commented out while one is soloed.
+    solo: Option<Solo>,
+    /// Status-line prompt collecting an argument; it takes all keys while open.
+    prompt: Option<Prompt>,
+    /// Read-only panel shown instead of the text area until Esc.
+    overlay: Option<Overlay>,
+    /// Every occurrence of a word being edited together, until Esc.
+    occurrences: Option<Occurrences>,
+    event_tx: Option<UnboundedSender<PreferencesEditorEvent>>,
+    /// Counters behind the `SessionEnded` event.
+    session: SessionCounters,
+    /// Language hint for wrapping the next paste in a code fence, once armed.
+    fence_next_paste: Option<String>,
+    /// Save automatically when the editor loses focus.
+    save_on_blur: bool,
+    /// Show the key hint line under the editor; toggled with Alt+/.
+    show_hint: bool,
+    /// Number the lines in the gutter; toggled with Alt+G.
+    show_line_numbers: bool,
+    /// Dim lines that match the saved file so unsaved changes stand out;
+    /// toggled with Alt+E.
+    focus_unsaved: bool,
+    /// Once the save message expires, keep showing where and how long ago
+    /// the buffer was saved instead of "All changes saved".
+    relative_save_status: bool,
+    last_saved_at: Option<Instant>,
+    /// Only mark the buffer dirty once it has differed from the saved text
+    /// for this long, so edits that are immediately reverted do not flicker.
+    dirty_debounce: Option<Duration>,
+    /// When the buffer started differing while `dirty` is held back.
+    dirty_pending_since: Option<Instant>,
+    /// Save by itself once typing has paused for this long.
+    auto_save: Option<Duration>,
+    /// When the buffer last changed, for auto-save.
+    last_edit_at: Option<Instant>,
+    /// Typographic characters and the ASCII text that replaces them.
+    ascii_replacements: Vec<(char, String)>,
+    /// Header inserted by the front-matter command when the buffer has none.
+    front_matter_template: String,
+    /// Line comment token; defaults to one picked from the file extension.
+    comment_token: String,
+    /// Column limit for Alt+W.
+    hard_wrap_width: usize,
+    /// Schema or documentation page for this file, opened with Alt+I.
+    docs_url: Option<String>,
+    /// No browser to open links in, so they are shown instead.
+    headless: bool,
+    /// Log-style file: the cursor stays at the end and the saved contents
+    /// cannot be changed, only added to.
+    append_only: bool,
+    /// The file cannot be written: navigation and copying work, edits are
+    /// refused.
+    read_only: bool,
+    /// Full size in bytes of a file too large to edit, of which only the start
+    /// is loaded, read-only. Nothing can be saved from it.
+    preview_of: Option<usize>,
+    /// Line endings and byte order mark of the file, restored when the
+    /// buffer is written.
+    file_format: FileFormat,
+    /// Only the tail of a large file is loaded; saves append to it.
+    window: Option<FileWindow>,
+    /// Buffer and cursor before each edit, newest last; Ctrl+Z pops.
+    undo_stack: VecDeque<(String, usize)>,
+    /// States undone since the last edit; Ctrl+Y pops.
+    redo_stack: Vec<(String, usize)>,
+    /// Inserted between copies by Alt+Y.
+    duplicate_separator: String,
+    /// Spaces Tab inserts and Shift+Tab removes.
+    tab_spaces: usize,
+    /// Known preference keys offered while typing at the start of a line.
+    completion_keys: Vec<String>,
+    /// Popup of matching keys; Tab accepts, Esc dismisses.
+    key_completion: Option<KeyCompletion>,
+    /// Alt+X may run shell commands and insert their output.
+    allow_shell_commands: bool,
+    /// An Alt+X command still running.
+    shell_run: Option<ShellRun>,
+    /// Where discarded buffers go so Alt+R can bring the last one back.
+    trash: Trash,
+    /// Remove comments from the buffer before each save.
+    strip_comments_on_save: bool,
+    /// Trim trailing whitespace and end with exactly one newline on save.
+    normalize_whitespace_on_save: bool,
+    /// Most lines a save keeps, and which end loses the rest.
+    max_lines: Option<(usize, LineCapDrop)>,
+    /// Lines the current save dropped to stay under `max_lines`, for the
+    /// saved message.
+    trimmed_on_save: usize,
+    /// Why the formatter could not run on the current save, for the saved
+    /// message.
+    format_error_on_save: Option<String>,
+    /// Rewrites the buffer before each save.
+    formatter: Option<Formatter>,
+    on_save: Option<SaveHook>,
+    /// Ask before applying a formatting pass that changes more lines than this.
+    format_confirm_threshold: Option<usize>,
+    /// Formatted contents awaiting confirmation, shown as a diff preview.
+    pending_format: Option<String>,
+    validator: Option<Validator>,
+    /// Renders how the host will read the buffer, shown by Ctrl+X p.
+    parse_preview: Option<ParsePreview>,
+    /// Problems from the last validation run, marked in the gutter.
+    validation_errors: Vec<ValidationError>,
+    /// Run the validator on a worker thread when saving.
+    validate_in_background: bool,
+    validation_run: Option<ValidationRun>,
+}
+
+/// Format-on-save hook: returns the reformatted buffer or an error message.
+pub(crate) type Formatter = Box<dyn Fn(&str) -> Result<String, String>>;
+
+/// Save hook: called with the path and the contents just written after every
+/// successful save, e.g. so the host can reload its configuration. Not called
+/// for windowed files, whose full contents are never in memory.
+pub(crate) type SaveHook = Box<dyn FnMut(&Path, &str)>;
+
+/// Parse hook: returns the parsed preferences rendered as text (e.g. a
+/// key/value tree), or an error message.
+pub(crate) type ParsePreview = Box<dyn Fn(&str) -> Result<String, String>>;
+
+/// The second viewport of a split: its own cursor and scroll over the same
+/// buffer. The focused pane uses the text area's cursor and state, so the
+/// two are swapped when focus moves.
+struct SplitPane {
+    cursor: usize,
+    state: RefCell<TextAreaState>,
+    /// The lower pane has focus.
+    focus_bottom: bool,
+}
+
+/// A small buffer for throwaway notes. Its contents are never written to disk
+/// and are dropped when the pane is hidden or the editor closes.
+struct ScratchPane {
+    textarea: TextArea,
+    state: RefCell<TextAreaState>,
+}
+
+/// Editing commands bound to keys, tracked so the last one can be repeated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditorCommand {
+    AlignSeparators,
+    SqueezeBlankLines,
+    InsertMarkdownLink,
+    InsertUuid,
+    AsciiPunctuation,
+    InsertFrontMatter,
+    IncrementNumber,
+    DecrementNumber,
+    SortFrontMatter,
+    ToggleComment,
+    HardWrapLine,
+    JoinLines,
+    PrettyPrintJson,
+    DuplicateSelection,
+    ToggleListCsv,
+}
+
+impl EditorCommand {
+    fn for_alt_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('=') => Some(Self::AlignSeparators),
+            KeyCode::Char('q') | KeyCode::Char('Q') => Some(Self::SqueezeBlankLines),
+            KeyCode::Char('k') | KeyCode::Char('K') => Some(Self::InsertMarkdownLink),
+            KeyCode::Char('u') | KeyCode::Char('U') => Some(Self::InsertUuid),
+            KeyCode::Char('a') | KeyCode::Char('A') => Some(Self::AsciiPunctuation),
+            KeyCode::Char('m') | KeyCode::Char('M') => Some(Self::InsertFrontMatter),
+            KeyCode::Char('o') | KeyCode::Char('O') => Some(Self::SortFrontMatter),
+            KeyCode::Char('c') | KeyCode::Char('C') => Some(Self::ToggleComment),
+            KeyCode::Char('w') | KeyCode::Char('W') => Some(Self::HardWrapLine),
+            KeyCode::Char('j') | KeyCode::Char('J') => Some(Self::JoinLines),
+            KeyCode::Char('p') | KeyCode::Char('P') => Some(Self::PrettyPrintJson),
+            KeyCode::Char('+') => Some(Self::IncrementNumber),
+            KeyCode::Char('-') => Some(Self::DecrementNumber),
+            KeyCode::Char('y') => Some(Self::DuplicateSelection),
+            _ => None,
+        }
+    }
+}
+
+/// What Ctrl+C does in the editor. Esc always closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum CtrlCBehavior {
+    /// Close the editor, asking first when there are unsaved changes.
+    Close,
+    /// Copy the selection if there is one, otherwise close.
+    #[default]
+    CopyOrClose,
+    /// Copy the selection if there is one, otherwise do nothing.
+    CopyOnly,
+}
+
+/// Which lines a save drops when the buffer is over its line limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum LineCapDrop {
+    /// Drop lines from the top, as in a log that grows at the bottom.
+    #[default]
+    Oldest,
+    /// Drop lines from the bottom.
+    Newest,
+}
+
+/// Running totals for the session summary sent when the editor closes.
+struct SessionCounters {
+    opened_text: String,
+    opened_at: Instant,
+    chars_changed: usize,
+    saves: usize,
+}
+
+impl SessionCounters {
+    fn new(text: &str) -> Self {
+        Self {
+            opened_text: text.to_string(),
+            opened_at: Instant::now(),
+            chars_changed: 0,
+            saves: 0,
+        }
+    }
+
+    /// Count the characters one edit removed and inserted. Edits are
+    /// contiguous, so comparing the common prefix and suffix is enough.
+    fn record_edit(&mut self, before: &str, after: &str) {
+        let prefix: usize = before
+            .chars()
+            .zip(after.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        let suffix: usize = before[prefix..]
+            .chars()
+            .rev()
+            .zip(after[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        let removed = before[prefix..before.len() - suffix].chars().count();
+        let inserted = after[prefix..after.len() - suffix].chars().count();
+        self.chars_changed += removed + inserted;
+    }
+
+    fn stats(&self, final_text: &str) -> SessionStats {
+        let (lines_added, lines_removed) = ChangeSummary::between(&self.opened_text, final_text)
+            .map_or((0, 0), |summary| (summary.added, summary.removed));
+        SessionStats {
+            lines_added,
+            lines_removed,
+            chars_changed: self.chars_changed,
+            saves: self.saves,
+            duration: self.opened_at.elapsed(),
+        }
+    }
+}
+
+/// Whether the buffer holds the real contents yet. Edits and saves are only
+/// accepted once the view is `Ready`.
+enum LoadState {
+    Ready,
+    Loading {
+        since: Instant,
+    },
+    /// Loading failed; the buffer stays empty so a save cannot clobber the file.
+    Failed,
+}
+
+/// Paragraph direction used to lay out the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum TextDirection {
+    /// Pick right-to-left when the buffer is dominated by RTL script.
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+/// Formatting used when inserting a generated UUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum UuidFormat {
+    /// `67e55044-10b1-426f-9247-bb680e5fe0c8`
+    #[default]
+    Hyphenated,
+    /// `67e5504410b1426f9247bb680e5fe0c8`
+    Compact,
+}
+
+/// Characters used for the editor's gutter. Both sets are two cells wide so
+/// switching never changes the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GutterGlyphs {
+    /// `▌ ` for the gutter and `✗ ` for lines with validation errors.
+    Unicode,
+    /// `| ` and `x `, for terminals or fonts without block elements.
+    Ascii,
+}
+
+impl GutterGlyphs {
+    /// `Ascii` when the locale says the terminal is not UTF-8, otherwise
+    /// `Unicode`.
+    pub(crate) fn detect() -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()));
+        match locale {
+            Some(locale) if !locale_is_utf8(&locale) => Self::Ascii,
+            _ => Self::Unicode,
+        }
+    }
+
+    fn gutter(self, theme: &EditorTheme) -> Span<'static> {
+        match self {
+            Self::Unicode => "▌ ".fg(theme.gutter),
+            Self::Ascii => "| ".fg(theme.gutter),
+        }
+    }
+
+    fn error(self, theme: &EditorTheme) -> Span<'static> {
+        match self {
+            Self::Unicode => "✗ ".fg(theme.error),
+            Self::Ascii => "x ".fg(theme.error),
+        }
+    }
+
+    fn scroll_thumb(self, theme: &EditorTheme) -> Span<'static> {
+        match self {
+            Self::Unicode => "┃".fg(theme.gutter),
+            Self::Ascii => "#".fg(theme.gutter),
+        }
+    }
+}
+
+/// Modification time and length of the file on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiskStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl DiskStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Files larger than this many bytes open as a read-only preview of their
+/// start: wrapping and highlighting the whole buffer on every keystroke
+/// would make them unusable.
+const MAX_EDITABLE_BYTES: usize = 1024 * 1024;
+
+/// `contents` cut back to the last whole line within [`MAX_EDITABLE_BYTES`],
+/// with the original length, when it is over the limit.
+fn preview_if_huge(mut contents: String) -> (String, Option<usize>) {
+    let full_len = contents.len();
+    if full_len <= MAX_EDITABLE_BYTES {
+        return (contents, None);
+    }
+    let mut end = MAX_EDITABLE_BYTES;
+    while !contents.is_char_boundary(end) {
+        end -= 1;
+    }
+    if let Some(newline) = contents[..end].rfind('\n') {
+        end = newline + 1;
+    }
+    contents.truncate(end);
+    (contents, Some(full_len))
+}
+
+/// Whether saving to `path` can succeed: the file opens for writing or, if it
+/// does not exist yet, a file can be created in the nearest existing
+/// directory above it (save creates the rest). Permission bits alone miss
+/// directories owned by someone else.
+fn is_writable(path: &Path) -> bool {
+    match fs::OpenOptions::new().write(true).open(path) {
+        Ok(_) => true,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => path
+            .ancestors()
+            .skip(1)
+            .map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    dir
+                }
+            })
+            .find(|dir| dir.exists())
+            .is_some_and(|dir| tempfile::tempfile_in(dir).is_ok()),
+        Err(_) => false,
+    }
+}
+
+/// How the buffer indents, used to repair lines that mix tabs and spaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IndentStyle {
+    /// Indent with this many spaces per level.
+    Spaces(usize),
+    /// Indent with tabs that are this many columns wide.
+    Tabs(usize),
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        Self::Spaces(4)
+    }
+}
+
+impl IndentStyle {
+    /// Columns per indentation level (and per tab stop).
+    pub(crate) fn width(self) -> usize {
+        match self {
+            Self::Spaces(width) | Self::Tabs(width) => width.max(1),
+        }
+    }
+}
+
+fn locale_is_utf8(locale: &str) -> bool {
+    let locale = locale.to_ascii_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
 }
 
-struct StatusMessage {
-    text: String,
-    kind: StatusKind,
+/// How the buffer differs from the last saved contents, used to tell the user
+/// what a discard would throw away.
+#[derive(Debug, PartialEq, Eq)]
+struct ChangeSummary {
+    added: usize,
+    removed: usize,
+    /// 1-based line number of the first line that differs.
+    first_line: usize,
+    first_text: String,
 }
 
-enum StatusKind {
-    Info,
-    Error,
-    Warning,
+const CHANGE_PREVIEW_MAX_CHARS: usize = 40;
+
+/// Spaces per leading tab when fencing pasted code.
+const FENCE_TAB_WIDTH: usize = 4;
+
+/// Text rows of the scratch pane, below its one-line label.
+const SCRATCH_TEXT_ROWS: u16 = 3;
+
+/// Most text rows in each pane of a split.
+const SPLIT_PANE_ROWS: u16 = 9;
+
+/// Where Ctrl+L scrolls the cursor row: center first, then top, then bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recenter {
+    Center,
+    Top,
+    Bottom,
+}
+
+impl Recenter {
+    fn next(current: Option<Self>) -> Self {
+        match current {
+            None | Some(Recenter::Bottom) => Recenter::Center,
+            Some(Recenter::Center) => Recenter::Top,
+            Some(Recenter::Top) => Recenter::Bottom,
+        }
+    }
+
+    fn row(self, height: u16) -> u16 {
+        match self {
+            Recenter::Center => height.saturating_sub(1) / 2,
+            Recenter::Top => 0,
+            Recenter::Bottom => height.saturating_sub(1),
+        }
+    }
 }
 
+/// Spaces per Tab press unless the host configures another width.
+const DEFAULT_TAB_SPACES: usize = 2;
+
+/// Most copies Alt+Shift+Y makes in one go.
+const MAX_DUPLICATES: usize = 1000;
+
+/// Edits Ctrl+Z can step back through.
+const UNDO_LIMIT: usize = 200;
+
+/// Rows one notch of the mouse wheel scrolls.
+const MOUSE_SCROLL_ROWS: i32 = 3;
+
+/// Line width Alt+W wraps to unless the host configures another.
+const DEFAULT_HARD_WRAP_WIDTH: usize = 80;
+
+/// Extra indentation for the lines Alt+W breaks off.
+const HARD_WRAP_CONTINUATION: &str = "  ";
+
+/// Local date inserted by Ctrl+T, as a `chrono` format string.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Local date and time inserted by Ctrl+Shift+T.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Front matter inserted by Alt+M unless the host configures its own.
+const DEFAULT_FRONT_MATTER: &str = "---\ntitle: \ndescription: \ntags: []\n---\n\n";
+
 impl PreferencesEditorView {
     pub(crate) fn new(path: PathBuf, contents: String) -> Self {
+        let (file_format, contents) = FileFormat::decode(contents);
+        let (contents, full_len) = preview_if_huge(contents);
         let mut textarea = TextArea::new();
         textarea.set_text(&contents);
         textarea.set_cursor(textarea.text().len());
-        Self {
+        let comment_token = default_comment_token(&path).to_string();
+        let session = SessionCounters::new(&contents);
+        let disk_stamp = DiskStamp::of(&path);
+        let read_only = !is_writable(&path);
+        let mut view = Self {
             display_path: path.display().to_string(),
             path,
             textarea,
             textarea_state: RefCell::new(TextAreaState::default()),
+            recenter: Cell::new(None),
+            recenter_cycle: None,
+            page_rows: Cell::new(0),
+            cursor_row: Cell::new(0),
+            page_scroll_row: Cell::new(None),
+            center_on_search: false,
+            split: None,
+            search_match: None,
+            search_status: None,
             last_saved_text: contents,
             dirty: false,
             complete: false,
             status_message: None,
-            confirm_discard: false,
-        }
+            busy: None,
+            confirm_close: None,
+            confirm_empty_save: false,
+            confirm_invalid_save: false,
+            disk_stamp,
+            confirm_external_overwrite: false,
+            confirm_reload: false,
+            confirm_save_as: None,
+            include_pattern: None,
+            confirm_follow_include: false,
+            text_direction: TextDirection::Auto,
+            uuid_format: UuidFormat::default(),
+            glyphs: GutterGlyphs::detect(),
+            theme: EditorTheme::default(),
+            cursor_shape: None,
+            mouse: false,
+            auto_pair: true,
+            selection_anchor: None,
+            align_separator: None,
+            trim_blank_edges: false,
+            load_state: LoadState::Ready,
+            frame_requester: None,
+            app_event_tx: None,
+            external_edit: None,
+            ctrl_c_behavior: CtrlCBehavior::default(),
+            keymap: EditorKeymap::default(),
+            overtype: false,
+            last_command: None,
+            status_persistence: StatusPersistence::default(),
+            scratch: None,
+            scratch_focused: false,
+            folded: Vec::new(),
+            fold_prefix: false,
+            ctrl_x_prefix: false,
+            indent_style: IndentStyle::default(),
+            mixed_indent: Vec::new(),
+            solo: None,
+            prompt: None,
+            overlay: None,
+            occurrences: None,
+            event_tx: None,
+            session,
+            fence_next_paste: None,
+            save_on_blur: false,
+            show_hint: true,
+            show_line_numbers: true,
+            focus_unsaved: false,
+            relative_save_status: false,
+            dirty_debounce: None,
+            dirty_pending_since: None,
+            auto_save: None,
+            last_edit_at: None,
+            last_saved_at: None,
+            ascii_replacements: default_ascii_replacements(),
+            front_matter_template: DEFAULT_FRONT_MATTER.to_string(),
+            comment_token,
+            hard_wrap_width: DEFAULT_HARD_WRAP_WIDTH,
+            docs_url: None,
+            headless: is_headless(),
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            duplicate_separator: String::new(),
+            tab_spaces: DEFAULT_TAB_SPACES,
+            completion_keys: Vec::new(),
+            key_completion: None,
+            allow_shell_commands: false,
+            shell_run: None,
+            append_only: false,
+            read_only,
+            window: None,
+            trash: Trash::for_session(),
+            strip_comments_on_save: false,
+            normalize_whitespace_on_save: true,
+            max_lines: None,
+            trimmed_on_save: 0,
+            format_error_on_save: None,
+            formatter: None,
+            on_save: None,
+            format_confirm_threshold: None,
+            pending_format: None,
+            validator: None,
+            parse_preview: None,
+            validation_errors: Vec::new(),
+            validate_in_background: false,
+            validation_run: None,
+            preview_of: None,
+            file_format,
+        };
+        view.resolve_text_direction();
+        view.lint_indentation();
+        view.sync_highlights();
+        view.set_preview(full_len);
+        view
     }
 
-    fn apply_editor_change<F: FnOnce(&mut TextArea)>(&mut self, edit: F) -> bool {
-        let before = self.textarea.text().to_string();
-        edit(&mut self.textarea);
-        let changed = self.textarea.text() != before;
-        if changed {
-            self.dirty = self.textarea.text() != self.last_saved_text;
-            self.status_message = None;
-            self.confirm_discard = false;
+    /// Like [`Self::new`], but with the cursor at 1-based `line` and `col`
+    /// (counted in characters) and that line scrolled into the middle of the
+    /// view. Positions past the end clamp to the nearest one that exists.
+    pub(crate) fn new_at(path: PathBuf, contents: String, line: usize, col: usize) -> Self {
+        let mut view = Self::new(path, contents);
+        let offset = text_ops::line_col_offset(view.textarea.text(), line, col);
+        view.textarea.set_cursor(offset);
+        view.recenter.set(Some(Recenter::Center));
+        view
+    }
+
+    /// Open `path`, pre-filling the buffer with `default_contents` when the
+    /// file does not exist yet. The template counts as unsaved unless
+    /// `defaults_saved` is set, so closing without saving asks first.
+    pub(crate) fn open(
+        path: PathBuf,
+        default_contents: Option<String>,
+        defaults_saved: bool,
+    ) -> io::Result<Self> {
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(Self::new(path, contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let mut view = Self::new(path, String::new());
+                if let Some(defaults) = default_contents {
+                    view.textarea.set_text(&defaults);
+                    view.textarea.set_cursor(0);
+                    if defaults_saved {
+                        view.last_saved_text = defaults;
+                    }
+                    view.dirty = view.textarea.text() != view.last_saved_text;
+                    view.resolve_text_direction();
+                    view.lint_indentation();
+                    view.sync_highlights();
+                }
+                Ok(view)
+            }
+            Err(err) => Err(err),
         }
-        changed
     }
 
-    fn save(&mut self) {
-        if let Some(parent) = self.path.parent() {
-            if let Err(err) = fs::create_dir_all(parent) {
-                self.status_message = Some(StatusMessage::error(format!(
-                    "Failed to save preferences: {err}"
-                )));
-                return;
+    /// Open `path` like [`Self::open`], except that a file over `max_bytes`
+    /// is loaded only from its last `max_bytes`, in append-only mode. Saves
+    /// then append the new lines instead of rewriting the file. `max_bytes`
+    /// is capped at [`MAX_EDITABLE_BYTES`], so the tail is never cut down to
+    /// a read-only preview.
+    pub(crate) fn open_windowed(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        let max_bytes = max_bytes.min(MAX_EDITABLE_BYTES as u64);
+        let (window, contents) = match window::read_tail(&path, max_bytes) {
+            Ok(tail) => tail,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Self::open(path, None, false);
             }
+            Err(err) => return Err(err),
+        };
+        let mut view = Self::new(path, contents);
+        if let Some(window) = window {
+            view.window = Some(window);
+            view.set_append_only(true);
+            view.status_message = Some(StatusMessage::info(format!(
+                "Large file: the first {} KB are not loaded; new lines are appended on save",
+                window.offset / 1024
+            )));
         }
+        Ok(view)
+    }
 
-        match fs::write(&self.path, self.textarea.text()) {
-            Ok(()) => {
-                self.last_saved_text = self.textarea.text().to_string();
+    /// Open the editor before its contents are available. It shows a spinner
+    /// and ignores edits until [`Self::finish_loading`] is called.
+    pub(crate) fn new_loading(path: PathBuf, frame_requester: FrameRequester) -> Self {
+        let mut view = Self::new(path, String::new());
+        view.load_state = LoadState::Loading {
+            since: Instant::now(),
+        };
+        frame_requester.schedule_frame();
+        view.frame_requester = Some(frame_requester);
+        view
+    }
+
+    /// Deliver the contents for a view opened with [`Self::new_loading`].
+    pub(crate) fn finish_loading(&mut self, result: Result<String, String>) {
+        if !matches!(self.load_state, LoadState::Loading { .. }) {
+            return;
+        }
+        match result {
+            Ok(contents) => {
+                let (file_format, contents) = FileFormat::decode(contents);
+                self.file_format = file_format;
+                let (contents, full_len) = preview_if_huge(contents);
+                self.textarea.set_text(&contents);
+                self.textarea.set_cursor(self.textarea.text().len());
+                self.session = SessionCounters::new(&contents);
+                self.last_saved_text = contents;
+                self.disk_stamp = DiskStamp::of(&self.path);
                 self.dirty = false;
-                self.status_message = Some(StatusMessage::info(format!(
-                    "Saved to {}",
-                    self.display_path
-                )));
-                self.confirm_discard = false;
+                self.load_state = LoadState::Ready;
+                self.resolve_text_direction();
+                self.lint_indentation();
+                self.sync_highlights();
+                self.set_preview(full_len);
             }
             Err(err) => {
+                self.load_state = LoadState::Failed;
                 self.status_message = Some(StatusMessage::error(format!(
-                    "Failed to save preferences: {err}"
+                    "Failed to load preferences: {err}"
                 )));
             }
         }
+        if let Some(frame_requester) = &self.frame_requester {
+            frame_requester.schedule_frame();
+        }
     }
 
-    fn request_close(&mut self) {
-        if self.dirty && !self.confirm_discard {
-            self.confirm_discard = true;
-            self.status_message = Some(StatusMessage::warning(
-                "Unsaved changes. Press Esc again to discard, or Ctrl+S to save.".to_string(),
-            ));
-        } else {
-            self.complete = true;
-        }
+    fn is_ready(&self) -> bool {
+        matches!(self.load_state, LoadState::Ready)
     }
 
-    fn status_span(&self) -> Span<'static> {
-        if let Some(message) = &self.status_message {
-            return message.as_span();
+    pub(crate) fn set_text_direction(&mut self, direction: TextDirection) {
+        self.text_direction = direction;
+        self.resolve_text_direction();
+    }
+
+    pub(crate) fn set_uuid_format(&mut self, format: UuidFormat) {
+        self.uuid_format = format;
+    }
+
+    /// Override the gutter characters picked from the locale.
+    pub(crate) fn set_gutter_glyphs(&mut self, glyphs: GutterGlyphs) {
+        self.glyphs = glyphs;
+    }
+
+    /// Replace the default colors, e.g. with ones readable on a light
+    /// terminal background.
+    pub(crate) fn set_theme(&mut self, theme: EditorTheme) {
+        self.theme = theme;
+        self.sync_highlights();
+    }
+
+    /// Request a terminal cursor shape while the editor is active. `None`
+    /// (the default) leaves the terminal's shape unchanged.
+    pub(crate) fn set_cursor_shape(&mut self, shape: Option<CursorShape>) {
+        self.cursor_shape = shape;
+    }
+
+    pub(crate) fn set_align_separator(&mut self, separator: Option<char>) {
+        self.align_separator = separator;
+    }
+
+    pub(crate) fn set_trim_blank_edges(&mut self, trim: bool) {
+        self.trim_blank_edges = trim;
+    }
+
+    /// Schedule redraws through `frame_requester`, e.g. when a status message
+    /// expires.
+    pub(crate) fn set_frame_requester(&mut self, frame_requester: FrameRequester) {
+        self.frame_requester = Some(frame_requester);
+    }
+
+    /// Ask the app through `app_event_tx` to run `$EDITOR`, which needs the
+    /// terminal to itself.
+    pub(crate) fn set_app_event_sender(&mut self, app_event_tx: AppEventSender) {
+        self.app_event_tx = Some(app_event_tx);
+    }
+
+    pub(crate) fn set_status_persistence(&mut self, persistence: StatusPersistence) {
+        self.status_persistence = persistence;
+    }
+
+    /// Report editor events to `tx`. Sends [`PreferencesEditorEvent::Opened`]
+    /// right away.
+    pub(crate) fn set_event_sender(&mut self, tx: UnboundedSender<PreferencesEditorEvent>) {
+        self.event_tx = Some(tx);
+        self.emit(PreferencesEditorEvent::Opened {
+            path: self.path.clone(),
+        });
+    }
+
+    fn emit(&self, event: PreferencesEditorEvent) {
+        if let Some(tx) = &self.event_tx {
+            // The host may have dropped its receiver; events are best-effort.
+            let _ = tx.send(event);
         }
+    }
 
-        if self.dirty {
-            "Unsaved changes — press Ctrl+S to save"
-                .to_string()
-                .yellow()
-        } else {
-            "All changes saved".to_string().green()
+    pub(crate) fn set_save_on_blur(&mut self, enabled: bool) {
+        self.save_on_blur = enabled;
+    }
+
+    /// Whether the key hint line starts out visible. Hiding it gives its rows
+    /// back to the editor.
+    pub(crate) fn set_show_hint(&mut self, show: bool) {
+        self.show_hint = show;
+    }
+
+    /// Scroll each search match to the middle of the editor rather than
+    /// just onto the screen.
+    pub(crate) fn set_center_on_search(&mut self, center: bool) {
+        self.center_on_search = center;
+    }
+
+    /// Show the buffer in two stacked viewports, or go back to one.
+    fn toggle_split(&mut self) {
+        if self.split.take().is_none() {
+            self.split = Some(SplitPane {
+                cursor: self.textarea.cursor(),
+                state: RefCell::new(*self.textarea_state.borrow()),
+                focus_bottom: false,
+            });
         }
     }
 
-    fn input_height(&self, width: u16) -> u16 {
-        let usable_width = width.saturating_sub(2);
-        let text_height = self.textarea.desired_height(usable_width).clamp(4, 18);
-        text_height.saturating_add(1)
+    /// Move focus to the other split pane, swapping in its cursor and scroll.
+    fn switch_split_focus(&mut self) {
+        let Some(split) = &mut self.split else {
+            return;
+        };
+        let cursor = self.textarea.cursor();
+        self.textarea.set_cursor(split.cursor);
+        split.cursor = cursor;
+        self.textarea_state.swap(&split.state);
+        split.focus_bottom = !split.focus_bottom;
+        self.selection_anchor = None;
     }
 
-    fn textarea_rect(&self, area: Rect) -> Option<Rect> {
-        if area.width < 4 {
-            return None;
+    /// Scroll the cursor row to the next Ctrl+L position when the view is
+    /// next drawn.
+    fn recenter(&mut self) {
+        let position = Recenter::next(self.recenter_cycle);
+        self.recenter_cycle = Some(position);
+        self.recenter.set(Some(position));
+    }
+
+    pub(crate) fn set_show_line_numbers(&mut self, show: bool) {
+        self.show_line_numbers = show;
+    }
+
+    pub(crate) fn set_relative_save_status(&mut self, enabled: bool) {
+        self.relative_save_status = enabled;
+    }
+
+    /// Indentation style Ctrl+X i normalizes mixed tabs and spaces to.
+    pub(crate) fn set_indent_style(&mut self, style: IndentStyle) {
+        self.indent_style = style;
+    }
+
+    /// Put `separator` between a selection and its copies when duplicating,
+    /// e.g. `"\n"` or `", "`.
+    pub(crate) fn set_duplicate_separator(&mut self, separator: String) {
+        self.duplicate_separator = separator;
+    }
+
+    /// Make Tab insert `spaces` spaces and Shift+Tab remove up to as many
+    /// from the start of the line.
+    pub(crate) fn set_tab_spaces(&mut self, spaces: usize) {
+        self.tab_spaces = spaces.max(1);
+    }
+
+    /// Hold back the dirty flag until the buffer has differed from the saved
+    /// file for `debounce`. Closing, quitting and save-on-blur still see
+    /// pending changes at once. `None` marks edits dirty immediately.
+    pub(crate) fn set_dirty_debounce(&mut self, debounce: Option<Duration>) {
+        self.dirty_debounce = debounce;
+    }
+
+    /// Save on its own once the buffer has gone `delay` without an edit.
+    /// The hint line says when this is on. `None` turns it off.
+    pub(crate) fn set_auto_save(&mut self, delay: Option<Duration>) {
+        self.auto_save = delay;
+    }
+
+    /// Promote a pending change to dirty once the debounce has passed, and
+    /// auto-save once typing has paused. The host calls this before each
+    /// draw; key handling calls it too.
+    pub(crate) fn tick(&mut self) {
+        let now = Instant::now();
+        if self.dirty_settled(now) {
+            self.settle_dirty();
         }
-        let text_area_height = self.input_height(area.width).saturating_sub(1);
-        if text_area_height == 0 {
-            return None;
+        if self.auto_save_due(now) {
+            self.auto_save();
+        }
+        self.finish_validation();
+        self.finish_shell_run();
+        if self.busy.is_some()
+            && let Some(frame_requester) = &self.frame_requester
+        {
+            frame_requester.schedule_frame_in(SPINNER_INTERVAL);
         }
-        Some(Rect {
-            x: area.x.saturating_add(2),
-            y: area.y.saturating_add(4),
-            width: area.width.saturating_sub(2),
-            height: text_area_height,
-        })
     }
-}
 
-impl BottomPaneView for PreferencesEditorView {
-    fn handle_key_event(&mut self, key_event: KeyEvent) {
-        let modifiers = key_event.modifiers;
-        if modifiers.contains(KeyModifiers::CONTROL) || modifiers.contains(KeyModifiers::SUPER) {
-            match key_event.code {
-                KeyCode::Char('s') | KeyCode::Char('S') => {
-                    self.save();
-                    return;
-                }
-                KeyCode::Char('c') | KeyCode::Char('C') => {
-                    self.request_close();
-                    return;
-                }
-                _ => {}
-            }
+    /// Show `message` with a spinner in the status line while an operation
+    /// runs, until [`Self::end_busy`].
+    pub(crate) fn begin_busy(&mut self, message: String) {
+        self.busy = Some(Busy::new(message));
+        self.status_message = None;
+        if let Some(frame_requester) = &self.frame_requester {
+            frame_requester.schedule_frame();
         }
-
-        self.apply_editor_change(|ta| ta.input(key_event));
     }
 
-    fn on_ctrl_c(&mut self) -> CancellationEvent {
-        self.request_close();
-        CancellationEvent::Handled
+    /// Stop the spinner and report how the operation went: `Ok` as a
+    /// success message, `Err` as an error.
+    pub(crate) fn end_busy(&mut self, result: Result<String, String>) {
+        self.busy = None;
+        self.status_message = Some(match result {
+            Ok(message) => StatusMessage::success(message),
+            Err(message) => StatusMessage::error(message),
+        });
+        if let Some(frame_requester) = &self.frame_requester {
+            frame_requester.schedule_frame();
+        }
     }
 
-    fn is_complete(&self) -> bool {
-        self.complete
+    /// Whether a held-back change has outlasted the debounce at `now`.
+    fn dirty_settled(&self, now: Instant) -> bool {
+        match (self.dirty_pending_since, self.dirty_debounce) {
+            (Some(since), Some(debounce)) => now.duration_since(since) >= debounce,
+            _ => false,
+        }
     }
 
-    fn desired_height(&self, width: u16) -> u16 {
-        self.input_height(width).saturating_add(5)
+    /// Whether typing paused long enough at `now` to auto-save.
+    fn auto_save_due(&self, now: Instant) -> bool {
+        match (self.last_edit_at, self.auto_save) {
+            (Some(edited), Some(delay)) => now.duration_since(edited) >= delay,
+            _ => false,
+        }
     }
 
-    fn render(&self, area: Rect, buf: &mut Buffer) {
-        if area.width == 0 || area.height == 0 {
+    /// Save without being asked. Anything that would need a confirmation,
+    /// like a file changed on disk or an emptied buffer, is left for an
+    /// explicit save. The buffer is left alone: the user may be mid-word
+    /// after a trailing space, so only the copy written to disk is capped,
+    /// stripped and normalized, and the formatter waits for an explicit save.
+    fn auto_save(&mut self) {
+        self.last_edit_at = None;
+        if !self.is_ready() || self.textarea.text() == self.last_saved_text {
+            return;
+        }
+        if self.changed_on_disk() {
+            self.status_message = Some(StatusMessage::warning(format!(
+                "File changed on disk — not auto-saving; press {} to overwrite",
+                self.save_key()
+            )));
+            return;
+        }
+        if self.read_only || self.preview_of.is_some() || self.would_blank_file() {
             return;
         }
+        self.validate_and_write(true);
+    }
 
-        // Title
-        let mut y = area.y;
-        Paragraph::new(Line::from(vec![gutter(), "Edit preferences.md".bold()])).render(
-            Rect {
-                x: area.x,
-                y,
-                width: area.width,
-                height: 1,
-            },
-            buf,
-        );
-        y = y.saturating_add(1);
+    /// Make `dirty` reflect the buffer right now, skipping any debounce.
+    fn settle_dirty(&mut self) {
+        self.dirty = self.textarea.text() != self.last_saved_text;
+        self.dirty_pending_since = None;
+    }
 
-        // Path line
-        Paragraph::new(Line::from(vec![
-            gutter(),
-            format!("Path: {}", self.display_path).dim(),
-        ]))
-        .render(
-            Rect {
-                x: area.x,
-                y,
-                width: area.width,
-                height: 1,
-            },
-            buf,
-        );
-        y = y.saturating_add(1);
+    /// Rows used by the spacer and key hint below the editor.
+    fn hint_height(&self) -> u16 {
+        if self.show_hint { 2 } else { 0 }
+    }
 
-        // Status line
-        Paragraph::new(Line::from(vec![gutter(), self.status_span()])).render(
-            Rect {
-                x: area.x,
-                y,
-                width: area.width,
-                height: 1,
-            },
-            buf,
-        );
-        y = y.saturating_add(1);
+    /// Replace the table used by the smart-punctuation-to-ASCII command.
+    pub(crate) fn set_ascii_replacements(&mut self, table: Vec<(char, String)>) {
+        self.ascii_replacements = table;
+    }
 
-        // Editor area with gutter
-        let input_height = self.input_height(area.width);
-        let input_area = Rect {
-            x: area.x,
-            y,
-            width: area.width,
-            height: input_height,
+    /// Replace the header inserted by the front-matter command.
+    pub(crate) fn set_front_matter_template(&mut self, template: String) {
+        self.front_matter_template = template;
+    }
+
+    /// Use `token` for line comments instead of the one implied by the file
+    /// extension.
+    pub(crate) fn set_comment_token(&mut self, token: String) {
+        self.comment_token = token;
+    }
+
+    /// Offer `keys` (e.g. from the preferences schema) as completions for the
+    /// first word typed on a line. An empty list turns completion off.
+    pub(crate) fn set_completion_keys(&mut self, keys: Vec<String>) {
+        self.completion_keys = keys;
+        self.key_completion = None;
+    }
+
+    /// Keys for the open completion popup: Tab accepts the highlighted key
+    /// and Up/Down move the highlight. Returns false for every other key,
+    /// which also closes the popup.
+    fn handle_completion_key(&mut self, key_event: KeyEvent) -> bool {
+        let Some(mut completion) = self.key_completion.take() else {
+            return false;
         };
-        if input_area.width >= 2 {
-            for row in 0..input_area.height {
-                Paragraph::new(Line::from(vec![gutter()])).render(
-                    Rect {
-                        x: input_area.x,
-                        y: input_area.y.saturating_add(row),
-                        width: 2,
-                        height: 1,
-                    },
-                    buf,
-                );
+        if !key_event.modifiers.is_empty() {
+            return false;
+        }
+        match key_event.code {
+            KeyCode::Tab => {
+                let key = completion.selected().to_string();
+                let range = completion.prefix;
+                let cursor = range.start + key.len();
+                self.apply_editor_change(|ta| {
+                    ta.replace_range(range, &key);
+                    ta.set_cursor(cursor);
+                });
             }
+            KeyCode::Up | KeyCode::Down => {
+                completion.step(key_event.code == KeyCode::Down);
+                self.key_completion = Some(completion);
+            }
+            _ => return false,
+        }
+        true
+    }
 
-            let text_area_height = input_area.height.saturating_sub(1);
-            if text_area_height > 0 {
-                if input_area.width > 2 {
-                    Clear.render(
-                        Rect {
-                            x: input_area.x.saturating_add(2),
-                            y: input_area.y,
-                            width: input_area.width.saturating_sub(2),
-                            height: 1,
-                        },
-                        buf,
-                    );
-                }
-                if let Some(rect) = self.textarea_rect(area) {
-                    let mut state = self.textarea_state.borrow_mut();
-                    StatefulWidgetRef::render_ref(&(&self.textarea), rect, buf, &mut state);
-                    if self.textarea.text().is_empty() {
-                        Paragraph::new(Line::from(vec![
-                            "Type your preferences and press Ctrl+S to save".dim(),
-                        ]))
-                        .render(rect, buf);
-                    }
+    /// Update the completion popup after `key_event` edited the buffer.
+    fn refresh_key_completion(&mut self, key_event: KeyEvent) {
+        let typed = matches!(key_event.code, KeyCode::Char(_) | KeyCode::Backspace)
+            && !key_event
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        self.key_completion = if typed && !self.completion_keys.is_empty() {
+            KeyCompletion::find(
+                self.textarea.text(),
+                self.textarea.cursor(),
+                &self.completion_keys,
+            )
+        } else {
+            None
+        };
+    }
+
+    /// Treat the file as an append-only log: the cursor is pinned to the end
+    /// and edits that would change the saved contents are refused.
+    pub(crate) fn set_append_only(&mut self, append_only: bool) {
+        self.append_only = append_only;
+        if append_only {
+            self.pin_cursor_to_end();
+        }
+    }
+
+    /// Refuse edits, as when the file turned out not to be writable. Set up
+    /// front by [`Self::new`]; hosts can force it for files they own.
+    pub(crate) fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Record whether the buffer is only the start of a file of `full_len`
+    /// bytes, making it read-only and saying so.
+    fn set_preview(&mut self, full_len: Option<usize>) {
+        match full_len {
+            Some(len) => {
+                self.read_only = true;
+                self.status_message = Some(StatusMessage::warning(format!(
+                    "{} is {} KB, too large to edit here; showing the first {} KB read-only",
+                    self.display_path,
+                    len / 1024,
+                    self.textarea.text().len() / 1024
+                )));
+            }
+            None if self.preview_of.is_some() => self.read_only = !is_writable(&self.path),
+            None => {}
+        }
+        self.preview_of = full_len;
+    }
+
+    fn pin_cursor_to_end(&mut self) {
+        self.selection_anchor = None;
+        self.textarea.set_cursor(self.textarea.text().len());
+    }
+
+    /// Let Alt+X run a shell command and insert its output. Off by default
+    /// since it executes arbitrary commands.
+    pub(crate) fn set_allow_shell_commands(&mut self, allow: bool) {
+        self.allow_shell_commands = allow;
+    }
+
+    /// Let Ctrl+O open the file referenced on the cursor line. The first
+    /// capture group of `pattern` is the path, read relative to this file's
+    /// directory, e.g. `include:\s*(\S+)`.
+    pub(crate) fn set_include_pattern(&mut self, pattern: Option<Regex>) {
+        self.include_pattern = pattern;
+    }
+
+    /// Link Alt+I to the schema or documentation for this file.
+    pub(crate) fn set_docs_url(&mut self, url: Option<String>) {
+        self.docs_url = url;
+    }
+
+    pub(crate) fn set_hard_wrap_width(&mut self, width: usize) {
+        self.hard_wrap_width = width.max(1);
+    }
+
+    /// Keep discarded buffers in `path` instead of the per-process default.
+    pub(crate) fn set_trash_path(&mut self, path: PathBuf) {
+        self.trash = Trash::at(path);
+    }
+
+    /// Drop `comment_token` comments, whole-line and trailing, from the buffer
+    /// on every save.
+    pub(crate) fn set_strip_comments_on_save(&mut self, strip: bool) {
+        self.strip_comments_on_save = strip;
+    }
+
+    /// Trim trailing spaces and tabs and end the buffer with exactly one
+    /// newline on every save. On by default.
+    pub(crate) fn set_normalize_whitespace_on_save(&mut self, normalize: bool) {
+        self.normalize_whitespace_on_save = normalize;
+    }
+
+    /// Keep at most `max` lines on every save, dropping the excess from the
+    /// end `drop` names. `None` (the default) never trims.
+    pub(crate) fn set_max_lines(&mut self, max: Option<usize>, drop: LineCapDrop) {
+        self.max_lines = max.map(|max| (max.max(1), drop));
+    }
+
+    /// Run `formatter` over the buffer on every save.
+    pub(crate) fn set_format_on_save(&mut self, formatter: Option<Formatter>) {
+        self.formatter = formatter;
+    }
+
+    /// Call `hook` after each successful save, alongside the
+    /// [`PreferencesEditorEvent::Saved`] event. Saves to a file opened with
+    /// [`Self::open_windowed`] only append, so they skip the hook; hosts that
+    /// need to know can watch for the event and read the file themselves.
+    pub(crate) fn set_on_save(&mut self, hook: Option<SaveHook>) {
+        self.on_save = hook;
+    }
+
+    /// When formatting would change more than `threshold` lines, preview the
+    /// diff and ask before applying it. `None` applies formatting silently.
+    pub(crate) fn set_format_confirm_threshold(&mut self, threshold: Option<usize>) {
+        self.format_confirm_threshold = threshold;
+    }
+
+    /// Check the buffer with `validator` now and before every save. A save
+    /// with problems is held back until Ctrl+S is pressed again.
+    pub(crate) fn set_validator(&mut self, validator: Option<Validator>) {
+        self.validator = validator;
+        let errors = match &self.validator {
+            Some(validator) => validator(self.textarea.text()),
+            None => Vec::new(),
+        };
+        self.set_validation_errors(errors);
+    }
+
+    /// Let Ctrl+X p show the buffer as `parser` reads it.
+    pub(crate) fn set_parse_preview(&mut self, parser: Option<ParsePreview>) {
+        self.parse_preview = parser;
+    }
+
+    /// Validate on a worker thread when saving so a slow validator does not
+    /// freeze the UI. The result is picked up on the next tick, which the
+    /// worker schedules; without a frame requester the validator runs
+    /// inline.
+    pub(crate) fn set_validate_in_background(&mut self, enabled: bool) {
+        self.validate_in_background = enabled;
+    }
+
+    fn set_validation_errors(&mut self, errors: Vec<ValidationError>) {
+        self.validation_errors = errors;
+        if !self.validation_errors.is_empty() {
+            self.emit(PreferencesEditorEvent::ValidationFailed {
+                errors: self.validation_errors.len(),
+            });
+        }
+    }
+
+    /// Validate the buffer, then write it if it is clean or the user already
+    /// chose to save despite problems. An auto-save (`auto`) always validates.
+    fn validate_and_write(&mut self, auto: bool) {
+        let Some(validator) = self.validator.clone() else {
+            self.write_buffer(auto);
+            return;
+        };
+        if self.confirm_invalid_save && !auto {
+            self.write_buffer(auto);
+            return;
+        }
+        if self.validate_in_background
+            && let Some(frame_requester) = self.frame_requester.clone()
+        {
+            let text = self.textarea.text().to_string();
+            let (tx, result) = mpsc::channel();
+            self.validation_run = Some(ValidationRun {
+                text: text.clone(),
+                since: Instant::now(),
+                auto,
+                result,
+            });
+            self.status_message = None;
+            std::thread::spawn(move || {
+                // A cancelled run has dropped the receiver.
+                if tx.send(validator(&text)).is_ok() {
+                    frame_requester.schedule_frame();
                 }
+            });
+            if let Some(frame_requester) = &self.frame_requester {
+                frame_requester.schedule_frame();
             }
+            return;
         }
-        y = y.saturating_add(input_area.height);
+        let errors = validator(self.textarea.text());
+        self.finish_validation_with(errors, auto);
+    }
 
-        // Blank spacer before hint
-        if y < area.y.saturating_add(area.height) {
-            Clear.render(
-                Rect {
-                    x: area.x,
-                    y,
-                    width: area.width,
-                    height: 1,
-                },
-                buf,
-            );
+    /// Apply the background validation's result once it has arrived. A
+    /// result for a buffer that has since changed is dropped.
+    fn finish_validation(&mut self) {
+        let received = match &self.validation_run {
+            Some(run) => run.result.try_recv(),
+            None => return,
+        };
+        let errors = match received {
+            Ok(errors) => errors,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.validation_run = None;
+                self.status_message = Some(StatusMessage::error(
+                    "Validation failed; nothing was saved".to_string(),
+                ));
+                return;
+            }
+        };
+        let Some(run) = self.validation_run.take() else {
+            return;
+        };
+        if run.text != self.textarea.text() {
+            self.status_message = Some(StatusMessage::info(format!(
+                "The buffer changed while validating; press {} to save",
+                self.save_key()
+            )));
+        } else {
+            self.finish_validation_with(errors, run.auto);
+        }
+        if let Some(frame_requester) = &self.frame_requester {
+            frame_requester.schedule_frame();
         }
+    }
 
-        let hint_y = y.saturating_add(1);
-        if hint_y < area.y.saturating_add(area.height) {
-            Paragraph::new(Line::from(vec![
-                gutter(),
-                "Ctrl+S save · Esc close".to_string().dim(),
-            ]))
-            .render(
-                Rect {
-                    x: area.x,
-                    y: hint_y,
-                    width: area.width,
-                    height: 1,
-                },
-                buf,
-            );
+    fn finish_validation_with(&mut self, errors: Vec<ValidationError>, auto: bool) {
+        self.set_validation_errors(errors);
+        let problems = self.validation_errors.len();
+        if problems == 0 {
+            self.write_buffer(auto);
+            return;
         }
+        self.format_error_on_save = None;
+        self.confirm_invalid_save = true;
+        let noun = if problems == 1 { "problem" } else { "problems" };
+        let again = if auto { "" } else { " again" };
+        self.status_message = Some(StatusMessage::warning(format!(
+            "Not saved: {problems} validation {noun}. Press {}{again} to save anyway.",
+            self.save_key()
+        )));
     }
 
-    fn handle_paste(&mut self, pasted: String) -> bool {
-        self.apply_editor_change(|ta| ta.insert_str(&pasted))
+    /// Stop waiting on a background validation; the save it was guarding
+    /// does not happen.
+    fn cancel_validation(&mut self) -> bool {
+        if self.validation_run.take().is_none() {
+            return false;
+        }
+        self.format_error_on_save = None;
+        self.status_message = Some(StatusMessage::info(
+            "Validation cancelled; nothing was saved".to_string(),
+        ));
+        true
     }
 
-    fn cursor_pos(&self, area: Rect) -> Option<(u16, u16)> {
-        let rect = self.textarea_rect(area)?;
-        let state = *self.textarea_state.borrow();
-        self.textarea.cursor_pos_with_state(rect, state)
+    /// 1-based line number of the cursor.
+    fn cursor_line(&self) -> usize {
+        self.textarea.text()[..self.textarea.cursor()]
+            .matches('\n')
+            .count()
+            + 1
     }
-}
 
-impl StatusMessage {
-    fn info(text: String) -> Self {
-        Self {
-            text,
-            kind: StatusKind::Info,
+    fn validation_error_at_cursor(&self) -> Option<&ValidationError> {
+        let line = self.cursor_line();
+        self.validation_errors
+            .iter()
+            .find(|error| error.line == line)
+    }
+
+    /// Copy the validation error on the cursor line. When that line has
+    /// none, jump to the next line that does (wrapping) and copy its error.
+    fn copy_validation_error(&mut self) {
+        let line = self.cursor_line();
+        let error = self
+            .validation_error_at_cursor()
+            .or_else(|| {
+                self.validation_errors
+                    .iter()
+                    .filter(|error| error.line > line)
+                    .min_by_key(|error| error.line)
+            })
+            .or_else(|| self.validation_errors.iter().min_by_key(|error| error.line))
+            .cloned();
+        let Some(error) = error else {
+            self.status_message = Some(StatusMessage::info(
+                "No validation problems to copy".to_string(),
+            ));
+            return;
+        };
+        if error.line != line {
+            let text = self.textarea.text();
+            let start = text
+                .split_inclusive('\n')
+                .take(error.line - 1)
+                .map(str::len)
+                .sum::<usize>()
+                .min(text.len());
+            self.selection_anchor = None;
+            self.textarea.set_cursor(start);
         }
+        self.status_message = Some(match clipboard_copy::copy_text(&error.message) {
+            Ok(()) => {
+                StatusMessage::success(format!("Copied line {}: {}", error.line, error.message))
+            }
+            Err(err) => StatusMessage::error(format!("Failed to copy the error: {err}")),
+        });
     }
 
-    fn error(text: String) -> Self {
-        Self {
-            text,
-            kind: StatusKind::Error,
+    /// Wrap long lines at the editor width (the default), or cut them off at
+    /// the right edge. Ctrl+Shift+W toggles it.
+    pub(crate) fn set_soft_wrap(&mut self, wrap: bool) {
+        self.textarea.set_wrap(wrap);
+    }
+
+    /// Whether typing `(`, `[`, `{`, a backtick or `"` also inserts its
+    /// closer. On by default.
+    pub(crate) fn set_auto_pair(&mut self, enabled: bool) {
+        self.auto_pair = enabled;
+    }
+
+    /// Capture the mouse for clicks and scrolling. Off by default, which
+    /// leaves the terminal's own text selection working.
+    pub(crate) fn set_mouse_support(&mut self, enabled: bool) {
+        self.mouse = enabled;
+    }
+
+    pub(crate) fn set_ctrl_c_behavior(&mut self, behavior: CtrlCBehavior) {
+        self.ctrl_c_behavior = behavior;
+    }
+
+    /// Rebind save, close, search and the other main commands, e.g. when the
+    /// terminal swallows Ctrl+S.
+    pub(crate) fn set_keymap(&mut self, keymap: EditorKeymap) {
+        self.keymap = keymap;
+    }
+
+    /// What the save key is called in messages.
+    fn save_key(&self) -> String {
+        self.keymap.save.label()
+    }
+
+    fn resolve_text_direction(&mut self) {
+        let rtl = match self.text_direction {
+            TextDirection::Auto => is_dominantly_rtl(self.textarea.text()),
+            TextDirection::LeftToRight => false,
+            TextDirection::RightToLeft => true,
+        };
+        self.textarea.set_rtl(rtl);
+    }
+
+    fn apply_editor_change<F: FnOnce(&mut TextArea)>(&mut self, edit: F) -> bool {
+        if self.read_only {
+            self.status_message = Some(StatusMessage::warning(format!(
+                "{} is read-only",
+                self.display_path
+            )));
+            return false;
+        }
+        let before = self.textarea.text().to_string();
+        let before_cursor = self.textarea.cursor();
+        edit(&mut self.textarea);
+        if self.append_only && !self.textarea.text().starts_with(&self.last_saved_text) {
+            self.textarea.set_text(&before);
+            self.pin_cursor_to_end();
+            self.status_message = Some(StatusMessage::warning(
+                "Append-only: saved entries cannot be changed".to_string(),
+            ));
+            return false;
+        }
+        let changed = self.textarea.text() != before;
+        if changed {
+            self.redo_stack.clear();
+            self.after_edit(&before);
+            self.undo_stack.push_back((before, before_cursor));
+            if self.undo_stack.len() > UNDO_LIMIT {
+                self.undo_stack.pop_front();
+            }
         }
+        changed
     }
 
-    fn warning(text: String) -> Self {
-        Self {
-            text,
-            kind: StatusKind::Warning,
+    /// Step back to the buffer before the last edit.
+    fn undo(&mut self) {
+        let Some(snapshot) = self.undo_stack.pop_back() else {
+            self.status_message = Some(StatusMessage::info("Nothing to undo".to_string()));
+            return;
+        };
+        if self.append_only && !snapshot.0.starts_with(&self.last_saved_text) {
+            self.undo_stack.push_back(snapshot);
+            self.status_message = Some(StatusMessage::warning(
+                "Append-only: saved entries cannot be changed".to_string(),
+            ));
+            return;
         }
+        let current = self.restore_snapshot(snapshot);
+        self.redo_stack.push(current);
+    }
+
+    /// Reapply the edit most recently undone.
+    fn redo(&mut self) {
+        let Some(snapshot) = self.redo_stack.pop() else {
+            self.status_message = Some(StatusMessage::info("Nothing to redo".to_string()));
+            return;
+        };
+        let current = self.restore_snapshot(snapshot);
+        self.undo_stack.push_back(current);
+    }
+
+    /// Put `(text, cursor)` back in the text area and return the state it
+    /// replaced.
+    fn restore_snapshot(&mut self, (text, cursor): (String, usize)) -> (String, usize) {
+        let current = (self.textarea.text().to_string(), self.textarea.cursor());
+        self.selection_anchor = None;
+        self.textarea.set_text(&text);
+        self.textarea.set_cursor(cursor);
+        self.after_edit(&current.0);
+        current
     }
 
-    fn as_span(&self) -> Span<'static> {
-        match self.kind {
-            StatusKind::Info => self.text.clone().green(),
-            StatusKind::Error => self.text.clone().red(),
-            StatusKind::Warning => self.text.clone().yellow(),
+    /// Bookkeeping after the buffer changed from `before`.
+    fn after_edit(&mut self, before: &str) {
+        if let Some(split) = &mut self.split {
+            split.cursor = text_ops::shift_position(before, self.textarea.text(), split.cursor);
+        }
+        self.lint_indentation();
+        self.session.record_edit(before, self.textarea.text());
+        if let Some(delay) = self.auto_save {
+            self.last_edit_at = Some(Instant::now());
+            if let Some(frame_requester) = &self.frame_requester {
+                frame_requester.schedule_frame_in(delay);
+            }
+        }
+        let differs = self.textarea.text() != self.last_saved_text;
+        match self.dirty_debounce {
+            Some(debounce) if differs && !self.dirty => {
+                if self.dirty_pending_since.is_none() {
+                    self.dirty_pending_since = Some(Instant::now());
+                    if let Some(frame_requester) = &self.frame_requester {
+                        frame_requester.schedule_frame_in(debounce);
+                    }
+                }
+            }
+            _ => self.settle_dirty(),
+        }
+        self.status_message = None;
+        self.confirm_empty_save = false;
+        self.confirm_invalid_save = false;
+        self.confirm_external_overwrite = false;
+        self.confirm_reload = false;
+        self.confirm_follow_include = false;
+        if self.text_direction == TextDirection::Auto {
+            self.resolve_text_direction();
         }
+        if !self.folded.is_empty() {
+            self.sync_folds();
+        }
+        self.emit(PreferencesEditorEvent::Edited);
     }
-}
 
-fn gutter() -> Span<'static> {
-    "▌ ".cyan()
+    fn save(&mut self) {
+        if self.preview_of.is_some() {
+            self.status_message = Some(StatusMessage::warning(
+                "Only the start of this file is loaded; it cannot be saved from here".to_string(),
+            ));
+            return;
+        }
+        if self.read_only {
+            self.status_message = Some(StatusMessage::warning(format!(
+                "{} is read-only — press {} to save a copy",
+                self.display_path,
+                self.keymap.save_as.label()
+            )));
+            return;
+        }
+        if self.changed_on_disk() && !self.confirm_external_overwrite {
+            self.confirm_external_overwrite = true;
+            self.status_message = Some(StatusMessage::warning(format!(
+                "File changed on disk — press {} again to overwrite",
+                self.save_key()
+            )));
+            return;
+        }
+        self.confirm_external_overwrite = false;
+
+        if self.would_blank_file() && !self.confirm_empty_save {
+            self.confirm_empty_save = true;
+            self.status_message = Some(StatusMessage::warning(format!(
+                "The buffer is empty. Press {} again to overwrite {} anyway.",
+                self.save_key(),
+                self.display_path
+            )));
+            return;
+        }
+        self.confirm_empty_save = false;
+
+        let (saved_form, dropped) = self.saved_form(self.textarea.text());
+        if saved_form != self.textarea.text() {
+            self.replace_lines(0..self.textarea.text().len(), &saved_form);
+        }
+        self.trimmed_on_save = dropped;
+        self.format_error_on_save = None;
+        if let Some(formatter) = &self.formatter {
+            match formatter(self.textarea.text()) {
+                Ok(formatted) if formatted != self.textarea.text() => {
+                    let changed = ChangeSummary::between(self.textarea.text(), &formatted)
+                        .map_or(0, |summary| summary.added.max(summary.removed));
+                    if self
+                        .format_confirm_threshold
+                        .is_some_and(|threshold| changed > threshold)
+                    {
+                        let lines = if changed == 1 { "line" } else { "lines" };
+                        self.status_message = Some(StatusMessage::warning(format!(
+                            "Formatting changes {changed} {lines}. Enter applies and saves; Esc saves unformatted."
+                        )));
+                        self.pending_format = Some(formatted);
+                        return;
+                    }
+                    self.replace_lines(0..self.textarea.text().len(), &formatted);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    // The formatter failing already gets the user's attention;
+                    // save what they have, validated like any other save,
+                    // rather than stack another prompt.
+                    self.format_error_on_save = Some(err);
+                    self.validate_and_write(false);
+                    return;
+                }
+            }
+        }
+        self.validate_and_write(false);
+    }
+
+    /// Answer the format-on-save confirmation: write either the formatted or
+    /// the original buffer.
+    fn resolve_pending_format(&mut self, accept: bool) {
+        let Some(formatted) = self.pending_format.take() else {
+            return;
+        };
+        if accept {
+            self.replace_lines(0..self.textarea.text().len(), &formatted);
+        }
+        self.validate_and_write(false);
+    }
+
+    /// `text` as saving writes it: capped to the line limit, with comments
+    /// stripped and whitespace normalized as configured. Also returns how
+    /// many lines the cap dropped.
+    fn saved_form(&self, text: &str) -> (String, usize) {
+        let (mut text, dropped) = match self.max_lines {
+            Some((max, drop)) => text_ops::cap_lines(text, max, drop),
+            None => (text.to_string(), 0),
+        };
+        if self.strip_comments_on_save {
+            text = text_ops::strip_comments(&text, &self.comment_token);
+        }
+        if self.normalize_whitespace_on_save {
+            text = text_ops::normalize_whitespace(&text);
+        }
+        (text, dropped)
+    }
+
+    /// Write the buffer to disk. An auto-save writes its saved form instead,
+    /// so the buffer itself is never rewritten behind the user's back;
+    /// appends to a windowed file stay verbatim, since trimming the end of
+    /// one append would glue it to the next.
+    fn write_buffer(&mut self, auto: bool) {
+        let format_error = self.format_error_on_save.take();
+        // Only what follows the loaded tail is new; the rest of the file was
+        // never in the buffer.
+        let new_text = match self.window {
+            Some(_) => self
+                .textarea
+                .text()
+                .strip_prefix(self.last_saved_text.as_str()),
+            None => Some(self.textarea.text()),
+        };
+        let Some(new_text) = new_text else {
+            self.report_save_error(
+                "only new lines can be saved to a partly loaded file".to_string(),
+            );
+            return;
+        };
+        let contents = if auto && self.window.is_none() {
+            let (saved_form, dropped) = self.saved_form(new_text);
+            self.trimmed_on_save = dropped;
+            saved_form
+        } else {
+            new_text.to_string()
+        };
+        if let Some(parent) = self.path.parent()
+            && let Err(err) = fs::create_dir_all(parent)
+        {
+            self.report_save_error(err.to_string());
+            return;
+        }
+
+        // Appending never touches what is already in the file, and copying a
+        // file too large to load on every save would defeat the window.
+        let backup_error = match self.window {
+            Some(_) => None,
+            None => back_up(&self.path).err(),
+        };
+        let encoded = self.file_format.encode(&contents);
+        let written = match self.window {
+            Some(_) => window::append(&self.path, &encoded),
+            None => write_atomically(&self.path, &encoded),
+        };
+        match written {
+            Ok(()) => {
+                self.last_saved_text = self.textarea.text().to_string();
+                self.disk_stamp = DiskStamp::of(&self.path);
+                self.dirty = false;
+                self.dirty_pending_since = None;
+                self.sync_highlights();
+                let trimmed = std::mem::take(&mut self.trimmed_on_save);
+                let note = match (trimmed, self.max_lines) {
+                    (0, _) | (_, None) => String::new(),
+                    (trimmed, Some((max, drop))) => {
+                        let noun = if trimmed == 1 { "line" } else { "lines" };
+                        let end = match drop {
+                            LineCapDrop::Oldest => "oldest",
+                            LineCapDrop::Newest => "newest",
+                        };
+                        format!(" (dropped {trimmed} {end} {noun} to stay at {max})")
+                    }
+                };
+                self.status_message = Some(StatusMessage::success(format!(
+                    "Saved to {}{note}",
+                    self.display_path
+                )));
+                self.notify_saved(&contents);
+                self.confirm_invalid_save = false;
+                self.session.saves += 1;
+                self.last_saved_at = Some(Instant::now());
+                let problems = self.validation_errors.len();
+                if let Some(err) = backup_error {
+                    self.status_message = Some(StatusMessage::warning(format!(
+                        "Saved to {}, but the previous version was not backed up: {err}",
+                        self.display_path
+                    )));
+                } else if let Some(err) = format_error {
+                    self.status_message = Some(StatusMessage::warning(format!(
+                        "Saved without formatting: {err}"
+                    )));
+                } else if problems > 0 {
+                    let noun = if problems == 1 { "problem" } else { "problems" };
+                    self.status_message = Some(StatusMessage::warning(format!(
+                        "Saved to {} with {problems} validation {noun}",
+                        self.display_path
+                    )));
+                } else if is_markdown(&self.path)
+                    && let Some((line, problem)) = text_ops::markdown_problem(self.textarea.text())
+                {
+                    self.status_message = Some(StatusMessage::warning(format!(
+                        "Saved to {}, but line {line}: {problem}",
+                        self.display_path
+                    )));
+                }
+            }
+            Err(err) => self.report_save_error(err.to_string()),
+        }
+    }
+
+    /// Save to `input`, read relative to this file's directory, asking first
+    /// when that would replace another file.
+    fn request_save_as(&mut self, input: &str) {
+        if input.is_empty() {
+            return;
+        }
+        if self.window.is_some() || self.preview_of.is_some() {
+            self.status_message = Some(StatusMessage::warning(
+                "Save as is unavailable while only part of the file is loaded".to_string(),
+            ));
+            return;
+        }
+        let path = includes::resolve(&self.path, input);
+        if path != self.path && path.exists() {
+            self.status_message = Some(StatusMessage::warning(format!(
+                "{} already exists. Press Enter to overwrite it.",
+                path.display()
+            )));
+            self.confirm_save_as = Some(path);
+            return;
+        }
+        self.save_as(path);
+    }
+
+    /// Write the buffer to `path` and keep editing it there.
+    fn save_as(&mut self, path: PathBuf) {
+        let written = match path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|()| write_atomically(&path, &self.file_format.encode(self.textarea.text())));
+        if let Err(err) = written {
+            self.report_save_error(err.to_string());
+            return;
+        }
+        self.disk_stamp = DiskStamp::of(&path);
+        self.display_path = path.display().to_string();
+        self.path = path;
+        self.read_only = false;
+        self.last_saved_text = self.textarea.text().to_string();
+        self.dirty = false;
+        self.dirty_pending_since = None;
+        self.confirm_external_overwrite = false;
+        self.session.saves += 1;
+        self.last_saved_at = Some(Instant::now());
+        self.sync_highlights();
+        self.status_message = Some(StatusMessage::success(format!(
+            "Saved to {}",
+            self.display_path
+        )));
+        let contents = self.last_saved_text.clone();
+        self.notify_saved(&contents);
+    }
+
+    /// Replace the buffer with the file as it is on disk now. Unsaved changes
+    /// need a second Ctrl+R; a failed read leaves the buffer alone.
+    fn reload_from_disk(&mut self) {
+        self.settle_dirty();
+        if self.dirty && !self.confirm_reload {
+            self.confirm_reload = true;
+            self.status_message = Some(StatusMessage::warning(
+                "Reloading discards unsaved changes. Press Ctrl+R again to reload.".to_string(),
+            ));
+            return;
+        }
+        self.confirm_reload = false;
+        let read = match self.window {
+            Some(window) => {
+                window::read_tail(&self.path, window.max_bytes).map(|(tail, contents)| {
+                    self.window = tail;
+                    contents
+                })
+            }
+            None => fs::read_to_string(&self.path),
+        };
+        let contents = match read {
+            Ok(contents) => contents,
+            Err(err) => {
+                self.status_message = Some(StatusMessage::error(format!(
+                    "Failed to reload {}: {err}",
+                    self.display_path
+                )));
+                return;
+            }
+        };
+        if self.dirty {
+            self.move_to_trash();
+        }
+        let (file_format, contents) = FileFormat::decode(contents);
+        self.file_format = file_format;
+        let (contents, full_len) = preview_if_huge(contents);
+        let before = self.textarea.text().to_string();
+        let cursor = text_ops::shift_position(&before, &contents, self.textarea.cursor());
+        self.textarea.set_text(&contents);
+        self.textarea.set_cursor(cursor);
+        if let Some(split) = &mut self.split {
+            split.cursor = text_ops::shift_position(&before, &contents, split.cursor);
+        }
+        self.last_saved_text = contents;
+        self.disk_stamp = DiskStamp::of(&self.path);
+        self.dirty = false;
+        self.dirty_pending_since = None;
+        self.confirm_external_overwrite = false;
+        self.selection_anchor = None;
+        self.solo = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        if !self.folded.is_empty() {
+            self.sync_folds();
+        }
+        self.resolve_text_direction();
+        self.lint_indentation();
+        self.status_message = Some(StatusMessage::success(format!(
+            "Reloaded {}",
+            self.display_path
+        )));
+        self.set_preview(full_len);
+    }
+
+    /// Open the file referenced on the cursor line in place of this one,
+    /// asking first when there are unsaved changes.
+    fn follow_include(&mut self) {
+        let Some(pattern) = &self.include_pattern else {
+            self.status_message = Some(StatusMessage::info(
+                "No include pattern configured for this editor".to_string(),
+            ));
+            return;
+        };
+        let Some(reference) =
+            includes::include_at(self.textarea.text(), self.textarea.cursor(), pattern)
+        else {
+            self.status_message = Some(StatusMessage::info(
+                "No file reference on this line".to_string(),
+            ));
+            return;
+        };
+        let path = includes::resolve(&self.path, reference);
+        let display_path = path.display().to_string();
+        if !path.is_file() {
+            self.status_message = Some(StatusMessage::warning(format!(
+                "{display_path} does not exist"
+            )));
+            return;
+        }
+        self.settle_dirty();
+        if self.dirty && !self.confirm_follow_include {
+            self.confirm_follow_include = true;
+            self.status_message = Some(StatusMessage::warning(format!(
+                "Opening {display_path} discards unsaved changes. Press Ctrl+O again to open it."
+            )));
+            return;
+        }
+        self.confirm_follow_include = false;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                self.status_message = Some(StatusMessage::error(format!(
+                    "Failed to open {display_path}: {err}"
+                )));
+                return;
+            }
+        };
+        if self.dirty {
+            self.move_to_trash();
+        }
+        let (file_format, contents) = FileFormat::decode(contents);
+        self.file_format = file_format;
+        let (contents, full_len) = preview_if_huge(contents);
+        self.textarea.set_text(&contents);
+        self.textarea.set_cursor(0);
+        if let Some(split) = &mut self.split {
+            split.cursor = 0;
+        }
+        self.disk_stamp = DiskStamp::of(&path);
+        self.read_only = !is_writable(&path);
+        // The window, and the append-only mode that came with it, belonged
+        // to the file being left.
+        if self.window.take().is_some() {
+            self.append_only = false;
+        }
+        self.path = path;
+        self.display_path = display_path;
+        self.last_saved_text = contents;
+        self.dirty = false;
+        self.dirty_pending_since = None;
+        self.confirm_external_overwrite = false;
+        self.confirm_reload = false;
+        self.selection_anchor = None;
+        self.solo = None;
+        self.occurrences = None;
+        self.folded.clear();
+        self.sync_folds();
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.resolve_text_direction();
+        self.lint_indentation();
+        self.status_message = Some(StatusMessage::success(format!(
+            "Opened {}",
+            self.display_path
+        )));
+        self.set_preview(full_len);
+        self.emit(PreferencesEditorEvent::Opened {
+            path: self.path.clone(),
+        });
+    }
+
+    /// True when the file on disk is no longer the one loaded or last saved.
+    /// A file that has since been deleted is not a conflict.
+    fn changed_on_disk(&self) -> bool {
+        DiskStamp::of(&self.path).is_some_and(|now| Some(now) != self.disk_stamp)
+    }
+
+    /// True when saving would replace non-blank contents with a blank buffer.
+    fn would_blank_file(&self) -> bool {
+        self.textarea.text().trim().is_empty() && !self.last_saved_text.trim().is_empty()
+    }
+
+    /// Tell the host `contents` was just written to `path`.
+    fn notify_saved(&mut self, contents: &str) {
+        self.emit(PreferencesEditorEvent::Saved {
+            path: self.path.clone(),
+        });
+        // A windowed save wrote only the lines it appended.
+        if self.window.is_none()
+            && let Some(hook) = &mut self.on_save
+        {
+            hook(&self.path, contents);
+        }
+    }
+
+    fn report_save_error(&mut self, error: String) {
+        self.status_message = Some(StatusMessage::error(format!(
+            "Failed to save preferences: {error}"
+        )));
+        self.emit(PreferencesEditorEvent::SaveFailed { error });
+    }
+
+    fn selection_range(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.textarea.cursor();
+        (anchor != cursor).then(|| anchor.min(cursor)..anchor.max(cursor))
+    }
+
+    /// Select the whole buffer, leaving the cursor at the end.
+    fn select_all(&mut self) {
+        self.selection_anchor = Some(0);
+        self.textarea.set_cursor(self.textarea.text().len());
+    }
+
+    /// Replace the selection with what `key_event` types: a character, or
+    /// nothing for Backspace/Delete. Returns whether the key was used.
+    fn replace_selection(&mut self, key_event: KeyEvent) -> bool {
+        let Some(range) = self.selection_range() else {
+            return false;
+        };
+        let modifiers = key_event.modifiers;
+        let replacement = match key_event.code {
+            KeyCode::Char(ch) if (modifiers - KeyModifiers::SHIFT).is_empty() => ch.to_string(),
+            KeyCode::Backspace | KeyCode::Delete if modifiers.is_empty() => String::new(),
+            _ => return false,
+        };
+        self.selection_anchor = None;
+        self.apply_editor_change(|ta| {
+            ta.replace_range(range.clone(), &replacement);
+            ta.set_cursor(range.start + replacement.len());
+        });
+        true
+    }
+
+    /// Move the cursor with `motion` while keeping the selection anchored.
+    fn extend_selection(&mut self, motion: KeyEvent) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.textarea.cursor());
+        }
+        self.textarea.input(motion);
+    }
+
+    /// Highlight merge conflicts and, on top of them, the selection.
+    fn sync_highlights(&mut self) {
+        let text = self.textarea.text();
+        let mut highlights = Vec::new();
+        if let Some(color) = self.theme.current_line {
+            let cursor = self.textarea.cursor();
+            let start = text_ops::line_start(text, cursor);
+            let end = text_ops::line_end(text, cursor);
+            highlights.push((start..end, Style::default().bg(color)));
+        }
+        if self.focus_unsaved {
+            let changed = changed_lines(&self.last_saved_text, text);
+            let mut start = 0;
+            for (index, line) in text.split_inclusive('\n').enumerate() {
+                if !changed.contains(&index) {
+                    highlights.push((start..start + line.len(), Style::default().dim()));
+                }
+                start += line.len();
+            }
+        }
+        for range in &self.mixed_indent {
+            highlights.push((range.clone(), Style::default().bg(self.theme.warning)));
+        }
+        for conflict in conflicts::conflicts(text) {
+            for marker in conflict.marker_lines(text) {
+                highlights.push((marker, Style::default().fg(self.theme.warning).dim()));
+            }
+            highlights.push((
+                conflict.ours.clone(),
+                Style::default().fg(self.theme.success),
+            ));
+            highlights.push((
+                conflict.theirs.clone(),
+                Style::default().fg(self.theme.accent),
+            ));
+        }
+        if let Some(occurrences) = &self.occurrences {
+            for range in occurrences.ranges() {
+                highlights.push((range, Style::default().fg(self.theme.accent).underlined()));
+            }
+        }
+        if let Some(range) = self.selection_range() {
+            highlights.push((range, Style::default().add_modifier(Modifier::REVERSED)));
+        }
+        self.textarea.set_highlights(highlights);
+    }
+
+    /// The conflict containing the cursor, with its 1-based position and the
+    /// total number of conflicts.
+    fn conflict_at_cursor(&self) -> Option<(Conflict, usize, usize)> {
+        let cursor = self.textarea.cursor();
+        let all = conflicts::conflicts(self.textarea.text());
+        let total = all.len();
+        all.into_iter()
+            .enumerate()
+            .find(|(_, conflict)| conflict.range.contains(&cursor))
+            .map(|(index, conflict)| (conflict, index + 1, total))
+    }
+
+    /// Replace the conflict at the cursor with the side(s) chosen.
+    fn resolve_conflict(&mut self, resolution: Resolution) {
+        let Some((conflict, _, _)) = self.conflict_at_cursor() else {
+            self.status_message = Some(StatusMessage::info(
+                "Move the cursor into a merge conflict to resolve it".to_string(),
+            ));
+            return;
+        };
+        let resolved = conflict.resolve(self.textarea.text(), resolution);
+        let cursor = conflict.range.start;
+        self.selection_anchor = None;
+        self.apply_editor_change(|ta| {
+            ta.replace_range(conflict.range, &resolved);
+            ta.set_cursor(cursor);
+        });
+    }
+
+    /// Whole lines covered by the selection, if any.
+    fn selected_lines(&self) -> Option<Range<usize>> {
+        let text = self.textarea.text();
+        let selection = self.selection_range()?;
+        // A selection ending at the start of a line does not include it.
+        let last = if text[..selection.end].ends_with('\n') {
+            selection.end - 1
+        } else {
+            selection.end
+        };
+        Some(text_ops::line_start(text, selection.start)..text_ops::line_end(text, last))
+    }
+
+    /// Whole lines covered by the selection, or the paragraph around the
+    /// cursor when nothing is selected.
+    fn target_lines(&self) -> Range<usize> {
+        self.selected_lines().unwrap_or_else(|| {
+            text_ops::paragraph_range(self.textarea.text(), self.textarea.cursor())
+        })
+    }
+
+    /// Replace whole lines in `range`, keeping the cursor on the same line and
+    /// column where it still exists.
+    fn replace_lines(&mut self, range: Range<usize>, replacement: &str) -> bool {
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        let line = text[..cursor].matches('\n').count();
+        let col = cursor - text_ops::line_start(text, cursor);
+        let changed = self.apply_editor_change(|ta| ta.replace_range(range, replacement));
+        if changed {
+            let text = self.textarea.text();
+            let start = match line.checked_sub(1) {
+                Some(prev) => text
+                    .match_indices('\n')
+                    .nth(prev)
+                    .map_or(text.len(), |(i, _)| i + 1),
+                None => 0,
+            };
+            let end = text_ops::line_end(text, start);
+            let mut pos = (start + col).min(end);
+            while !text.is_char_boundary(pos) {
+                pos -= 1;
+            }
+            self.textarea.set_cursor(pos);
+        }
+        changed
+    }
+
+    /// Line up the `=`/`:` separators of the selected lines (or the current
+    /// paragraph).
+    fn align_selection(&mut self) {
+        let range = self.target_lines();
+        let block = &self.textarea.text()[range.clone()];
+        let Some(separator) = self
+            .align_separator
+            .or_else(|| text_ops::detect_separator(block))
+        else {
+            self.status_message = Some(StatusMessage::warning(
+                "No key-value separators to align".to_string(),
+            ));
+            return;
+        };
+        let (aligned, count) = text_ops::align_on_separator(block, separator);
+        self.replace_lines(range, &aligned);
+        self.selection_anchor = None;
+        self.status_message = Some(StatusMessage::success(if count == 0 {
+            format!("Lines already aligned on '{separator}'")
+        } else {
+            let lines = if count == 1 { "line" } else { "lines" };
+            format!("Aligned {count} {lines} on '{separator}'")
+        }));
+    }
+
+    /// Collapse runs of blank lines in the selection, or in the whole buffer
+    /// when nothing is selected.
+    fn squeeze_blank_lines(&mut self) {
+        let (range, trim_edges) = match self.selected_lines() {
+            Some(range) => (range, false),
+            None => (0..self.textarea.text().len(), self.trim_blank_edges),
+        };
+        let block = &self.textarea.text()[range.clone()];
+        let (squeezed, removed) = text_ops::squeeze_blank_lines(block, trim_edges);
+        self.replace_lines(range, &squeezed);
+        self.selection_anchor = None;
+        let lines = if removed == 1 { "line" } else { "lines" };
+        self.status_message = Some(StatusMessage::success(format!(
+            "Removed {removed} blank {lines}"
+        )));
+    }
+
+    /// Wrap the selection as `[selection]()` with the cursor between the
+    /// parentheses, ready for the URL. Without a selection an empty `[]()`
+    /// template is inserted with the cursor in the link text.
+    fn insert_markdown_link(&mut self) {
+        let selection = self.selection_range();
+        self.selection_anchor = None;
+        let (range, label) = match selection {
+            Some(range) => {
+                let label = self.textarea.text()[range.clone()].to_string();
+                (range, label)
+            }
+            None => {
+                let cursor = self.textarea.cursor();
+                (cursor..cursor, String::new())
+            }
+        };
+        let link = format!("[{label}]()");
+        // Inside `()` when wrapping a selection, inside `[]` for the template.
+        let cursor = if label.is_empty() {
+            range.start + 1
+        } else {
+            range.start + link.len() - 1
+        };
+        let prompt = if label.is_empty() {
+            "Type the link text, then the URL between ()"
+        } else {
+            "Type the link URL"
+        };
+        self.apply_editor_change(|ta| {
+            ta.replace_range(range, &link);
+            ta.set_cursor(cursor);
+        });
+        self.status_message = Some(StatusMessage::info(prompt.to_string()));
+    }
+
+    /// Turn curly quotes, dashes and similar typography in the selection (or
+    /// the whole buffer) into plain ASCII.
+    fn asciify_selection(&mut self) {
+        let range = self
+            .selection_range()
+            .unwrap_or(0..self.textarea.text().len());
+        let (replaced, count) = text_ops::replace_chars(
+            &self.textarea.text()[range.clone()],
+            &self.ascii_replacements,
+        );
+        if count > 0 {
+            let anchor = self.selection_anchor.map(|_| range.start);
+            self.apply_editor_change(|ta| {
+                ta.replace_range(range.clone(), &replaced);
+                ta.set_cursor(range.start + replaced.len());
+            });
+            self.selection_anchor = anchor;
+        }
+        let noun = if count == 1 {
+            "character"
+        } else {
+            "characters"
+        };
+        self.status_message = Some(StatusMessage::success(format!(
+            "Replaced {count} typographic {noun} with ASCII"
+        )));
+    }
+
+    /// Insert a freshly generated v4 UUID at the cursor.
+    fn insert_uuid(&mut self) {
+        let id = Uuid::new_v4();
+        let text = match self.uuid_format {
+            UuidFormat::Hyphenated => id.hyphenated().to_string(),
+            UuidFormat::Compact => id.simple().to_string(),
+        };
+        self.apply_editor_change(|ta| ta.insert_str(&text));
+    }
+
+    /// Put the front-matter template at the top of the buffer and move the
+    /// cursor to its first empty field. Buffers that already have front
+    /// matter are left alone.
+    fn insert_front_matter(&mut self) {
+        if text_ops::has_front_matter(self.textarea.text()) {
+            self.status_message = Some(StatusMessage::warning(
+                "This file already has front matter".to_string(),
+            ));
+            return;
+        }
+        let template = self.front_matter_template.clone();
+        let cursor = text_ops::first_empty_field(&template).unwrap_or(template.len());
+        self.apply_editor_change(|ta| {
+            ta.insert_str_at(0, &template);
+            ta.set_cursor(cursor);
+        });
+    }
+
+    /// Break the current line at spaces to fit the hard-wrap width.
+    fn hard_wrap_line(&mut self) {
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        let range = text_ops::line_start(text, cursor)..text_ops::line_end(text, cursor);
+        let wrapped = text_ops::hard_wrap(
+            &text[range.clone()],
+            self.hard_wrap_width,
+            HARD_WRAP_CONTINUATION,
+        );
+        self.selection_anchor = None;
+        self.replace_lines(range, &wrapped);
+    }
+
+    /// Join the current line with the continuation lines below it, undoing
+    /// [`Self::hard_wrap_line`].
+    fn join_continuation_lines(&mut self) {
+        let text = self.textarea.text();
+        let range = text_ops::continuation_range(text, self.textarea.cursor());
+        let joined = text_ops::join_lines(&text[range.clone()]);
+        self.selection_anchor = None;
+        self.replace_lines(range, &joined);
+    }
+
+    /// Convert the selected list (or the list around the cursor) to one
+    /// comma-separated line, or the current comma-separated line to a list.
+    fn toggle_list_csv(&mut self) {
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        let range = self
+            .selected_lines()
+            .or_else(|| text_ops::list_range(text, cursor))
+            .unwrap_or_else(|| {
+                text_ops::line_start(text, cursor)..text_ops::line_end(text, cursor)
+            });
+        let Some(toggled) = text_ops::toggle_list_csv(&text[range.clone()]) else {
+            self.status_message = Some(StatusMessage::info(
+                "Select a list or a comma-separated line to convert".to_string(),
+            ));
+            return;
+        };
+        self.selection_anchor = None;
+        self.replace_lines(range, &toggled);
+    }
+
+    /// Put a cursor on every whole-word occurrence of the word at the cursor.
+    fn select_occurrences(&mut self) {
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        let Some(word) = text_ops::word_at(text, cursor) else {
+            self.status_message = Some(StatusMessage::info(
+                "Move the cursor to a word to edit all its occurrences".to_string(),
+            ));
+            return;
+        };
+        self.selection_anchor = None;
+        self.occurrences = Some(Occurrences::find(text, word, cursor));
+    }
+
+    /// Apply a key to every occurrence. Returns `false` for keys that end
+    /// multi-cursor editing, which are then handled as usual.
+    fn handle_occurrences_key(&mut self, key_event: KeyEvent) -> bool {
+        let Some(occurrences) = &mut self.occurrences else {
+            return false;
+        };
+        let plain = (key_event.modifiers - KeyModifiers::SHIFT).is_empty();
+        let edit = match key_event.code {
+            KeyCode::Char(ch) if plain => OccurrenceEdit::Insert(ch),
+            KeyCode::Backspace if plain => OccurrenceEdit::Backspace,
+            KeyCode::Delete if plain => OccurrenceEdit::Delete,
+            KeyCode::Left if plain => OccurrenceEdit::Left,
+            KeyCode::Right if plain => OccurrenceEdit::Right,
+            _ => {
+                self.occurrences = None;
+                return false;
+            }
+        };
+        let rewritten = occurrences.apply(self.textarea.text(), edit);
+        let cursor = occurrences.cursor();
+        let emptied = occurrences.is_empty();
+        if let Some(rewritten) = rewritten {
+            let end = self.textarea.text().len();
+            self.apply_editor_change(|ta| ta.replace_range(0..end, &rewritten));
+        }
+        self.textarea.set_cursor(cursor);
+        if emptied {
+            self.occurrences = None;
+        }
+        true
+    }
+
+    /// Reformat the JSON in the ```json fence around the cursor. The rest of
+    /// the document is left alone.
+    fn pretty_print_json_fence(&mut self) {
+        let text = self.textarea.text();
+        let Some((_, body)) = text_ops::fence_at(text, self.textarea.cursor())
+            .filter(|(language, _)| language.eq_ignore_ascii_case("json"))
+        else {
+            self.status_message = Some(StatusMessage::info(
+                "Move the cursor into a ```json code block to format it".to_string(),
+            ));
+            return;
+        };
+        let source = &text[body.clone()];
+        let formatted = serde_json::from_str::<serde_json::Value>(source)
+            .and_then(|value| serde_json::to_string_pretty(&value));
+        let formatted = match formatted {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                let line = text[..body.start].matches('\n').count() + err.line();
+                self.status_message = Some(StatusMessage::warning(format!(
+                    "Invalid JSON on line {line}: {err}"
+                )));
+                return;
+            }
+        };
+        let indent = text[text_ops::line_start(text, body.start)..]
+            .chars()
+            .take_while(|ch| *ch == ' ')
+            .count();
+        let indent = " ".repeat(indent);
+        let mut block: String = formatted
+            .lines()
+            .map(|line| format!("{indent}{line}\n"))
+            .collect();
+        if !source.ends_with('\n') {
+            block.pop();
+        }
+        self.selection_anchor = None;
+        self.replace_lines(body, &block);
+    }
+
+    /// Open the documentation URL in the browser, or show it when there is no
+    /// browser to open it in.
+    fn open_docs_url(&mut self) {
+        let Some(url) = self.docs_url.clone() else {
+            self.status_message = Some(StatusMessage::info(
+                "No documentation link for this file".to_string(),
+            ));
+            return;
+        };
+        if self.headless {
+            self.status_message = Some(StatusMessage::info(format!("Docs: {url}")));
+            return;
+        }
+        self.status_message = Some(match webbrowser::open(&url) {
+            Ok(()) => StatusMessage::success(format!("Opened {url}")),
+            Err(err) => StatusMessage::warning(format!("Could not open a browser ({err}): {url}")),
+        });
+    }
+
+    /// Show the host's parse of the buffer in a read-only overlay.
+    fn show_parse_preview(&mut self) {
+        let Some(parser) = &self.parse_preview else {
+            self.status_message = Some(StatusMessage::info(
+                "No parser configured for this file".to_string(),
+            ));
+            return;
+        };
+        match parser(self.textarea.text()) {
+            Ok(parsed) => {
+                let lines = parsed
+                    .lines()
+                    .map(|line| Line::from(line.to_string()))
+                    .collect();
+                self.overlay = Some(Overlay::new("Parsed preferences".to_string(), lines));
+            }
+            Err(err) => {
+                self.status_message = Some(StatusMessage::error(format!("Failed to parse: {err}")));
+            }
+        }
+    }
+
+    /// Show what changed since the last save in a read-only overlay, until
+    /// Ctrl+D is pressed again.
+    fn show_unsaved_diff(&mut self) {
+        if self.textarea.text() == self.last_saved_text {
+            self.status_message = Some(StatusMessage::info("No unsaved changes".to_string()));
+            return;
+        }
+        let lines = diff_preview_lines(&self.last_saved_text, self.textarea.text(), &self.theme);
+        self.overlay = Some(Overlay::new(
+            "Unsaved changes (Ctrl+D to edit)".to_string(),
+            lines,
+        ));
+    }
+
+    /// Show the file's documentation comments in a read-only overlay.
+    fn show_docs(&mut self) {
+        let docs = text_ops::comment_docs(self.textarea.text(), &self.comment_token);
+        if docs.is_empty() {
+            self.status_message = Some(StatusMessage::info(format!(
+                "No {} comments to show",
+                self.comment_token
+            )));
+            return;
+        }
+        let lines = docs.into_iter().map(Line::from).collect();
+        self.overlay = Some(Overlay::new("Documentation".to_string(), lines));
+    }
+
+    /// Comment out the selected lines (or the current line), or uncomment
+    /// them if they are all commented already.
+    fn toggle_comment(&mut self) {
+        let text = self.textarea.text();
+        let range = self.selected_lines().unwrap_or_else(|| {
+            let cursor = self.textarea.cursor();
+            text_ops::line_start(text, cursor)..text_ops::line_end(text, cursor)
+        });
+        let toggled = text_ops::toggle_comment(&text[range.clone()], &self.comment_token);
+        self.replace_lines(range, &toggled);
+    }
+
+    /// Sort the top-level keys of the front-matter block. The body below the
+    /// closing fence is left untouched.
+    fn sort_front_matter(&mut self) {
+        let text = self.textarea.text();
+        let Some((range, toml)) = front_matter::body_range(text) else {
+            self.status_message = Some(StatusMessage::warning(
+                "No front matter to sort".to_string(),
+            ));
+            return;
+        };
+        let sorted = match front_matter::sort_keys(&text[range.clone()], toml) {
+            Ok(sorted) => sorted,
+            Err(err) => {
+                self.status_message = Some(StatusMessage::warning(format!(
+                    "Front matter left unsorted: {err}"
+                )));
+                return;
+            }
+        };
+        self.selection_anchor = None;
+        self.replace_lines(range, &sorted);
+    }
+
+    /// Add `delta` to the integer under or after the cursor on its line and
+    /// leave the cursor on its last digit.
+    fn step_number_at_cursor(&mut self, delta: i64) {
+        let text = self.textarea.text();
+        let Some(range) = text_ops::number_at(text, self.textarea.cursor()) else {
+            return;
+        };
+        let Some(stepped) = text_ops::step_number(&text[range.clone()], delta) else {
+            return;
+        };
+        self.selection_anchor = None;
+        let cursor = range.start + stepped.len().saturating_sub(1);
+        self.apply_editor_change(|ta| {
+            ta.replace_range(range, &stepped);
+            ta.set_cursor(cursor);
+        });
+    }
+
+    /// Remove up to `tab_spaces` leading spaces from the cursor line. A line
+    /// that does not start with a space is left alone.
+    fn dedent_line(&mut self) {
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        let start = text_ops::line_start(text, cursor);
+        let spaces = text[start..]
+            .bytes()
+            .take(self.tab_spaces)
+            .take_while(|byte| *byte == b' ')
+            .count();
+        if spaces == 0 {
+            return;
+        }
+        let cursor = cursor.saturating_sub(spaces).max(start);
+        self.apply_editor_change(|ta| {
+            ta.replace_range(start..start + spaces, "");
+            ta.set_cursor(cursor);
+        });
+    }
+
+    /// Type `ch` over the character at the cursor. Newlines and the end of
+    /// the buffer are never overwritten, so typing there inserts.
+    fn overtype_char(&mut self, ch: char) {
+        let cursor = self.textarea.cursor();
+        let end = self.textarea.text()[cursor..]
+            .graphemes(true)
+            .next()
+            .filter(|grapheme| *grapheme != "\n" && *grapheme != "\r\n")
+            .map_or(cursor, |grapheme| cursor + grapheme.len());
+        let replacement = ch.to_string();
+        self.apply_editor_change(|ta| {
+            ta.replace_range(cursor..end, &replacement);
+            ta.set_cursor(cursor + replacement.len());
+        });
+    }
+
+    /// Render the buffer to HTML for rich-text paste. The file itself is
+    /// untouched.
+    fn export_html(&mut self) {
+        self.status_message = Some(match export::export_html(self.textarea.text()) {
+            Ok(HtmlExport::Clipboard) => {
+                StatusMessage::success("Copied HTML to the clipboard".to_string())
+            }
+            Ok(HtmlExport::TempFile(path)) => StatusMessage::info(format!(
+                "Clipboard unavailable; wrote HTML to {}",
+                path.display()
+            )),
+            Err(err) => StatusMessage::error(format!("Failed to export HTML: {err}")),
+        });
+    }
+
+    /// Push the folded sections down to the textarea, dropping folds whose
+    /// heading no longer exists.
+    fn sync_folds(&mut self) {
+        let text = self.textarea.text();
+        let sections = folding::sections(text);
+        self.folded.retain(|heading| {
+            sections
+                .iter()
+                .any(|s| text[s.heading.clone()] == **heading)
+        });
+        let folds = sections
+            .iter()
+            .filter(|s| {
+                !s.body.is_empty() && self.folded.iter().any(|h| **h == text[s.heading.clone()])
+            })
+            .map(|s| {
+                let lines = s.body_lines(text);
+                let noun = if lines == 1 { "line" } else { "lines" };
+                Fold {
+                    range: s.body.clone(),
+                    summary: format!(" ⋯ {lines} {noun} folded"),
+                }
+            })
+            .collect();
+        self.textarea.set_folds(folds);
+    }
+
+    /// The outermost folded section whose body hides `pos`.
+    fn folded_section_hiding(&self, pos: usize) -> Option<folding::Section> {
+        let text = self.textarea.text();
+        folding::sections(text).into_iter().find(|s| {
+            let hidden = s.body.contains(&pos)
+                || (!s.body.is_empty() && pos == text.len() && s.body.end == pos);
+            hidden && self.folded.iter().any(|h| **h == text[s.heading.clone()])
+        })
+    }
+
+    /// Unfold whatever hides the cursor, e.g. after moving into a fold.
+    fn reveal_cursor(&mut self) {
+        while let Some(section) = self.folded_section_hiding(self.textarea.cursor()) {
+            let heading = &self.textarea.text()[section.heading];
+            self.folded.retain(|h| h != heading);
+            self.sync_folds();
+        }
+    }
+
+    /// The folded section whose heading line, where the fold summary is
+    /// drawn, holds the cursor.
+    fn folded_heading_at_cursor(&self) -> Option<folding::Section> {
+        if self.folded.is_empty() {
+            return None;
+        }
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        folding::sections(text).into_iter().find(|s| {
+            (s.heading.start..=s.heading.end).contains(&cursor)
+                && !s.body.is_empty()
+                && self.folded.iter().any(|h| **h == text[s.heading.clone()])
+        })
+    }
+
+    /// Unfold the section under the cursor before a key edits its heading,
+    /// so the edit never lands next to lines the user cannot see.
+    fn unfold_before_edit(&mut self, key_event: KeyEvent) {
+        let edits = match key_event.code {
+            KeyCode::Char(_) => !key_event
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT),
+            KeyCode::Enter
+            | KeyCode::Backspace
+            | KeyCode::Delete
+            | KeyCode::Tab
+            | KeyCode::BackTab => true,
+            _ => false,
+        };
+        if !edits {
+            return;
+        }
+        let Some(section) = self.folded_heading_at_cursor() else {
+            return;
+        };
+        let heading = &self.textarea.text()[section.heading];
+        self.folded.retain(|h| h != heading);
+        self.sync_folds();
+    }
+
+    /// After folding, park the cursor on the heading that now hides it so it
+    /// stays on screen.
+    fn keep_cursor_on_visible_line(&mut self) {
+        if let Some(section) = self.folded_section_hiding(self.textarea.cursor()) {
+            self.textarea.set_cursor(section.heading.start);
+        }
+    }
+
+    fn toggle_fold_at_cursor(&mut self) {
+        let cursor = self.textarea.cursor();
+        let text = self.textarea.text();
+        let Some(section) = folding::sections(text)
+            .into_iter()
+            .rev()
+            .find(|s| s.contains(cursor))
+        else {
+            self.status_message = Some(StatusMessage::info(
+                "Not inside a heading section".to_string(),
+            ));
+            return;
+        };
+        let heading = text[section.heading.clone()].to_string();
+        if self.folded.contains(&heading) {
+            self.folded.retain(|h| *h != heading);
+        } else if section.body.is_empty() {
+            self.status_message = Some(StatusMessage::info(
+                "Section has nothing to fold".to_string(),
+            ));
+            return;
+        } else {
+            self.folded.push(heading);
+        }
+        self.sync_folds();
+        self.keep_cursor_on_visible_line();
+    }
+
+    fn fold_all(&mut self) {
+        let text = self.textarea.text();
+        let mut folded: Vec<String> = Vec::new();
+        for section in folding::sections(text) {
+            let heading = &text[section.heading.clone()];
+            if !section.body.is_empty() && !folded.iter().any(|h| h == heading) {
+                folded.push(heading.to_string());
+            }
+        }
+        let count = folded.len();
+        self.folded = folded;
+        self.sync_folds();
+        self.keep_cursor_on_visible_line();
+        let noun = if count == 1 { "section" } else { "sections" };
+        self.status_message = Some(StatusMessage::success(format!("Folded {count} {noun}")));
+    }
+
+    fn unfold_all(&mut self) {
+        self.folded.clear();
+        self.sync_folds();
+        self.status_message = Some(StatusMessage::success("Unfolded all sections".to_string()));
+    }
+
+    fn lint_indentation(&mut self) {
+        self.mixed_indent = text_ops::mixed_indentation(self.textarea.text());
+    }
+
+    /// Rewrite every indentation that mixes tabs and spaces in the
+    /// configured style, as one edit.
+    fn fix_indentation(&mut self) {
+        if self.mixed_indent.is_empty() {
+            self.status_message = Some(StatusMessage::info(
+                "No lines mix tabs and spaces".to_string(),
+            ));
+            return;
+        }
+        let text = self.textarea.text();
+        let mut fixed = String::with_capacity(text.len());
+        let mut copied = 0;
+        for range in &self.mixed_indent {
+            fixed.push_str(&text[copied..range.start]);
+            fixed.push_str(&text_ops::normalize_indent(
+                &text[range.clone()],
+                self.indent_style,
+            ));
+            copied = range.end;
+        }
+        fixed.push_str(&text[copied..]);
+        let count = self.mixed_indent.len();
+        let len = text.len();
+        self.selection_anchor = None;
+        self.replace_lines(0..len, &fixed);
+        let noun = if count == 1 { "line" } else { "lines" };
+        self.status_message = Some(StatusMessage::success(format!(
+            "Fixed indentation on {count} {noun}"
+        )));
+    }
+
+    /// Handle the key after the Ctrl+X prefix. Returns false when the key
+    /// is not a Ctrl+X command and should be processed normally.
+    fn handle_ctrl_x_key(&mut self, key_event: KeyEvent) -> bool {
+        match key_event.code {
+            KeyCode::Char('t') | KeyCode::Char('T')
+                if key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.transpose_lines();
+            }
+            KeyCode::Char('i') if key_event.modifiers.is_empty() => self.fix_indentation(),
+            KeyCode::Char('e') if key_event.modifiers.is_empty() => self.copy_validation_error(),
+            KeyCode::Char(',') if key_event.modifiers.is_empty() => {
+                self.run_command(EditorCommand::ToggleListCsv);
+            }
+            KeyCode::Char('p') if key_event.modifiers.is_empty() => self.show_parse_preview(),
+            KeyCode::Char('r') | KeyCode::Char('R')
+                if key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.restore_backup();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Swap the current line with the one above and move down a line,
+    /// keeping the column where the line is long enough.
+    fn transpose_lines(&mut self) {
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        let Some((range, swapped)) = text_ops::transpose_lines(text, cursor) else {
+            return;
+        };
+        let col = cursor - text_ops::line_start(text, cursor);
+        self.selection_anchor = None;
+        self.apply_editor_change(|ta| {
+            ta.replace_range(range.clone(), &swapped);
+            let text = ta.text();
+            let swapped_end = range.start + swapped.len();
+            let next = text_ops::line_end(text, swapped_end);
+            let line = if next < text.len() {
+                next + 1
+            } else {
+                text_ops::line_start(text, swapped_end)
+            };
+            let end = text_ops::line_end(text, line);
+            let mut pos = (line + col).min(end);
+            while !text.is_char_boundary(pos) {
+                pos -= 1;
+            }
+            ta.set_cursor(pos);
+        });
+    }
+
+    /// Comment out every section except the one at the cursor, as a single
+    /// edit.
+    fn solo_section(&mut self) {
+        if let Some(solo) = &self.solo {
+            self.status_message = Some(StatusMessage::info(format!(
+                "Already soloing {} · Alt+Z u restores",
+                solo.heading
+            )));
+            return;
+        }
+        let cursor = self.textarea.cursor();
+        let text = self.textarea.text();
+        let sections = folding::sections(text);
+        let Some(keep) = sections.iter().rev().find(|s| s.contains(cursor)) else {
+            self.status_message = Some(StatusMessage::info(
+                "Not inside a heading section".to_string(),
+            ));
+            return;
+        };
+        let Some((soloed, solo)) = solo::solo(text, &sections, keep, &self.comment_token) else {
+            self.status_message = Some(StatusMessage::info(
+                "No other sections to comment out".to_string(),
+            ));
+            return;
+        };
+        let len = text.len();
+        self.selection_anchor = None;
+        self.replace_lines(0..len, &soloed);
+        self.status_message = Some(StatusMessage::info(format!(
+            "Soloing {} · Alt+Z u restores",
+            solo.heading
+        )));
+        self.solo = Some(solo);
+    }
+
+    /// Uncomment the sections commented out by the last solo.
+    fn unsolo_section(&mut self) {
+        let Some(solo) = self.solo.take() else {
+            self.status_message = Some(StatusMessage::info("No section is soloed".to_string()));
+            return;
+        };
+        let text = self.textarea.text();
+        let Some(restored) = solo::unsolo(text, &solo, &self.comment_token) else {
+            self.status_message = Some(StatusMessage::warning(
+                "Commented-out sections were edited; uncomment them by hand".to_string(),
+            ));
+            return;
+        };
+        let len = text.len();
+        self.selection_anchor = None;
+        self.replace_lines(0..len, &restored);
+        self.status_message = Some(StatusMessage::success(format!(
+            "Restored all sections around {}",
+            solo.heading
+        )));
+    }
+
+    /// Handle the key after the Alt+Z fold prefix. Returns false when the key
+    /// is not a fold command and should be processed normally.
+    fn handle_fold_key(&mut self, key_event: KeyEvent) -> bool {
+        if !(key_event.modifiers - KeyModifiers::SHIFT).is_empty() {
+            return false;
+        }
+        match key_event.code {
+            KeyCode::Char('z') | KeyCode::Char('a') => self.toggle_fold_at_cursor(),
+            KeyCode::Char('m') | KeyCode::Char('M') => self.fold_all(),
+            KeyCode::Char('r') | KeyCode::Char('R') => self.unfold_all(),
+            KeyCode::Char('s') | KeyCode::Char('S') => self.solo_section(),
+            KeyCode::Char('u') | KeyCode::Char('U') => self.unsolo_section(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        let candidates = match kind {
+            PromptKind::EnvVar => {
+                let mut names: Vec<String> = std::env::vars_os()
+                    .filter_map(|(name, _)| name.into_string().ok())
+                    .collect();
+                names.sort();
+                names
+            }
+            PromptKind::FenceLanguage
+            | PromptKind::Surround
+            | PromptKind::ShellCommand
+            | PromptKind::Search
+            | PromptKind::DuplicateCount
+            | PromptKind::SaveAs
+            | PromptKind::GoToLine => Vec::new(),
+        };
+        self.status_message = None;
+        self.search_match = None;
+        self.search_status = None;
+        self.prompt = Some(Prompt::new(kind, candidates));
+    }
+
+    fn handle_prompt_key(&mut self, key_event: KeyEvent) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+        let kind = prompt.kind;
+        match prompt.handle_key(key_event) {
+            PromptOutcome::Pending => {}
+            PromptOutcome::Cancelled => self.prompt = None,
+            // Search stays open so Enter can step through the matches.
+            PromptOutcome::Submitted(input) if kind == PromptKind::Search => {
+                self.search_next(&input);
+            }
+            // A bad line number keeps the prompt open for correction.
+            PromptOutcome::Submitted(input) if kind == PromptKind::GoToLine => {
+                self.go_to_line(input.trim());
+            }
+            PromptOutcome::Submitted(input) => {
+                self.prompt = None;
+                match kind {
+                    PromptKind::EnvVar => self.insert_env_var(input.trim()),
+                    PromptKind::Surround => self.surround(&input),
+                    PromptKind::ShellCommand => self.insert_shell_output(input.trim()),
+                    PromptKind::Search | PromptKind::GoToLine => {}
+                    PromptKind::SaveAs => self.request_save_as(input.trim()),
+                    PromptKind::DuplicateCount => match input.trim() {
+                        "" => self.duplicate_selection(1),
+                        count => match count.parse::<usize>() {
+                            Ok(count) if (1..=MAX_DUPLICATES).contains(&count) => {
+                                self.duplicate_selection(count);
+                            }
+                            _ => {
+                                self.status_message = Some(StatusMessage::warning(format!(
+                                    "Enter a count from 1 to {MAX_DUPLICATES}"
+                                )));
+                            }
+                        },
+                    },
+                    PromptKind::FenceLanguage => {
+                        let language = input.trim().to_string();
+                        let fence = format!("```{language}");
+                        self.fence_next_paste = Some(language);
+                        self.status_message = Some(StatusMessage::info(format!(
+                            "Paste now to insert it as a {fence} code block"
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Move to the start of 1-based line `input`, clamped to the last line.
+    fn go_to_line(&mut self, input: &str) {
+        let Ok(line) = input.parse::<usize>() else {
+            self.status_message = Some(StatusMessage::warning(format!(
+                "Not a line number: {input}"
+            )));
+            return;
+        };
+        self.prompt = None;
+        self.status_message = None;
+        self.selection_anchor = None;
+        let offset = text_ops::line_offset(self.textarea.text(), line);
+        self.textarea.set_cursor(offset);
+    }
+
+    /// Wrap the selection, or the word at the cursor, in the pair described
+    /// by `input`.
+    fn surround(&mut self, input: &str) {
+        let Some((open, close)) = text_ops::surround_pair(input) else {
+            return;
+        };
+        let text = self.textarea.text();
+        let Some(range) = self
+            .selection_range()
+            .or_else(|| text_ops::word_at(text, self.textarea.cursor()))
+        else {
+            self.status_message = Some(StatusMessage::warning(
+                "Select text or move to a word to surround it".to_string(),
+            ));
+            return;
+        };
+        let wrapped = format!("{open}{}{close}", &text[range.clone()]);
+        let cursor = range.start + wrapped.len();
+        self.selection_anchor = None;
+        self.apply_editor_change(|ta| {
+            ta.replace_range(range, &wrapped);
+            ta.set_cursor(cursor);
+        });
+    }
+
+    /// Insert `count` copies of the selection right after it, each preceded
+    /// by the separator, and select the last copy.
+    fn duplicate_selection(&mut self, count: usize) {
+        let Some(range) = self.selection_range().filter(|range| !range.is_empty()) else {
+            self.status_message = Some(StatusMessage::info(
+                "Select text to duplicate it".to_string(),
+            ));
+            return;
+        };
+        let copy = format!(
+            "{}{}",
+            self.duplicate_separator,
+            &self.textarea.text()[range.clone()]
+        );
+        let inserted = copy.repeat(count);
+        let end = range.end + inserted.len();
+        let last_copy = end - range.len();
+        self.apply_editor_change(|ta| {
+            ta.replace_range(range.end..range.end, &inserted);
+            ta.set_cursor(end);
+        });
+        self.selection_anchor = Some(last_copy);
+    }
+
+    /// Move the cursor to the next match of `query` after the cursor,
+    /// wrapping around at the end of the buffer. The match the cursor is
+    /// already on counts only for the first Enter.
+    fn search_next(&mut self, query: &str) {
+        let matches = text_ops::find_all(self.textarea.text(), query);
+        if matches.is_empty() {
+            self.search_match = None;
+            self.search_status = Some("no matches".to_string());
+            return;
+        }
+        let cursor = self.textarea.cursor();
+        let from = if self.search_match == Some(cursor) {
+            cursor + 1
+        } else {
+            cursor
+        };
+        let index = matches.iter().position(|start| *start >= from).unwrap_or(0);
+        let start = matches[index];
+        self.selection_anchor = None;
+        self.textarea.set_cursor(start);
+        self.search_match = Some(start);
+        self.search_status = Some(format!("{} of {}", index + 1, matches.len()));
+        if self.center_on_search {
+            self.recenter.set(Some(Recenter::Center));
+        }
+    }
+
+    /// Run `command` and insert its stdout at the cursor, without the final
+    /// newline. With a frame requester the command runs on a worker and the
+    /// output is inserted on the tick after it finishes.
+    fn insert_shell_output(&mut self, command: &str) {
+        if command.is_empty() {
+            return;
+        }
+        let Some(frame_requester) = self.frame_requester.clone() else {
+            let result = shell::run(command, shell::SHELL_TIMEOUT, shell::SHELL_MAX_OUTPUT);
+            self.insert_command_result(command, result);
+            return;
+        };
+        self.status_message = None;
+        frame_requester.schedule_frame();
+        self.shell_run = Some(ShellRun::start(command.to_string(), move || {
+            frame_requester.schedule_frame();
+        }));
+    }
+
+    /// Insert the output of the running shell command once it has finished.
+    fn finish_shell_run(&mut self) {
+        let Some(result) = self.shell_run.as_ref().and_then(ShellRun::try_result) else {
+            return;
+        };
+        if let Some(run) = self.shell_run.take() {
+            self.insert_command_result(&run.command, result);
+            self.reveal_cursor();
+            self.sync_highlights();
+        }
+    }
+
+    fn insert_command_result(&mut self, command: &str, result: Result<String, String>) {
+        match result {
+            Ok(output) => {
+                let output = output.strip_suffix('\n').unwrap_or(&output);
+                let output = output.strip_suffix('\r').unwrap_or(output);
+                if output.is_empty() {
+                    self.status_message =
+                        Some(StatusMessage::info(format!("`{command}` printed nothing")));
+                    return;
+                }
+                self.apply_editor_change(|ta| ta.insert_str(output));
+            }
+            Err(err) => {
+                self.status_message =
+                    Some(StatusMessage::warning(format!("`{command}` failed: {err}")));
+            }
+        }
+    }
+
+    /// Insert the current value of environment variable `name` at the cursor.
+    fn insert_env_var(&mut self, name: &str) {
+        if name.is_empty() {
+            return;
+        }
+        match std::env::var(name) {
+            Ok(value) if !value.is_empty() => {
+                self.apply_editor_change(|ta| ta.insert_str(&value));
+            }
+            Ok(_) => {
+                self.status_message = Some(StatusMessage::warning(format!(
+                    "Environment variable {name} is empty"
+                )));
+            }
+            Err(_) => {
+                self.status_message = Some(StatusMessage::warning(format!(
+                    "Environment variable {name} is not set"
+                )));
+            }
+        }
+    }
+
+    fn move_by_paragraph(&mut self, forward: bool) {
+        let text = self.textarea.text();
+        let cursor = self.textarea.cursor();
+        let target = if forward {
+            text_ops::next_paragraph_start(text, cursor)
+        } else {
+            text_ops::previous_paragraph_start(text, cursor)
+        };
+        self.textarea.set_cursor(target);
+    }
+
+    /// Delete back to the start of the previous word or punctuation run,
+    /// stopping at the start of the line; there it joins the line above.
+    fn delete_previous_word(&mut self) {
+        let cursor = self.textarea.cursor();
+        let start = text_ops::previous_word_start(self.textarea.text(), cursor);
+        self.apply_editor_change(|ta| {
+            ta.replace_range(start..cursor, "");
+            ta.set_cursor(start);
+        });
+    }
+
+    /// Move the cursor a screen up or down, scrolling the text with it so it
+    /// stays on the same row.
+    fn move_by_page(&mut self, forward: bool) {
+        let before = self.textarea.cursor();
+        for _ in 0..self.page_rows.get().max(1) {
+            if forward {
+                self.textarea.move_cursor_down();
+            } else {
+                self.textarea.move_cursor_up();
+            }
+        }
+        if self.textarea.cursor() != before {
+            self.page_scroll_row.set(Some(self.cursor_row.get()));
+        }
+    }
+
+    /// Insert `pasted` as a fenced code block on its own lines.
+    fn paste_as_code_fence(&mut self, pasted: &str, language: &str) {
+        let cursor = self.textarea.cursor();
+        let mut block = text_ops::code_fence(pasted, language, FENCE_TAB_WIDTH);
+        let text = self.textarea.text();
+        if cursor > 0 && !text[..cursor].ends_with('\n') {
+            block.insert(0, '\n');
+        }
+        if text[cursor..].starts_with('\n') {
+            block.pop();
+        }
+        self.apply_editor_change(|ta| ta.insert_str(&block));
+    }
+
+    fn toggle_scratch(&mut self) {
+        if self.scratch.take().is_none() {
+            self.scratch = Some(ScratchPane {
+                textarea: TextArea::new(),
+                state: RefCell::new(TextAreaState::default()),
+            });
+            self.scratch_focused = true;
+        } else {
+            self.scratch_focused = false;
+        }
+    }
+
+    /// Ctrl+C closes the editor like Esc, except that a second Ctrl+C at the
+    /// close confirmation escalates to the host instead of closing just the
+    /// editor.
+    fn handle_ctrl_c(&mut self) -> CancellationEvent {
+        self.settle_dirty();
+        if self.ctrl_c_behavior != CtrlCBehavior::CopyOnly && self.confirm_close.take().is_some() {
+            self.move_to_trash();
+            self.emit(PreferencesEditorEvent::QuitRequested);
+            return CancellationEvent::Escalate;
+        }
+        let selection = self.selection_range();
+        match (self.ctrl_c_behavior, selection) {
+            (CtrlCBehavior::Close, _) | (CtrlCBehavior::CopyOrClose, None) => {
+                self.request_close();
+            }
+            (_, Some(range)) => self.copy_range(range),
+            (CtrlCBehavior::CopyOnly, None) => {
+                self.status_message = Some(StatusMessage::info("Press Esc to close".to_string()));
+            }
+        }
+        CancellationEvent::Handled
+    }
+
+    /// Copy the selection to the clipboard and delete it. The buffer is left
+    /// alone when the clipboard is unavailable.
+    fn cut_range(&mut self, range: Range<usize>) {
+        let text = &self.textarea.text()[range.clone()];
+        let chars = text.chars().count();
+        if let Err(err) = clipboard_copy::copy_text(text) {
+            self.status_message = Some(StatusMessage::error(format!(
+                "Failed to cut selection: {err}"
+            )));
+            return;
+        }
+        self.selection_anchor = None;
+        let start = range.start;
+        self.apply_editor_change(|ta| {
+            ta.replace_range(range, "");
+            ta.set_cursor(start);
+        });
+        self.status_message = Some(StatusMessage::success(format!("Cut {chars} characters")));
+    }
+
+    fn copy_range(&mut self, range: Range<usize>) {
+        let text = &self.textarea.text()[range];
+        self.status_message = Some(match clipboard_copy::copy_text(text) {
+            Ok(()) => StatusMessage::success(format!("Copied {} characters", text.chars().count())),
+            Err(err) => StatusMessage::error(format!("Failed to copy selection: {err}")),
+        });
+    }
+
+    /// Close the editor, first asking whether to save when there are
+    /// unsaved changes.
+    fn request_close(&mut self) {
+        self.settle_dirty();
+        if self.dirty {
+            let summary = ChangeSummary::between(&self.last_saved_text, self.textarea.text())
+                .map(|summary| summary.describe());
+            self.confirm_close = Some(ConfirmClose::new(summary, self.theme.warning));
+        } else {
+            self.close();
+        }
+    }
+
+    /// Finish the editor, throwing away any unsaved changes.
+    fn close(&mut self) {
+        if self.dirty {
+            self.move_to_trash();
+        }
+        self.complete = true;
+        self.emit(if self.dirty {
+            PreferencesEditorEvent::Discarded
+        } else {
+            PreferencesEditorEvent::Closed
+        });
+        self.emit(PreferencesEditorEvent::SessionEnded {
+            stats: self.session.stats(self.textarea.text()),
+        });
+    }
+
+    /// Act on the close confirmation once an option has been picked. Save
+    /// only closes when the save went through rather than stopping to ask
+    /// something else.
+    fn resolve_confirm_close(&mut self) {
+        let Some(choice) = self.confirm_close.as_ref().and_then(ConfirmClose::choice) else {
+            return;
+        };
+        self.confirm_close = None;
+        match choice {
+            CloseChoice::Save => {
+                self.save();
+                if !self.dirty {
+                    self.close();
+                }
+            }
+            CloseChoice::Discard => self.close(),
+            CloseChoice::Cancel => {}
+        }
+    }
+
+    /// Stash the buffer in the session trash before it is thrown away. This
+    /// is best-effort: a failure is logged rather than blocking the operation.
+    fn move_to_trash(&self) {
+        if self.textarea.text().is_empty() {
+            return;
+        }
+        if let Err(err) = self.trash.put(self.textarea.text()) {
+            tracing::warn!("failed to keep discarded preferences: {err}");
+        }
+    }
+
+    /// Replace the buffer with the last discarded contents. The current
+    /// buffer takes its place in the trash, so recovering twice swaps back.
+    fn recover_from_trash(&mut self) {
+        let recovered = match self.trash.last() {
+            Ok(Some(contents)) => contents,
+            Ok(None) => {
+                self.status_message = Some(StatusMessage::info("Nothing to recover".to_string()));
+                return;
+            }
+            Err(err) => {
+                self.status_message = Some(StatusMessage::error(format!(
+                    "Failed to read discarded contents: {err}"
+                )));
+                return;
+            }
+        };
+        if recovered == self.textarea.text() {
+            return;
+        }
+        self.move_to_trash();
+        self.selection_anchor = None;
+        let end = self.textarea.text().len();
+        self.apply_editor_change(|ta| {
+            ta.replace_range(0..end, &recovered);
+            ta.set_cursor(0);
+        });
+        self.status_message = Some(StatusMessage::info(
+            "Recovered the last discarded contents".to_string(),
+        ));
+    }
+
+    /// Insert `now` at the cursor, written with the `chrono` `format`.
+    fn insert_time(&mut self, now: DateTime<Local>, format: &str) {
+        let stamp = now.format(format).to_string();
+        self.selection_anchor = None;
+        self.apply_editor_change(|ta| ta.insert_str(&stamp));
+    }
+
+    /// Type a bracket or quote together with its closer, or step over the
+    /// closer already at the cursor. Returns false when the key should be
+    /// typed normally.
+    fn type_auto_pair(&mut self, key_event: KeyEvent) -> bool {
+        let KeyCode::Char(ch) = key_event.code else {
+            return false;
+        };
+        if !self.auto_pair || !(key_event.modifiers - KeyModifiers::SHIFT).is_empty() {
+            return false;
+        }
+        let cursor = self.textarea.cursor();
+        match text_ops::auto_pair(self.textarea.text(), cursor, ch) {
+            Some(AutoPair::StepOver) => self.textarea.set_cursor(cursor + ch.len_utf8()),
+            Some(AutoPair::Insert(close)) => {
+                self.apply_editor_change(|ta| {
+                    ta.insert_str(&format!("{ch}{close}"));
+                    ta.set_cursor(cursor + ch.len_utf8());
+                });
+            }
+            None => return false,
+        }
+        true
+    }
+
+    /// Hand the buffer to `$EDITOR`.
+    fn edit_in_external_editor(&mut self) {
+        let editor = std::env::var("EDITOR").unwrap_or_default();
+        self.start_external_edit(&editor);
+    }
+
+    /// Ask the app to open the buffer in `editor`. It runs the editor once
+    /// the terminal is free and reports back through
+    /// [`BottomPaneView::on_external_command_finished`].
+    fn start_external_edit(&mut self, editor: &str) {
+        if self.read_only {
+            self.status_message = Some(StatusMessage::warning(format!(
+                "{} is read-only",
+                self.display_path
+            )));
+            return;
+        }
+        let Some(name) = editor.split_whitespace().next() else {
+            self.status_message = Some(StatusMessage::error(
+                "$EDITOR is not set — set it to edit in another editor".to_string(),
+            ));
+            return;
+        };
+        let Some(app_event_tx) = &self.app_event_tx else {
+            self.status_message = Some(StatusMessage::error(
+                "Editing in another editor is not available here".to_string(),
+            ));
+            return;
+        };
+        match ExternalEdit::start(editor, &self.path, self.textarea.text()) {
+            Ok((edit, command)) => {
+                self.external_edit = Some(edit);
+                app_event_tx.send(AppEvent::RunExternalCommand(command));
+            }
+            Err(err) => {
+                self.status_message = Some(StatusMessage::error(format!("{name}: {err}")));
+            }
+        }
+    }
+
+    /// Take back what was saved in the external editor as an edit that can
+    /// be undone. A failed run leaves the buffer as it was.
+    fn finish_external_edit(&mut self, status: io::Result<ExitStatus>) {
+        let Some(edit) = self.external_edit.take() else {
+            return;
+        };
+        let name = edit.name.clone();
+        let edited = match edit.finish(status) {
+            Ok(edited) => edited,
+            Err(err) => {
+                self.status_message = Some(StatusMessage::error(format!("{name}: {err}")));
+                return;
+            }
+        };
+        if edited == self.textarea.text() {
+            self.status_message = Some(StatusMessage::info(
+                "No changes from the editor".to_string(),
+            ));
+            return;
+        }
+        self.selection_anchor = None;
+        let end = self.textarea.text().len();
+        let cursor = self.textarea.cursor();
+        if self.apply_editor_change(|ta| {
+            ta.replace_range(0..end, &edited);
+            ta.set_cursor(cursor);
+        }) {
+            self.status_message = Some(StatusMessage::info(format!(
+                "Took the editor's changes — {} to keep them",
+                self.save_key()
+            )));
+        }
+    }
+
+    /// Replace the buffer with the copy saved before the last save, as an
+    /// edit that can be undone and still needs saving.
+    fn restore_backup(&mut self) {
+        let backup = backup_path(&self.path);
+        let name = backup
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let restored = match fs::read_to_string(&backup) {
+            Ok(contents) => FileFormat::decode(contents).1,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.status_message = Some(StatusMessage::info(format!("No {name} to restore")));
+                return;
+            }
+            Err(err) => {
+                self.status_message = Some(StatusMessage::error(format!(
+                    "Failed to read {name}: {err}"
+                )));
+                return;
+            }
+        };
+        if restored == self.textarea.text() {
+            self.status_message = Some(StatusMessage::info(format!(
+                "The buffer already matches {name}"
+            )));
+            return;
+        }
+        self.selection_anchor = None;
+        let end = self.textarea.text().len();
+        if self.apply_editor_change(|ta| {
+            ta.replace_range(0..end, &restored);
+            ta.set_cursor(0);
+        }) {
+            self.status_message = Some(StatusMessage::info(format!(
+                "Restored {name} — {} to keep it",
+                self.save_key()
+            )));
+        }
+    }
+
+    /// The status message to show, unless it has expired.
+    fn visible_status(&self, now: Instant) -> Option<&StatusMessage> {
+        let message = self.status_message.as_ref()?;
+        (!message.is_expired(&self.status_persistence, now)).then_some(message)
+    }
+
+    fn status_span(&self) -> Span<'static> {
+        let now = Instant::now();
+        if let Some(message) = self.visible_status(now) {
+            if let (Some(remaining), Some(frame_requester)) = (
+                message.remaining(&self.status_persistence, now),
+                &self.frame_requester,
+            ) {
+                frame_requester.schedule_frame_in(remaining);
+            }
+            return message.as_span(&self.theme);
+        }
+
+        if let LoadState::Loading { since } = self.load_state {
+            return format!("{} Loading…", spinner_frame(since)).dim();
+        }
+
+        if let Some(occurrences) = &self.occurrences {
+            return format!("Editing {} occurrences · Esc to stop", occurrences.count())
+                .fg(self.theme.accent);
+        }
+
+        if let Some(run) = &self.validation_run {
+            if let Some(frame_requester) = &self.frame_requester {
+                frame_requester.schedule_frame_in(SPINNER_INTERVAL);
+            }
+            return format!("{} Validating… (Ctrl+C cancels)", spinner_frame(run.since)).dim();
+        }
+
+        if let Some(run) = &self.shell_run {
+            if let Some(frame_requester) = &self.frame_requester {
+                frame_requester.schedule_frame_in(SPINNER_INTERVAL);
+            }
+            return format!(
+                "{} Running `{}`… (Ctrl+C cancels)",
+                spinner_frame(run.since),
+                run.command
+            )
+            .dim();
+        }
+
+        if let Some(busy) = &self.busy {
+            if let Some(frame_requester) = &self.frame_requester {
+                frame_requester.schedule_frame_in(SPINNER_INTERVAL);
+            }
+            return busy.as_span();
+        }
+
+        if let Some(error) = self.validation_error_at_cursor() {
+            return format!("Line {}: {}", error.line, error.message).fg(self.theme.error);
+        }
+
+        if let Some((_, index, total)) = self.conflict_at_cursor() {
+            return format!("Conflict {index} of {total} — Alt+1 ours · Alt+2 theirs · Alt+3 both")
+                .fg(self.theme.warning);
+        }
+
+        if !self.mixed_indent.is_empty() {
+            let count = self.mixed_indent.len();
+            let noun = if count == 1 {
+                "line mixes"
+            } else {
+                "lines mix"
+            };
+            return format!("{count} {noun} tabs and spaces — Ctrl+X i fixes")
+                .fg(self.theme.warning);
+        }
+
+        if self.dirty || self.dirty_settled(now) {
+            format!("Unsaved changes — press {} to save", self.save_key()).fg(self.theme.accent)
+        } else if self.relative_save_status
+            && let Some(saved_at) = self.last_saved_at
+        {
+            let (ago, next_change) = relative_time(now.duration_since(saved_at));
+            if let Some(frame_requester) = &self.frame_requester {
+                frame_requester.schedule_frame_in(next_change);
+            }
+            format!("Saved to {} {ago}", self.display_path).fg(self.theme.success)
+        } else {
+            "All changes saved".to_string().fg(self.theme.success)
+        }
+    }
+
+    /// Columns left of the text: the gutter bar plus, when shown, the line
+    /// numbers right-aligned to the widest one and a space.
+    fn gutter_width(&self) -> u16 {
+        if !self.show_line_numbers {
+            return 2;
+        }
+        let lines = self.textarea.text().split('\n').count();
+        2 + lines.to_string().len() as u16 + 1
+    }
+
+    fn input_height(&self, width: u16) -> u16 {
+        let usable_width = width.saturating_sub(self.gutter_width());
+        let desired = self.textarea.desired_height(usable_width);
+        let text_height = if self.split.is_some() {
+            // Two panes and the divider between them.
+            desired.clamp(4, SPLIT_PANE_ROWS) * 2 + 1
+        } else {
+            desired.clamp(4, 18)
+        };
+        text_height.saturating_add(1)
+    }
+
+    /// The top and bottom panes of a split, in that order.
+    fn split_rects(&self, area: Rect) -> Option<(Rect, Rect)> {
+        self.split.as_ref()?;
+        let full = self.full_text_rect(area)?;
+        let pane = full.height.saturating_sub(1) / 2;
+        let top = Rect {
+            height: pane,
+            ..full
+        };
+        let bottom = Rect {
+            y: full.y + pane + 1,
+            height: full.height.saturating_sub(pane + 1),
+            ..full
+        };
+        Some((top, bottom))
+    }
+
+    /// Where the focused pane draws its text.
+    fn textarea_rect(&self, area: Rect) -> Option<Rect> {
+        match (self.split_rects(area), &self.split) {
+            (Some((_, bottom)), Some(split)) if split.focus_bottom => Some(bottom),
+            (Some((top, _)), _) => Some(top),
+            _ => self.full_text_rect(area),
+        }
+    }
+
+    fn full_text_rect(&self, area: Rect) -> Option<Rect> {
+        let gutter = self.gutter_width();
+        if area.width < gutter + 2 {
+            return None;
+        }
+        let text_area_height = self.input_height(area.width).saturating_sub(1);
+        if text_area_height == 0 {
+            return None;
+        }
+        Some(Rect {
+            x: area.x.saturating_add(gutter),
+            y: area.y.saturating_add(4),
+            width: area.width.saturating_sub(gutter),
+            height: text_area_height,
+        })
+    }
+
+    /// Draw the unfocused pane of a split and the divider above the lower
+    /// pane.
+    fn render_other_split_pane(&self, area: Rect, buf: &mut Buffer) {
+        let (Some(split), Some((top, bottom))) = (&self.split, self.split_rects(area)) else {
+            return;
+        };
+        let rect = if split.focus_bottom { top } else { bottom };
+        let mut state = split.state.borrow_mut();
+        self.textarea
+            .render_with_cursor(split.cursor, rect, buf, &mut state);
+        self.render_line_numbers(rect, state.scroll(), buf);
+        self.render_error_gutter(rect, state.scroll(), buf);
+
+        let divider = "─".repeat(bottom.width as usize);
+        let label = if split.focus_bottom { " ↓ " } else { " ↑ " };
+        buf.set_span(bottom.x, bottom.y - 1, &divider.dim(), bottom.width);
+        buf.set_span(
+            bottom.x,
+            bottom.y - 1,
+            &format!("{label}F6 switch · Alt+T unsplit ").dim(),
+            bottom.width,
+        );
+    }
+
+    /// Number the first visual row of each line in the gutter left of
+    /// `rect`; wrapped continuation rows stay blank.
+    fn render_line_numbers(&self, rect: Rect, scroll: u16, buf: &mut Buffer) {
+        let width = self.gutter_width().saturating_sub(3);
+        if !self.show_line_numbers || rect.x < width + 1 {
+            return;
+        }
+        let x = rect.x - width - 1;
+        let text = self.textarea.text();
+        let starts = self.textarea.visual_line_starts(rect.width);
+        let mut line = 1;
+        let mut counted = 0;
+        for (row, start) in starts.iter().enumerate() {
+            if row >= scroll as usize + rect.height as usize {
+                break;
+            }
+            line += text[counted..*start].matches('\n').count();
+            counted = *start;
+            let first_row = *start == 0 || text[..*start].ends_with('\n');
+            if row < scroll as usize || !first_row {
+                continue;
+            }
+            let label = format!("{line:>width$}", width = width as usize);
+            buf.set_span(
+                x,
+                rect.y + (row - scroll as usize) as u16,
+                &label.dim(),
+                width,
+            );
+        }
+    }
+
+    /// Replace the gutter next to lines with validation errors by an error
+    /// glyph. Only the first visual row of a wrapped line is marked.
+    fn render_error_gutter(&self, rect: Rect, scroll: u16, buf: &mut Buffer) {
+        let gutter = self.gutter_width();
+        if self.validation_errors.is_empty() || rect.x < gutter {
+            return;
+        }
+        let text = self.textarea.text();
+        let starts = self.textarea.visual_line_starts(rect.width);
+        for (row, start) in starts
+            .iter()
+            .skip(scroll as usize)
+            .take(rect.height as usize)
+            .enumerate()
+        {
+            if *start > 0 && !text[..*start].ends_with('\n') {
+                continue;
+            }
+            let line = text[..*start].matches('\n').count() + 1;
+            if self
+                .validation_errors
+                .iter()
+                .any(|error| error.line == line)
+            {
+                buf.set_span(
+                    rect.x - gutter,
+                    rect.y + row as u16,
+                    &self.glyphs.error(&self.theme),
+                    2,
+                );
+            }
+        }
+    }
+
+    /// Mark which part of the buffer is in view in the column just left of
+    /// the text, when it does not all fit.
+    fn render_scroll_indicator(&self, rect: Rect, scroll: u16, buf: &mut Buffer) {
+        if rect.x == 0 {
+            return;
+        }
+        let total = self.textarea.visual_line_starts(rect.width).len();
+        let Some(thumb) = scroll_thumb(total, rect.height, scroll) else {
+            return;
+        };
+        for row in thumb {
+            buf.set_span(
+                rect.x - 1,
+                rect.y + row,
+                &self.glyphs.scroll_thumb(&self.theme),
+                1,
+            );
+        }
+    }
+
+    fn scratch_height(&self) -> u16 {
+        if self.scratch.is_some() {
+            SCRATCH_TEXT_ROWS + 1
+        } else {
+            0
+        }
+    }
+
+    /// Text area of the scratch pane, below its label row.
+    fn scratch_rect(&self, area: Rect) -> Option<Rect> {
+        if self.scratch.is_none() || area.width < 4 {
+            return None;
+        }
+        let y = area
+            .y
+            .saturating_add(4)
+            .saturating_add(self.input_height(area.width));
+        let bottom = area.y.saturating_add(area.height);
+        Some(Rect {
+            x: area.x.saturating_add(2),
+            y,
+            width: area.width.saturating_sub(2),
+            height: SCRATCH_TEXT_ROWS.min(bottom.saturating_sub(y)),
+        })
+        .filter(|rect| rect.height > 0)
+    }
+}
+
+impl PreferencesEditorView {
+    fn run_command(&mut self, command: EditorCommand) {
+        self.last_command = Some(command);
+        match command {
+            EditorCommand::AlignSeparators => self.align_selection(),
+            EditorCommand::SqueezeBlankLines => self.squeeze_blank_lines(),
+            EditorCommand::InsertMarkdownLink => self.insert_markdown_link(),
+            EditorCommand::AsciiPunctuation => self.asciify_selection(),
+            EditorCommand::SortFrontMatter => self.sort_front_matter(),
+            EditorCommand::ToggleComment => self.toggle_comment(),
+            EditorCommand::HardWrapLine => self.hard_wrap_line(),
+            EditorCommand::JoinLines => self.join_continuation_lines(),
+            EditorCommand::PrettyPrintJson => self.pretty_print_json_fence(),
+            EditorCommand::IncrementNumber => self.step_number_at_cursor(1),
+            EditorCommand::DecrementNumber => self.step_number_at_cursor(-1),
+            EditorCommand::DuplicateSelection => self.duplicate_selection(1),
+            EditorCommand::ToggleListCsv => self.toggle_list_csv(),
+            EditorCommand::InsertFrontMatter => {
+                self.selection_anchor = None;
+                self.insert_front_matter();
+            }
+            EditorCommand::InsertUuid => {
+                self.selection_anchor = None;
+                self.insert_uuid();
+            }
+        }
+    }
+
+    fn repeat_last_command(&mut self) {
+        match self.last_command {
+            Some(command) => self.run_command(command),
+            None => {
+                self.status_message =
+                    Some(StatusMessage::info("No command to repeat yet".to_string()));
+            }
+        }
+    }
+
+    /// Run the command `key_event` is bound to in the keymap, if any.
+    fn handle_keymap_key(&mut self, key_event: KeyEvent) -> bool {
+        let keymap = self.keymap;
+        if keymap.save_as.matches(key_event) {
+            self.open_prompt(PromptKind::SaveAs);
+        } else if keymap.save.matches(key_event) {
+            self.save();
+        } else if keymap.close.matches(key_event) {
+            self.handle_ctrl_c();
+        } else if keymap.search.matches(key_event) {
+            self.open_prompt(PromptKind::Search);
+        } else if keymap.go_to_line.matches(key_event) {
+            self.open_prompt(PromptKind::GoToLine);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn dispatch_key_event(&mut self, key_event: KeyEvent) {
+        if !self.is_ready() {
+            if self.keymap.close.matches(key_event) {
+                self.handle_ctrl_c();
+            }
+            return;
+        }
+
+        if self.prompt.is_some() {
+            self.handle_prompt_key(key_event);
+            return;
+        }
+        if let Some(overlay) = &mut self.overlay {
+            let toggle = (key_event.modifiers.contains(KeyModifiers::ALT)
+                || key_event.modifiers.contains(KeyModifiers::CONTROL))
+                && matches!(key_event.code, KeyCode::Char('d') | KeyCode::Char('D'));
+            if toggle {
+                self.overlay = None;
+            } else {
+                overlay.handle_key(key_event);
+            }
+            return;
+        }
+
+        if self.handle_occurrences_key(key_event) {
+            return;
+        }
+
+        if let Some(path) = self.confirm_save_as.take() {
+            if key_event.code == KeyCode::Enter {
+                self.save_as(path);
+            } else {
+                self.status_message = None;
+            }
+            return;
+        }
+
+        if self.pending_format.is_some() {
+            match key_event.code {
+                KeyCode::Enter | KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    self.resolve_pending_format(true);
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') => self.resolve_pending_format(false),
+                _ => {}
+            }
+            return;
+        }
+
+        let modifiers = key_event.modifiers;
+        if std::mem::take(&mut self.ctrl_x_prefix) && self.handle_ctrl_x_key(key_event) {
+            return;
+        }
+        if self.handle_keymap_key(key_event) {
+            return;
+        }
+        if modifiers.contains(KeyModifiers::CONTROL) || modifiers.contains(KeyModifiers::SUPER) {
+            match key_event.code {
+                KeyCode::Char('t') | KeyCode::Char('T') => {
+                    let format = if key_event.code == KeyCode::Char('T')
+                        || modifiers.contains(KeyModifiers::SHIFT)
+                    {
+                        TIMESTAMP_FORMAT
+                    } else {
+                        DATE_FORMAT
+                    };
+                    self.insert_time(Local::now(), format);
+                    return;
+                }
+                KeyCode::Char('x') | KeyCode::Char('X')
+                    if modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    match self.selection_range() {
+                        Some(range) => self.cut_range(range),
+                        None => self.ctrl_x_prefix = true,
+                    }
+                    return;
+                }
+                KeyCode::Char('a') | KeyCode::Char('A') => {
+                    self.select_all();
+                    return;
+                }
+                KeyCode::Char('d') | KeyCode::Char('D') => {
+                    self.show_unsaved_diff();
+                    return;
+                }
+                KeyCode::Char('e') | KeyCode::Char('E') => {
+                    self.edit_in_external_editor();
+                    return;
+                }
+                KeyCode::Char('w') | KeyCode::Char('W')
+                    if key_event.code == KeyCode::Char('W')
+                        || modifiers.contains(KeyModifiers::SHIFT) =>
+                {
+                    self.set_soft_wrap(!self.textarea.wraps());
+                    self.status_message = Some(StatusMessage::info(
+                        if self.textarea.wraps() {
+                            "Soft wrap on"
+                        } else {
+                            "Soft wrap off: long lines are cut off"
+                        }
+                        .to_string(),
+                    ));
+                    return;
+                }
+                KeyCode::Char('l') | KeyCode::Char('L') => {
+                    self.recenter();
+                    return;
+                }
+                KeyCode::Char('r') | KeyCode::Char('R') => {
+                    self.reload_from_disk();
+                    return;
+                }
+                KeyCode::Char('o') | KeyCode::Char('O') => {
+                    self.follow_include();
+                    return;
+                }
+                KeyCode::Char('z') if !modifiers.contains(KeyModifiers::SHIFT) => {
+                    self.undo();
+                    return;
+                }
+                KeyCode::Char('y')
+                | KeyCode::Char('Y')
+                | KeyCode::Char('Z')
+                | KeyCode::Char('z') => {
+                    self.redo();
+                    return;
+                }
+                _ => {}
+            }
+        }
+        if modifiers.contains(KeyModifiers::ALT)
+            && matches!(key_event.code, KeyCode::Char('n') | KeyCode::Char('N'))
+        {
+            self.toggle_scratch();
+            return;
+        }
+        if let Some(scratch) = &mut self.scratch {
+            if key_event.code == KeyCode::Tab && modifiers.is_empty() {
+                self.scratch_focused = !self.scratch_focused;
+                return;
+            }
+            if self.scratch_focused {
+                scratch.textarea.input(key_event);
+                return;
+            }
+        }
+
+        if std::mem::take(&mut self.fold_prefix) {
+            self.status_message = None;
+            if self.handle_fold_key(key_event) {
+                return;
+            }
+        }
+        if modifiers.contains(KeyModifiers::ALT)
+            && matches!(key_event.code, KeyCode::Char('z') | KeyCode::Char('Z'))
+        {
+            self.fold_prefix = true;
+            self.status_message = Some(StatusMessage::info(
+                "Fold: z toggle section · m fold all · r unfold all · s solo · u unsolo"
+                    .to_string(),
+            ));
+            return;
+        }
+
+        if let Some(motion) = selection_motion(key_event) {
+            self.extend_selection(motion);
+            return;
+        }
+        if let Some(forward) = paragraph_motion(key_event) {
+            self.selection_anchor = None;
+            self.move_by_paragraph(forward);
+            return;
+        }
+        match (key_event.code, modifiers) {
+            (KeyCode::PageDown | KeyCode::PageUp, KeyModifiers::NONE) => {
+                self.selection_anchor = None;
+                self.move_by_page(key_event.code == KeyCode::PageDown);
+                return;
+            }
+            (KeyCode::Home, KeyModifiers::CONTROL) => {
+                self.selection_anchor = None;
+                self.textarea.set_cursor(0);
+                return;
+            }
+            (KeyCode::End, KeyModifiers::CONTROL) => {
+                self.selection_anchor = None;
+                self.textarea.set_cursor(self.textarea.text().len());
+                return;
+            }
+            (KeyCode::Left | KeyCode::Right, KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                self.selection_anchor = None;
+                let text = self.textarea.text();
+                let cursor = self.textarea.cursor();
+                let target = if key_event.code == KeyCode::Left {
+                    text_ops::previous_word_start(text, cursor)
+                } else {
+                    text_ops::next_word_end(text, cursor)
+                };
+                self.textarea.set_cursor(target);
+                return;
+            }
+            (KeyCode::Char('w'), KeyModifiers::CONTROL)
+            | (KeyCode::Backspace, KeyModifiers::ALT) => {
+                self.selection_anchor = None;
+                self.delete_previous_word();
+                return;
+            }
+            _ => {}
+        }
+        if key_event.code == KeyCode::F(6) && self.split.is_some() {
+            self.switch_split_focus();
+            return;
+        }
+        if key_event.code == KeyCode::Insert && modifiers.is_empty() {
+            self.overtype = !self.overtype;
+            return;
+        }
+        self.unfold_before_edit(key_event);
+        if self.overtype
+            && let KeyCode::Char(ch) = key_event.code
+            && (modifiers - KeyModifiers::SHIFT).is_empty()
+            && !ch.is_control()
+        {
+            self.selection_anchor = None;
+            self.overtype_char(ch);
+            return;
+        }
+        if modifiers.contains(KeyModifiers::ALT) {
+            match key_event.code {
+                KeyCode::Char('.') => {
+                    self.repeat_last_command();
+                    return;
+                }
+                KeyCode::Char('h') | KeyCode::Char('H') => {
+                    self.export_html();
+                    return;
+                }
+                KeyCode::Char('v') | KeyCode::Char('V') => {
+                    self.open_prompt(PromptKind::EnvVar);
+                    return;
+                }
+                KeyCode::Char('`') => {
+                    self.open_prompt(PromptKind::FenceLanguage);
+                    return;
+                }
+                KeyCode::Char('s') | KeyCode::Char('S') => {
+                    self.open_prompt(PromptKind::Surround);
+                    return;
+                }
+                KeyCode::Char('Y') => {
+                    self.open_prompt(PromptKind::DuplicateCount);
+                    return;
+                }
+                KeyCode::Char('x') | KeyCode::Char('X') => {
+                    if self.allow_shell_commands {
+                        self.open_prompt(PromptKind::ShellCommand);
+                    } else {
+                        self.status_message = Some(StatusMessage::info(
+                            "Shell commands are disabled for this editor".to_string(),
+                        ));
+                    }
+                    return;
+                }
+                KeyCode::Char('/') => {
+                    self.show_hint = !self.show_hint;
+                    return;
+                }
+                KeyCode::Char('g') | KeyCode::Char('G') => {
+                    self.show_line_numbers = !self.show_line_numbers;
+                    return;
+                }
+                KeyCode::Char('t') | KeyCode::Char('T') => {
+                    self.toggle_split();
+                    return;
+                }
+                KeyCode::Char('e') | KeyCode::Char('E') => {
+                    self.focus_unsaved = !self.focus_unsaved;
+                    return;
+                }
+                KeyCode::Char('r') | KeyCode::Char('R') => {
+                    self.recover_from_trash();
+                    return;
+                }
+                KeyCode::Char('d') | KeyCode::Char('D') => {
+                    self.show_docs();
+                    return;
+                }
+                KeyCode::Char('l') | KeyCode::Char('L') => {
+                    self.select_occurrences();
+                    return;
+                }
+                KeyCode::Char('i') | KeyCode::Char('I') => {
+                    self.open_docs_url();
+                    return;
+                }
+                KeyCode::Char(digit @ '1'..='3') => {
+                    self.resolve_conflict(match digit {
+                        '1' => Resolution::Ours,
+                        '2' => Resolution::Theirs,
+                        _ => Resolution::Both,
+                    });
+                    return;
+                }
+                code => {
+                    if let Some(command) = EditorCommand::for_alt_key(code) {
+                        self.run_command(command);
+                        return;
+                    }
+                }
+            }
+        }
+
+        if self.replace_selection(key_event) {
+            return;
+        }
+        self.selection_anchor = None;
+        match key_event.code {
+            KeyCode::Tab if modifiers.is_empty() => {
+                let spaces = " ".repeat(self.tab_spaces);
+                self.apply_editor_change(|ta| ta.insert_str(&spaces));
+                return;
+            }
+            KeyCode::BackTab => {
+                self.dedent_line();
+                return;
+            }
+            _ => {}
+        }
+        if self.type_auto_pair(key_event) {
+            return;
+        }
+        self.apply_editor_change(|ta| ta.input(key_event));
+        self.refresh_key_completion(key_event);
+    }
+}
+
+impl BottomPaneView for PreferencesEditorView {
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        self.tick();
+        if let Some(confirm) = &mut self.confirm_close {
+            confirm.handle_key_event(key_event);
+            self.resolve_confirm_close();
+            return;
+        }
+        let ctrl_l = key_event.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key_event.code, KeyCode::Char('l') | KeyCode::Char('L'));
+        if !ctrl_l {
+            self.recenter_cycle = None;
+        }
+        if !self.handle_completion_key(key_event) {
+            self.dispatch_key_event(key_event);
+        }
+        if self.append_only && self.overlay.is_none() {
+            self.pin_cursor_to_end();
+        }
+        self.reveal_cursor();
+        self.sync_highlights();
+    }
+
+    fn on_ctrl_c(&mut self) -> CancellationEvent {
+        if self.cancel_validation() {
+            return CancellationEvent::Handled;
+        }
+        if let Some(run) = self.shell_run.take() {
+            self.status_message = Some(StatusMessage::info(format!(
+                "`{}` cancelled; nothing was inserted",
+                run.command
+            )));
+            return CancellationEvent::Handled;
+        }
+        self.handle_ctrl_c()
+    }
+
+    fn on_external_command_finished(&mut self, status: io::Result<ExitStatus>) {
+        self.finish_external_edit(status);
+    }
+
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent, area: Rect) -> bool {
+        if !self.mouse
+            || !self.is_ready()
+            || self.prompt.is_some()
+            || self.overlay.is_some()
+            || self.confirm_close.is_some()
+        {
+            return false;
+        }
+        let Some(rect) = self.textarea_rect(area) else {
+            return false;
+        };
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let (x, y) = (mouse_event.column, mouse_event.row);
+                if !rect.contains(Position::new(x, y)) {
+                    return false;
+                }
+                let state = *self.textarea_state.borrow();
+                let Some(pos) = self.textarea.position_at(rect, state, x, y) else {
+                    return false;
+                };
+                self.selection_anchor = None;
+                self.textarea.set_cursor(pos);
+                self.reveal_cursor();
+                self.sync_highlights();
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let delta = if mouse_event.kind == MouseEventKind::ScrollUp {
+                    -MOUSE_SCROLL_ROWS
+                } else {
+                    MOUSE_SCROLL_ROWS
+                };
+                self.selection_anchor = None;
+                let mut state = self.textarea_state.borrow_mut();
+                self.textarea.scroll_by(&mut state, rect, delta);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn wants_mouse(&self) -> bool {
+        self.mouse
+    }
+
+    fn on_tick(&mut self) {
+        self.tick();
+    }
+
+    fn on_focus_changed(&mut self, focused: bool) {
+        self.settle_dirty();
+        // Blanking a populated file always needs an explicit confirmation.
+        if !focused
+            && self.save_on_blur
+            && self.dirty
+            && self.is_ready()
+            && !self.would_blank_file()
+        {
+            self.save();
+        }
+    }
+
+    fn on_esc(&mut self) -> CancellationEvent {
+        if let Some(confirm) = &mut self.confirm_close {
+            let event = confirm.on_esc();
+            self.resolve_confirm_close();
+            return event;
+        }
+        if self.key_completion.take().is_some() {
+            return CancellationEvent::Handled;
+        }
+        if self.overlay.take().is_some() || self.occurrences.take().is_some() {
+            self.sync_highlights();
+            return CancellationEvent::Handled;
+        }
+        if self.confirm_save_as.take().is_some() {
+            self.status_message = None;
+        } else if self.pending_format.is_some() {
+            self.resolve_pending_format(false);
+        } else if self.prompt.take().is_none() {
+            self.request_close();
+        }
+        CancellationEvent::Handled
+    }
+
+    fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    fn desired_height(&self, width: u16) -> u16 {
+        self.input_height(width)
+            .saturating_add(self.scratch_height())
+            .saturating_add(self.hint_height())
+            .saturating_add(3)
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        // Title
+        let mut y = area.y;
+        let mut title = vec![
+            self.glyphs.gutter(&self.theme),
+            "Edit preferences.md".bold(),
+        ];
+        if self.read_only {
+            title.push(" [read-only]".fg(self.theme.warning));
+        }
+        Paragraph::new(Line::from(title)).render(
+            Rect {
+                x: area.x,
+                y,
+                width: area.width,
+                height: 1,
+            },
+            buf,
+        );
+        y = y.saturating_add(1);
+
+        // Path line
+        Paragraph::new(Line::from(vec![
+            self.glyphs.gutter(&self.theme),
+            format!("Path: {}", self.display_path).dim(),
+        ]))
+        .render(
+            Rect {
+                x: area.x,
+                y,
+                width: area.width,
+                height: 1,
+            },
+            buf,
+        );
+        y = y.saturating_add(1);
+
+        // Status line
+        let mut status = vec![self.glyphs.gutter(&self.theme)];
+        if let Some(prompt) = &self.prompt {
+            status.push(prompt.kind.label().bold());
+            status.push(prompt.input.clone().into());
+            if prompt.kind == PromptKind::Search
+                && let Some(search_status) = &self.search_status
+            {
+                status.push(format!("  {search_status}").dim());
+            }
+            if let Some(message) = self.visible_status(Instant::now()) {
+                status.push("  ".into());
+                status.push(message.as_span(&self.theme));
+            }
+        } else if let Some(overlay) = &self.overlay {
+            status.push(overlay.title.clone().bold());
+            status.push(" · ↑/↓ scroll · Esc back to editing".dim());
+        } else {
+            status.push(self.status_span());
+        }
+        if self.overtype {
+            status.push(" · ".dim());
+            status.push("OVERTYPE".bold());
+        }
+        if self.append_only {
+            status.push(" · ".dim());
+            status.push("APPEND-ONLY".bold());
+        }
+        if self.prompt.is_none()
+            && self.overlay.is_none()
+            && self.folded_heading_at_cursor().is_some()
+        {
+            status.push(" · ".dim());
+            status.push("FOLDED".bold());
+            status.push(" typing unfolds".dim());
+        }
+        // Cursor position, right-aligned so it never shifts the message.
+        let position = (self.is_ready() && self.overlay.is_none())
+            .then(|| {
+                let (line, col) = text_ops::line_col(self.textarea.text(), self.textarea.cursor());
+                format!(" Ln {line}, Col {col}")
+            })
+            .filter(|position| position.width() + 2 <= usize::from(area.width));
+        let position_width = position
+            .as_ref()
+            .map_or(0, |position| position.width() as u16);
+        Paragraph::new(Line::from(status)).render(
+            Rect {
+                x: area.x,
+                y,
+                width: area.width - position_width,
+                height: 1,
+            },
+            buf,
+        );
+        if let Some(position) = position {
+            Paragraph::new(Line::from(position.dim())).render(
+                Rect {
+                    x: area.x + area.width - position_width,
+                    y,
+                    width: position_width,
+                    height: 1,
+                },
+                buf,
+            );
+        }
+        y = y.saturating_add(1);
+
+        // Editor area with gutter
+        let input_height = self.input_height(area.width);
+        let input_area = Rect {
+            x: area.x,
+            y,
+            width: area.width,
+            height: input_height,
+        };
+        if input_area.width >= 2 {
+            for row in 0..input_area.height {
+                Paragraph::new(Line::from(vec![self.glyphs.gutter(&self.theme)])).render(
+                    Rect {
+                        x: input_area.x,
+                        y: input_area.y.saturating_add(row),
+                        width: 2,
+                        height: 1,
+                    },
+                    buf,
+                );
+            }
+
+            let text_area_height = input_area.height.saturating_sub(1);
+            if text_area_height > 0 {
+                if input_area.width > 2 {
+                    Clear.render(
+                        Rect {
+                            x: input_area.x.saturating_add(2),
+                            y: input_area.y,
+                            width: input_area.width.saturating_sub(2),
+                            height: 1,
+                        },
+                        buf,
+                    );
+                }
+                if let Some(rect) = self.textarea_rect(area)
+                    && !self.is_ready()
+                {
+                    if let (LoadState::Loading { .. }, Some(frame_requester)) =
+                        (&self.load_state, &self.frame_requester)
+                    {
+                        frame_requester.schedule_frame_in(SPINNER_INTERVAL);
+                    }
+                    let message = match self.load_state {
+                        LoadState::Failed => "Contents unavailable — press Esc to close",
+                        _ => "Fetching contents…",
+                    };
+                    Paragraph::new(Line::from(message.dim())).render(rect, buf);
+                } else if let (Some(rect), Some(confirm)) =
+                    (self.textarea_rect(area), &self.confirm_close)
+                {
+                    confirm.render(rect, buf);
+                } else if let (Some(rect), Some(overlay)) =
+                    (self.textarea_rect(area), &self.overlay)
+                {
+                    overlay.render(rect, buf);
+                } else if let (Some(rect), Some(formatted)) =
+                    (self.textarea_rect(area), &self.pending_format)
+                {
+                    Paragraph::new(diff_preview_lines(
+                        self.textarea.text(),
+                        formatted,
+                        &self.theme,
+                    ))
+                    .render(rect, buf);
+                } else if let Some(rect) = self.textarea_rect(area) {
+                    let mut state = self.textarea_state.borrow_mut();
+                    if let Some(position) = self.recenter.take() {
+                        self.textarea.scroll_cursor_to_row(
+                            &mut state,
+                            rect,
+                            position.row(rect.height),
+                        );
+                    }
+                    if let Some(row) = self.page_scroll_row.take() {
+                        self.textarea.scroll_cursor_to_row(&mut state, rect, row);
+                    }
+                    StatefulWidgetRef::render_ref(&(&self.textarea), rect, buf, &mut state);
+                    self.page_rows.set(rect.height);
+                    if let Some((_, y)) = self.textarea.cursor_pos_with_state(rect, *state) {
+                        self.cursor_row.set(y - rect.y);
+                    }
+                    self.render_line_numbers(rect, state.scroll(), buf);
+                    self.render_error_gutter(rect, state.scroll(), buf);
+                    self.render_scroll_indicator(rect, state.scroll(), buf);
+                    self.render_other_split_pane(area, buf);
+                    if let Some(completion) = &self.key_completion
+                        && let Some(pos) = self.textarea.cursor_pos_with_state(rect, *state)
+                    {
+                        completion.render(pos, rect, buf);
+                    }
+                    if self.textarea.text().is_empty() {
+                        Paragraph::new(Line::from(vec![
+                            format!(
+                                "Type your preferences and press {} to save",
+                                self.save_key()
+                            )
+                            .dim(),
+                        ]))
+                        .render(rect, buf);
+                    }
+                }
+            }
+        }
+        y = y.saturating_add(input_area.height);
+
+        if let Some(scratch) = &self.scratch {
+            let label = if self.scratch_focused {
+                "Scratch (not saved) · Tab back to file"
+            } else {
+                "Scratch (not saved) · Tab to focus"
+            };
+            Paragraph::new(Line::from(vec![
+                self.glyphs.gutter(&self.theme),
+                label.dim().italic(),
+            ]))
+            .render(
+                Rect {
+                    x: area.x,
+                    y,
+                    width: area.width,
+                    height: 1,
+                },
+                buf,
+            );
+            if let Some(rect) = self.scratch_rect(area) {
+                for row in 0..rect.height {
+                    Paragraph::new(Line::from(vec![self.glyphs.gutter(&self.theme)])).render(
+                        Rect {
+                            x: area.x,
+                            y: rect.y.saturating_add(row),
+                            width: 2,
+                            height: 1,
+                        },
+                        buf,
+                    );
+                }
+                let mut state = scratch.state.borrow_mut();
+                StatefulWidgetRef::render_ref(&(&scratch.textarea), rect, buf, &mut state);
+            }
+            y = y.saturating_add(self.scratch_height());
+        }
+
+        if !self.show_hint {
+            return;
+        }
+
+        // Blank spacer before hint
+        if y < area.y.saturating_add(area.height) {
+            Clear.render(
+                Rect {
+                    x: area.x,
+                    y,
+                    width: area.width,
+                    height: 1,
+                },
+                buf,
+            );
+        }
+
+        let hint_y = y.saturating_add(1);
+        if hint_y < area.y.saturating_add(area.height) {
+            let (chars, words) = text_ops::char_and_word_count(self.textarea.text());
+            let mut spans = vec![
+                self.glyphs.gutter(&self.theme),
+                format!(
+                    "{} save · Esc close · {chars} chars · {words} words",
+                    self.save_key()
+                )
+                .dim(),
+            ];
+            if let Some(delay) = self.auto_save {
+                spans.push(" · ".dim());
+                spans.push(format!("auto-save {}s", delay.as_secs()).fg(self.theme.accent));
+            }
+            Paragraph::new(Line::from(spans)).render(
+                Rect {
+                    x: area.x,
+                    y: hint_y,
+                    width: area.width,
+                    height: 1,
+                },
+                buf,
+            );
+        }
+    }
+
+    fn handle_paste(&mut self, pasted: String) -> bool {
+        if !self.is_ready() || self.confirm_close.is_some() {
+            return false;
+        }
+        if self.occurrences.take().is_some() {
+            self.sync_highlights();
+        }
+        if let Some(prompt) = &mut self.prompt {
+            prompt
+                .input
+                .push_str(pasted.lines().next().unwrap_or_default());
+            return true;
+        }
+        if let Some(scratch) = &mut self.scratch
+            && self.scratch_focused
+        {
+            scratch.textarea.insert_str(&pasted);
+            return true;
+        }
+        self.selection_anchor = None;
+        let changed = match self.fence_next_paste.take() {
+            Some(language) => {
+                self.paste_as_code_fence(&pasted, &language);
+                true
+            }
+            None => self.apply_editor_change(|ta| ta.insert_str(&pasted)),
+        };
+        if self.append_only {
+            self.pin_cursor_to_end();
+        }
+        self.reveal_cursor();
+        self.sync_highlights();
+        changed
+    }
+
+    fn cursor_pos(&self, area: Rect) -> Option<(u16, u16)> {
+        if !self.is_ready() {
+            return None;
+        }
+        if let Some(prompt) = &self.prompt {
+            let width = 2 + prompt.kind.label().width() + prompt.input.width();
+            let x = area.x.saturating_add(width as u16);
+            return (area.width > 2 && area.height > 2)
+                .then(|| (x.min(area.right().saturating_sub(1)), area.y + 2));
+        }
+        if self.overlay.is_some() || self.confirm_close.is_some() {
+            return None;
+        }
+        if let Some(scratch) = &self.scratch
+            && self.scratch_focused
+        {
+            let rect = self.scratch_rect(area)?;
+            let state = *scratch.state.borrow();
+            return scratch.textarea.cursor_pos_with_state(rect, state);
+        }
+        let rect = self.textarea_rect(area)?;
+        let state = *self.textarea_state.borrow();
+        self.textarea.cursor_pos_with_state(rect, state)
+    }
+
+    fn cursor_shape(&self) -> Option<CursorShape> {
+        self.cursor_shape
+    }
+}
+
+impl ChangeSummary {
+    fn between(saved: &str, current: &str) -> Option<Self> {
+        if saved == current {
+            return None;
+        }
+
+        let mut added = 0;
+        let mut removed = 0;
+        for hunk in diffy::create_patch(saved, current).hunks() {
+            for line in hunk.lines() {
+                match line {
+                    diffy::Line::Insert(_) => added += 1,
+                    diffy::Line::Delete(_) => removed += 1,
+                    diffy::Line::Context(_) => {}
+                }
+            }
+        }
+
+        let unchanged_prefix = saved
+            .lines()
+            .zip(current.lines())
+            .take_while(|(before, after)| before == after)
+            .count();
+        let first_text = current
+            .lines()
+            .nth(unchanged_prefix)
+            .or_else(|| saved.lines().nth(unchanged_prefix))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+
+        Some(Self {
+            added,
+            removed,
+            first_line: unchanged_prefix + 1,
+            first_text,
+        })
+    }
+
+    fn describe(&self) -> String {
+        let mut description = format!(
+            "+{} −{} lines, first change at line {}",
+            self.added, self.removed, self.first_line
+        );
+        if !self.first_text.is_empty() {
+            description.push_str(": ");
+            description.push_str(&truncate_preview(
+                &self.first_text,
+                CHANGE_PREVIEW_MAX_CHARS,
+            ));
+        }
+        description
+    }
+}
+
+/// Shift+motion keys extend the selection; returns the motion with Shift
+/// stripped so the textarea moves the cursor as usual.
+fn selection_motion(key_event: KeyEvent) -> Option<KeyEvent> {
+    if !key_event.modifiers.contains(KeyModifiers::SHIFT) {
+        return None;
+    }
+    match key_event.code {
+        KeyCode::Left
+        | KeyCode::Right
+        | KeyCode::Up
+        | KeyCode::Down
+        | KeyCode::Home
+        | KeyCode::End => Some(KeyEvent::new(
+            key_event.code,
+            key_event.modifiers.difference(KeyModifiers::SHIFT),
+        )),
+        _ => None,
+    }
+}
+
+/// Ctrl+Down / Alt+} move to the next paragraph and Ctrl+Up / Alt+{ to the
+/// previous one. Returns whether the motion goes forward.
+fn paragraph_motion(key_event: KeyEvent) -> Option<bool> {
+    let modifiers = key_event.modifiers;
+    match key_event.code {
+        KeyCode::Down if modifiers == KeyModifiers::CONTROL => Some(true),
+        KeyCode::Up if modifiers == KeyModifiers::CONTROL => Some(false),
+        KeyCode::Char('}') if modifiers.contains(KeyModifiers::ALT) => Some(true),
+        KeyCode::Char('{') if modifiers.contains(KeyModifiers::ALT) => Some(false),
+        _ => None,
+    }
+}
+
+/// Indices of the lines in `current` that were added or changed since
+/// `saved`.
+fn changed_lines(saved: &str, current: &str) -> Vec<usize> {
+    let mut changed = Vec::new();
+    for hunk in diffy::create_patch(saved, current).hunks() {
+        let mut index = hunk.new_range().start().saturating_sub(1);
+        for line in hunk.lines() {
+            match line {
+                diffy::Line::Insert(_) => {
+                    changed.push(index);
+                    index += 1;
+                }
+                diffy::Line::Context(_) => index += 1,
+                diffy::Line::Delete(_) => {}
+            }
+        }
+    }
+    changed
+}
+
+/// Unified-diff lines from `before` to `after`, coloured for a preview.
+fn diff_preview_lines(before: &str, after: &str, theme: &EditorTheme) -> Vec<Line<'static>> {
+    let patch = diffy::create_patch(before, after);
+    let mut lines = Vec::new();
+    for hunk in patch.hunks() {
+        if !lines.is_empty() {
+            lines.push(Line::from("⋮".dim()));
+        }
+        for line in hunk.lines() {
+            lines.push(match line {
+                diffy::Line::Insert(text) => {
+                    Line::from(format!("+ {}", text.trim_end_matches('\n')).fg(theme.success))
+                }
+                diffy::Line::Delete(text) => {
+                    Line::from(format!("- {}", text.trim_end_matches('\n')).fg(theme.error))
+                }
+                diffy::Line::Context(text) => {
+                    Line::from(format!("  {}", text.trim_end_matches('\n')).dim())
+                }
+            });
+        }
+    }
+    lines
+}
+
+/// Replace `path` with `contents` so that it holds either the old or the new
+/// contents, never a truncated mix: write a sibling temp file, then rename it
+/// over `path`. The temp file is removed if either step fails. A symlink is
+/// followed so the file it points at is replaced, not the link, and the
+/// file keeps its permissions.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let target = match fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(err) => return Err(err),
+    };
+    let permissions = fs::metadata(&target).ok().map(|meta| meta.permissions());
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".tmp-{}", std::process::id()));
+    let tmp = target.with_file_name(name);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // Replacing a file: start private so the new contents are never
+    // readable by more people than the original allows.
+    #[cfg(unix)]
+    if permissions.is_some() {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, &target));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Rows of a `visible`-row viewport, scrolled down `scroll` rows through
+/// `total` rows, that the scroll indicator fills. `None` when everything
+/// fits.
+fn scroll_thumb(total: usize, visible: u16, scroll: u16) -> Option<Range<u16>> {
+    let visible_rows = visible as usize;
+    if total <= visible_rows || visible == 0 {
+        return None;
+    }
+    let len = (visible_rows * visible_rows / total).max(1);
+    let max_scroll = total - visible_rows;
+    let travel = visible_rows - len;
+    let start = (scroll as usize).min(max_scroll) * travel / max_scroll;
+    Some(start as u16..(start + len) as u16)
+}
+
+/// Where the contents a save replaces are kept: `path` with `.bak` appended.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Copy `path` to its backup before it is overwritten. Nothing to back up
+/// is not an error.
+fn back_up(path: &Path) -> io::Result<()> {
+    match fs::copy(path, backup_path(path)) {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether links cannot be opened locally: an SSH session, or Linux without
+/// a display server.
+fn is_headless() -> bool {
+    let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+    if set("SSH_CONNECTION") || set("SSH_TTY") {
+        return true;
+    }
+    cfg!(target_os = "linux") && !set("DISPLAY") && !set("WAYLAND_DISPLAY")
+}
+
+/// Whether the file's extension marks it as markdown.
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown")
+        })
+}
+
+/// Line comment token for the file's type, `#` when the extension is unknown.
+fn default_comment_token(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("rs" | "js" | "ts" | "jsonc" | "c" | "cpp" | "go" | "java") => "//",
+        Some("ini") => ";",
+        Some("lua" | "sql") => "--",
+        _ => "#",
+    }
+}
+
+fn default_ascii_replacements() -> Vec<(char, String)> {
+    [
+        ('\u{2018}', "'"),
+        ('\u{2019}', "'"),
+        ('\u{201A}', "'"),
+        ('\u{201B}', "'"),
+        ('\u{2032}', "'"),
+        ('\u{201C}', "\""),
+        ('\u{201D}', "\""),
+        ('\u{201E}', "\""),
+        ('\u{201F}', "\""),
+        ('\u{2033}', "\""),
+        ('\u{2013}', "-"),
+        ('\u{2014}', "--"),
+        ('\u{2212}', "-"),
+        ('\u{2026}', "..."),
+        ('\u{00A0}', " "),
+    ]
+    .into_iter()
+    .map(|(from, to)| (from, to.to_string()))
+    .collect()
+}
+
+/// True when strongly right-to-left characters (Hebrew, Arabic and related
+/// scripts) outnumber strongly left-to-right letters.
+fn is_dominantly_rtl(text: &str) -> bool {
+    let mut rtl = 0usize;
+    let mut ltr = 0usize;
+    for ch in text.chars() {
+        if is_rtl_char(ch) {
+            rtl += 1;
+        } else if ch.is_alphabetic() {
+            ltr += 1;
+        }
+    }
+    rtl > ltr
+}
+
+fn is_rtl_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}'
+    )
+}
+
+fn truncate_preview(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    fn view_with(text: &str) -> PreferencesEditorView {
+        PreferencesEditorView::new(PathBuf::from("preferences.md"), text.to_string())
+    }
+
+    /// Render `view` at `width` and return each row as text.
+    fn render_rows(view: &PreferencesEditorView, width: u16) -> Vec<String> {
+        let area = Rect::new(0, 0, width, view.desired_height(width));
+        let mut buf = Buffer::empty(area);
+        view.render(area, &mut buf);
+        (0..area.height)
+            .map(|y| (0..width).map(|x| buf[(x, y)].symbol()).collect())
+            .collect()
+    }
+
+    fn type_str(view: &mut PreferencesEditorView, text: &str) {
+        for ch in text.chars() {
+            view.handle_key_event(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn change_summary_counts_lines_and_finds_first_change() {
+        let summary = ChangeSummary::between("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!(
+            summary,
+            Some(ChangeSummary {
+                added: 2,
+                removed: 1,
+                first_line: 2,
+                first_text: "B".to_string(),
+            })
+        );
+        assert_eq!(ChangeSummary::between("same", "same"), None);
+    }
+
+    #[test]
+    fn change_summary_previews_removed_line_when_buffer_is_shorter() {
+        let summary = ChangeSummary::between("keep\ndrop me\n", "keep\n").expect("summary");
+        assert_eq!(summary.first_line, 2);
+        assert_eq!(
+            summary.describe(),
+            "+0 −1 lines, first change at line 2: drop me"
+        );
+    }
+
+    #[test]
+    fn text_direction_is_detected_from_dominant_script() {
+        assert!(is_dominantly_rtl("שפה = עברית"));
+        assert!(!is_dominantly_rtl("language = עברית"));
+
+        let mut view = view_with("theme = dark");
+        assert!(!view.textarea.is_rtl());
+        view.set_text_direction(TextDirection::RightToLeft);
+        assert!(view.textarea.is_rtl());
+
+        let mut view = view_with("");
+        type_str(&mut view, "שלום");
+        assert!(view.textarea.is_rtl());
+    }
+
+    #[test]
+    fn alt_u_inserts_uuid_in_configured_format() {
+        let mut view = view_with("");
+        view.handle_key_event(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::ALT));
+        let hyphenated = view.textarea.text().to_string();
+        assert_eq!(hyphenated.len(), 36);
+        assert_eq!(hyphenated.matches('-').count(), 4);
+        assert!(view.dirty);
+
+        let mut view = view_with("");
+        view.set_uuid_format(UuidFormat::Compact);
+        view.handle_key_event(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::ALT));
+        let compact = view.textarea.text();
+        assert_eq!(compact.len(), 32);
+        assert!(compact.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    fn press(view: &mut PreferencesEditorView, code: KeyCode, modifiers: KeyModifiers) {
+        view.handle_key_event(KeyEvent::new(code, modifiers));
+    }
+
+    #[test]
+    fn shift_motion_selects_and_plain_motion_clears() {
+        let mut view = view_with("abc");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Right, KeyModifiers::SHIFT);
+        press(&mut view, KeyCode::Right, KeyModifiers::SHIFT);
+        assert_eq!(view.selection_range(), Some(0..2));
+        press(&mut view, KeyCode::Left, KeyModifiers::NONE);
+        assert_eq!(view.selection_range(), None);
+    }
+
+    #[test]
+    fn align_command_lines_up_selected_separators() {
+        let mut view = view_with("a = 1\nlonger = 2\n\nuntouched=3");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Down, KeyModifiers::SHIFT);
+        press(&mut view, KeyCode::End, KeyModifiers::SHIFT);
+        press(&mut view, KeyCode::Char('='), KeyModifiers::ALT);
+
+        assert_eq!(
+            view.textarea.text(),
+            "a      = 1\nlonger = 2\n\nuntouched=3"
+        );
+        assert_eq!(view.selection_range(), None);
+        assert!(view.dirty);
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Aligned 1 line on '='")
+        );
+    }
+
+    #[test]
+    fn squeeze_command_collapses_blank_runs_in_buffer() {
+        let mut view = view_with("\na\n\n\nb\n\n");
+        view.set_trim_blank_edges(true);
+        press(&mut view, KeyCode::Char('q'), KeyModifiers::ALT);
+
+        assert_eq!(view.textarea.text(), "a\n\nb\n");
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Removed 3 blank lines")
+        );
+    }
+
+    #[test]
+    fn link_command_wraps_selection_and_places_cursor_in_url() {
+        let mut view = view_with("see docs here");
+        view.textarea.set_cursor(4);
+        for _ in 0..4 {
+            press(&mut view, KeyCode::Right, KeyModifiers::SHIFT);
+        }
+        press(&mut view, KeyCode::Char('k'), KeyModifiers::ALT);
+        type_str(&mut view, "https://x");
+        assert_eq!(view.textarea.text(), "see [docs](https://x) here");
+
+        let mut view = view_with("");
+        press(&mut view, KeyCode::Char('k'), KeyModifiers::ALT);
+        type_str(&mut view, "label");
+        assert_eq!(view.textarea.text(), "[label]()");
+    }
+
+    #[test]
+    fn ctrl_c_behavior_controls_closing() {
+        let mut view = view_with("abc");
+        view.on_ctrl_c();
+        assert!(view.is_complete());
+
+        let mut view = view_with("abc");
+        view.set_ctrl_c_behavior(CtrlCBehavior::CopyOrClose);
+        view.on_ctrl_c();
+        assert!(view.is_complete());
+
+        let mut view = view_with("abc");
+        view.set_ctrl_c_behavior(CtrlCBehavior::CopyOnly);
+        view.on_ctrl_c();
+        assert!(!view.is_complete());
+        view.on_esc();
+        assert!(view.is_complete());
+    }
+
+    #[test]
+    fn ctrl_c_with_selection_copies_instead_of_closing() {
+        let mut view = view_with("abc");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Right, KeyModifiers::SHIFT);
+        view.on_ctrl_c();
+        assert!(!view.is_complete());
+        assert_eq!(view.selection_range(), Some(0..1));
+    }
+
+    #[test]
+    fn crlf_files_are_edited_with_lf_and_saved_with_crlf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        std::fs::write(&path, "a: 1\r\nb: 2\r\n").unwrap();
+        let mut view = PreferencesEditorView::open(path.clone(), None, false).unwrap();
+        assert_eq!(view.textarea.text(), "a: 1\nb: 2\n");
+
+        type_str(&mut view, "c: 3\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a: 1\r\nb: 2\r\nc: 3\r\n"
+        );
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn ctrl_d_toggles_a_diff_of_unsaved_changes() {
+        let mut view = view_with("a: 1\nb: 2\n");
+        press(&mut view, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert!(view.overlay.is_none());
+        assert!(view.status_span().content.contains("No unsaved changes"));
+
+        type_str(&mut view, "c: 3\n");
+        let cursor = view.textarea.cursor();
+        press(&mut view, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        let rows = render_rows(&view, 40).concat();
+        assert!(rows.contains("+ c: 3"), "{rows}");
+        assert!(rows.contains("  b: 2"));
+
+        type_str(&mut view, "x");
+        press(&mut view, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert!(view.overlay.is_none());
+        assert_eq!(view.textarea.text(), "a: 1\nb: 2\nc: 3\n");
+        assert_eq!(view.textarea.cursor(), cursor);
+    }
+
+    #[test]
+    fn saving_broken_markdown_warns_but_still_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        type_str(&mut view, "# Prefs\n```\nls\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# Prefs\n```\nls\n"
+        );
+        assert!(!view.dirty);
+        let status = view.status_span().content.to_string();
+        assert!(
+            status.contains("line 2: code fence is never closed"),
+            "{status}"
+        );
+    }
+
+    #[test]
+    fn bom_is_hidden_while_editing_and_saved_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let original = "\u{feff}# Prefs\na: 1\n";
+        std::fs::write(&path, original).unwrap();
+        let mut view = PreferencesEditorView::open(path.clone(), None, false).unwrap();
+        assert_eq!(view.textarea.text(), "# Prefs\na: 1\n");
+        let rows = render_rows(&view, 40);
+        assert!(rows[4].contains("# Prefs"));
+        assert!(!rows.concat().contains('\u{feff}'));
+
+        view.save();
+        assert_eq!(std::fs::read(&path).unwrap(), original.as_bytes());
+    }
+
+    #[test]
+    fn huge_contents_open_as_a_read_only_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let line = format!("{}\n", "x".repeat(99));
+        let contents = line.repeat(MAX_EDITABLE_BYTES / line.len() + 10);
+        let mut view = PreferencesEditorView::new(path.clone(), contents.clone());
+
+        assert!(view.textarea.text().len() <= MAX_EDITABLE_BYTES);
+        assert!(view.textarea.text().ends_with('\n'));
+        assert_eq!(view.preview_of, Some(contents.len()));
+        assert!(view.status_span().content.contains("too large to edit"));
+
+        type_str(&mut view, "y");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(!view.dirty);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn new_files_in_a_directory_that_refuses_writes_open_read_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+        // Root writes regardless of the mode, so there is nothing to check.
+        let probe = tempfile::tempfile_in(&locked);
+        if probe.is_err() {
+            assert!(!is_writable(&locked.join("sub").join("preferences.md")));
+        }
+        assert!(is_writable(&dir.path().join("sub").join("preferences.md")));
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn read_only_view_allows_navigation_but_refuses_edits() {
+        let mut view = view_with("a: 1\nb: 2\n");
+        view.set_read_only(true);
+        assert!(render_rows(&view, 40)[0].contains("[read-only]"));
+
+        press(&mut view, KeyCode::Up, KeyModifiers::NONE);
+        type_str(&mut view, "x");
+        press(&mut view, KeyCode::Char('a'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "a: 1\nb: 2\n");
+        assert!(!view.dirty);
+        assert!(view.status_span().content.contains("is read-only"));
+        assert_eq!(view.selection_range(), Some(0..10));
+    }
+
+    #[test]
+    fn ctrl_a_selects_everything_and_typing_replaces_it() {
+        let mut view = view_with("a: 1\nb: 2\n");
+        press(&mut view, KeyCode::Char('a'), KeyModifiers::CONTROL);
+        assert_eq!(view.selection_range(), Some(0..10));
+
+        type_str(&mut view, "x");
+        assert_eq!(view.textarea.text(), "x");
+        assert!(view.dirty);
+        assert_eq!(view.selection_range(), None);
+
+        press(&mut view, KeyCode::Char('a'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "");
+    }
+
+    #[test]
+    fn ctrl_x_cuts_the_selection_and_marks_the_buffer_dirty() {
+        let mut view = view_with("abc");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Right, KeyModifiers::SHIFT);
+        press(&mut view, KeyCode::Right, KeyModifiers::SHIFT);
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert!(!view.ctrl_x_prefix);
+        // Headless test machines may have no clipboard, and then nothing is
+        // deleted.
+        if view.status_span().content.starts_with("Failed to cut") {
+            assert_eq!(view.textarea.text(), "abc");
+        } else {
+            assert_eq!(view.textarea.text(), "c");
+            assert!(view.dirty);
+            assert_eq!(view.selection_range(), None);
+        }
+
+        // Without a selection Ctrl+X is the command prefix.
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert!(view.ctrl_x_prefix);
+    }
+
+    #[test]
+    fn overtype_replaces_characters_but_not_newlines() {
+        let mut view = view_with("abc\nd");
+        view.textarea.set_cursor(1);
+        press(&mut view, KeyCode::Insert, KeyModifiers::NONE);
+        type_str(&mut view, "XYZ");
+        assert_eq!(view.textarea.text(), "aXYZ\nd");
+        assert!(view.dirty);
+
+        press(&mut view, KeyCode::Insert, KeyModifiers::NONE);
+        type_str(&mut view, "!");
+        assert_eq!(view.textarea.text(), "aXYZ!\nd");
+    }
+
+    #[test]
+    fn alt_dot_repeats_the_last_command() {
+        let mut view = view_with("");
+        press(&mut view, KeyCode::Char('.'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "");
+
+        press(&mut view, KeyCode::Char('k'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::End, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Char('.'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "[]()[]()");
+        assert_eq!(view.last_command, Some(EditorCommand::InsertMarkdownLink));
+    }
+
+    #[test]
+    fn expired_status_falls_back_to_save_state() {
+        let mut view = view_with("abc");
+        view.status_message = Some(StatusMessage::info("hint".to_string()));
+        let later = Instant::now() + Duration::from_secs(5);
+        assert!(view.visible_status(later).is_none());
+
+        view.set_status_persistence(StatusPersistence {
+            info: None,
+            ..StatusPersistence::default()
+        });
+        assert!(view.visible_status(later).is_some());
+    }
+
+    #[test]
+    fn emptying_a_populated_file_needs_a_second_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        std::fs::write(&path, "keep me\n").unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "keep me\n".to_string());
+        view.textarea.set_text("  \n");
+        view.dirty = true;
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(
+            std::fs::read_to_string(&path).ok().as_deref(),
+            Some("keep me\n")
+        );
+        assert!(view.dirty);
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn saving_over_an_external_change_needs_a_second_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        std::fs::write(&path, "mine\n").unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "mine\n".to_string());
+        std::fs::write(&path, "someone else's\n").unwrap();
+        view.textarea.set_text("mine, edited\n");
+        view.dirty = true;
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "someone else's\n");
+        assert!(view.dirty);
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "mine, edited\n");
+        assert!(!view.dirty);
+        assert!(!view.changed_on_disk());
+    }
+
+    #[test]
+    fn ctrl_r_reloads_from_disk_after_confirming_unsaved_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        std::fs::write(&path, "one\n").unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "one\n".to_string());
+        type_str(&mut view, "two\n");
+        std::fs::write(&path, "three\n").unwrap();
+
+        press(&mut view, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "one\ntwo\n");
+        press(&mut view, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "three\n");
+        assert!(!view.dirty);
+        assert!(!view.changed_on_disk());
+
+        std::fs::remove_file(&path).unwrap();
+        press(&mut view, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "three\n");
+        assert!(view.status_span().content.starts_with("Failed to reload"));
+    }
+
+    #[test]
+    fn ctrl_o_opens_the_included_file_after_confirming_unsaved_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        std::fs::write(dir.path().join("other.md"), "other\n").unwrap();
+        let mut view = PreferencesEditorView::new(
+            path,
+            "include: missing.md\ninclude: other.md\n".to_string(),
+        );
+        view.set_include_pattern(Some(Regex::new(r"include:\s*(\S+)").unwrap()));
+
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert!(view.status_span().content.ends_with("does not exist"));
+
+        view.textarea.set_cursor(20);
+        type_str(&mut view, "#");
+        press(&mut view, KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert!(view.textarea.text().starts_with("include: missing.md\n#"));
+        press(&mut view, KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "other\n");
+        assert_eq!(view.path, dir.path().join("other.md"));
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn windowed_open_loads_the_tail_and_saves_by_appending() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let head: String = (0..100).map(|i| format!("entry {i}\n")).collect();
+        std::fs::write(&path, format!("{head}last\n")).unwrap();
+
+        let mut view = PreferencesEditorView::open_windowed(path.clone(), 16).unwrap();
+        assert_eq!(view.textarea.text(), "entry 99\nlast\n");
+        assert!(view.append_only);
+        type_str(&mut view, "new\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{head}last\nnew\n")
+        );
+        assert!(!view.dirty);
+
+        let view = PreferencesEditorView::open_windowed(path, 1 << 20).unwrap();
+        assert!(view.window.is_none());
+        assert!(!view.append_only);
+    }
+
+    #[test]
+    fn windowed_saves_append_without_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let big: String = (0..1000).map(|i| format!("entry {i}\n")).collect();
+        std::fs::write(&path, &big).unwrap();
+
+        let mut view = PreferencesEditorView::open_windowed(path.clone(), 256).unwrap();
+        type_str(&mut view, "new: 1\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        assert!(!backup_path(&path).exists());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{big}new: 1\n")
+        );
+    }
+
+    /// Timing check on a file far larger than the window; run with
+    /// `cargo test -p codex-tui -- --ignored windowed_save_timing`.
+    #[test]
+    #[ignore = "writes a 512 MB file; run by hand to time windowed saves"]
+    fn windowed_save_timing_on_a_huge_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let line = "key: a fairly ordinary preference value\n";
+        let big = line.repeat((512 << 20) / line.len());
+        std::fs::write(&path, &big).unwrap();
+
+        let started = Instant::now();
+        let mut view = PreferencesEditorView::open_windowed(path.clone(), 64 << 10).unwrap();
+        let opened = started.elapsed();
+        type_str(&mut view, "new: 1\n");
+        let started = Instant::now();
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        let saved = started.elapsed();
+
+        // Neither should depend on the size of the file, only the window's.
+        assert!(opened < Duration::from_secs(1), "open took {opened:?}");
+        assert!(saved < Duration::from_secs(1), "save took {saved:?}");
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            (big.len() + "new: 1\n".len()) as u64
+        );
+    }
+
+    #[test]
+    fn windows_larger_than_the_edit_limit_stay_appendable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let line = "key: value\n";
+        let big = line.repeat(2 * MAX_EDITABLE_BYTES / line.len());
+        std::fs::write(&path, &big).unwrap();
+
+        let mut view =
+            PreferencesEditorView::open_windowed(path.clone(), 4 * MAX_EDITABLE_BYTES as u64)
+                .unwrap();
+        assert!(view.window.is_some());
+        assert!(view.append_only);
+        assert!(!view.read_only);
+        assert_eq!(view.preview_of, None);
+        assert!(view.textarea.text().len() <= MAX_EDITABLE_BYTES);
+        assert!(view.status_span().content.starts_with("Large file"));
+
+        type_str(&mut view, "new: 1\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            (big.len() + "new: 1\n".len()) as u64
+        );
+    }
+
+    #[test]
+    fn following_an_include_leaves_the_window_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let head: String = (0..100).map(|i| format!("entry {i}\n")).collect();
+        // No trailing newline: append-only mode keeps the cursor at the end,
+        // which is then still on the include line.
+        std::fs::write(&path, format!("{head}include: other.md")).unwrap();
+        std::fs::write(dir.path().join("other.md"), "other\n").unwrap();
+
+        let mut view = PreferencesEditorView::open_windowed(path, 32).unwrap();
+        assert!(view.append_only);
+        view.set_include_pattern(Some(Regex::new(r"include: (\S+)").unwrap()));
+        press(&mut view, KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "other\n");
+        assert!(view.window.is_none());
+        assert!(!view.append_only);
+    }
+
+    #[test]
+    fn ctrl_shift_s_saves_to_a_new_path_and_confirms_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        std::fs::write(dir.path().join("taken.md"), "taken\n").unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "a: 1\n".to_string());
+        type_str(&mut view, "b: 2\n");
+
+        let save_as = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        press(&mut view, KeyCode::Char('S'), save_as);
+        type_str(&mut view, "fork/profile.md");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        let fork = dir.path().join("fork/profile.md");
+        assert_eq!(std::fs::read_to_string(&fork).unwrap(), "a: 1\nb: 2\n");
+        assert!(!path.exists());
+        assert_eq!(view.path, fork);
+        assert!(!view.dirty);
+
+        press(&mut view, KeyCode::Char('S'), save_as);
+        type_str(&mut view, "../taken.md");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(view.confirm_save_as.is_some());
+        press(&mut view, KeyCode::Char('n'), KeyModifiers::NONE);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("taken.md")).unwrap(),
+            "taken\n"
+        );
+
+        press(&mut view, KeyCode::Char('S'), save_as);
+        type_str(&mut view, "../taken.md");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("taken.md")).unwrap(),
+            "a: 1\nb: 2\n"
+        );
+        assert_eq!(view.path, dir.path().join("fork/../taken.md"));
+    }
+
+    #[test]
+    fn scratch_pane_takes_focus_and_never_dirties_the_file() {
+        let mut view = view_with("file");
+        press(&mut view, KeyCode::Char('n'), KeyModifiers::ALT);
+        type_str(&mut view, "note");
+        assert_eq!(view.textarea.text(), "file");
+        assert!(!view.dirty);
+        assert_eq!(
+            view.scratch.as_ref().map(|scratch| scratch.textarea.text()),
+            Some("note")
+        );
+
+        press(&mut view, KeyCode::Tab, KeyModifiers::NONE);
+        type_str(&mut view, "!");
+        assert_eq!(view.textarea.text(), "file!");
+
+        press(&mut view, KeyCode::Char('n'), KeyModifiers::ALT);
+        assert!(view.scratch.is_none());
+    }
+
+    #[test]
+    fn fold_all_collapses_sections_and_keeps_cursor_visible() {
+        let mut view = view_with("# A\na1\na2\n# B\nb1\n");
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::Char('m'), KeyModifiers::NONE);
+
+        assert_eq!(view.folded, vec!["# A".to_string(), "# B".to_string()]);
+        assert_eq!(view.textarea.desired_height(40), 2);
+        // The cursor was at the end of B's body and moves to its heading.
+        assert_eq!(view.textarea.cursor(), "# A\na1\na2\n".len());
+
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::Char('r'), KeyModifiers::NONE);
+        assert!(view.folded.is_empty());
+        assert_eq!(view.textarea.desired_height(40), 6);
+    }
+
+    #[test]
+    fn editing_a_folded_heading_unfolds_it_first() {
+        let mut view = view_with("# A\na1\n# B\nb1\n");
+        view.textarea.set_cursor(3);
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::NONE);
+        assert_eq!(view.folded, vec!["# A".to_string()]);
+        assert!(render_rows(&view, 60)[2].contains("FOLDED typing unfolds"));
+
+        // Enter at the end of the heading would otherwise add a hidden line.
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(view.folded.is_empty());
+        assert_eq!(view.textarea.text(), "# A\n\na1\n# B\nb1\n");
+        assert!(!render_rows(&view, 60)[2].contains("FOLDED"));
+
+        // Moving along a folded heading leaves the fold alone.
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::NONE);
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        assert_eq!(view.folded, vec!["# A".to_string()]);
+    }
+
+    #[test]
+    fn undo_back_to_saved_text_clears_dirty_and_redo_reapplies() {
+        let mut view = view_with("a = 1\n");
+        type_str(&mut view, "b");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('k'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "\nb");
+
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "a = 1\nb");
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "a = 1\n");
+        assert!(!view.dirty);
+        assert!(
+            render_rows(&view, 40)
+                .concat()
+                .contains("All changes saved")
+        );
+
+        press(&mut view, KeyCode::Char('y'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "a = 1\nb");
+        assert!(view.dirty);
+        press(
+            &mut view,
+            KeyCode::Char('Z'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+        assert_eq!(view.textarea.text(), "\nb");
+
+        // A new edit drops the redo history.
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        type_str(&mut view, "c");
+        press(&mut view, KeyCode::Char('y'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "ca = 1\nb");
+    }
+
+    #[test]
+    fn ctrl_l_cycles_cursor_row_through_center_top_and_bottom() {
+        let text: String = (0..40).map(|i| format!("line {i}\n")).collect();
+        let mut view = view_with(&text);
+        view.textarea.set_cursor(text.find("line 20").unwrap());
+        let area = Rect::new(0, 0, 40, view.desired_height(40));
+        let cursor_row = |view: &PreferencesEditorView| {
+            let mut buf = Buffer::empty(area);
+            view.render(area, &mut buf);
+            let rect = view.textarea_rect(area).unwrap();
+            view.cursor_pos(area).unwrap().1 - rect.y
+        };
+        let height = view.textarea_rect(area).unwrap().height;
+        assert_eq!(cursor_row(&view), height - 1);
+
+        press(&mut view, KeyCode::Char('l'), KeyModifiers::CONTROL);
+        assert_eq!(cursor_row(&view), (height - 1) / 2);
+        press(&mut view, KeyCode::Char('l'), KeyModifiers::CONTROL);
+        assert_eq!(cursor_row(&view), 0);
+        press(&mut view, KeyCode::Char('l'), KeyModifiers::CONTROL);
+        assert_eq!(cursor_row(&view), height - 1);
+
+        // Any other key restarts the cycle at the center.
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Char('l'), KeyModifiers::CONTROL);
+        assert_eq!(cursor_row(&view), (height - 1) / 2);
+    }
+
+    #[test]
+    fn ctrl_arrows_hop_words_and_ctrl_w_deletes_back_to_the_line_start() {
+        let mut view = view_with("a\nkey.sub = on");
+        press(&mut view, KeyCode::Left, KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.cursor(), "a\nkey.sub = ".len());
+        press(&mut view, KeyCode::Left, KeyModifiers::ALT);
+        assert_eq!(view.textarea.cursor(), "a\nkey.sub ".len());
+        press(&mut view, KeyCode::Right, KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.cursor(), "a\nkey.sub =".len());
+
+        view.textarea.set_cursor(view.textarea.text().len());
+        for expected in ["a\nkey.sub = ", "a\nkey.sub ", "a\nkey.", "a\nkey", "a\n"] {
+            press(&mut view, KeyCode::Char('w'), KeyModifiers::CONTROL);
+            assert_eq!(view.textarea.text(), expected);
+        }
+        assert!(view.dirty);
+        press(&mut view, KeyCode::Backspace, KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "a");
+    }
+
+    #[test]
+    fn page_keys_move_by_a_screen_and_ctrl_home_end_jump_to_the_ends() {
+        let text: String = (0..60).map(|i| format!("line {i}\n")).collect();
+        let mut view = view_with(&text);
+        view.textarea.set_cursor(0);
+        let area = Rect::new(0, 0, 40, view.desired_height(40));
+        let height = view.textarea_rect(area).unwrap().height as usize;
+        let render = |view: &PreferencesEditorView| {
+            let mut buf = Buffer::empty(area);
+            view.render(area, &mut buf);
+            let rect = view.textarea_rect(area).unwrap();
+            view.cursor_pos(area).unwrap().1 - rect.y
+        };
+        let line_at_cursor = |view: &PreferencesEditorView| {
+            let text = view.textarea.text();
+            let cursor = view.textarea.cursor();
+            text[cursor..text_ops::line_end(text, cursor)].to_string()
+        };
+        assert_eq!(render(&view), 0);
+
+        press(&mut view, KeyCode::PageDown, KeyModifiers::NONE);
+        assert_eq!(line_at_cursor(&view), format!("line {height}"));
+        assert_eq!(render(&view), 0);
+        press(&mut view, KeyCode::PageUp, KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), 0);
+        assert!(!view.dirty);
+
+        press(&mut view, KeyCode::End, KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), "line 0".len());
+        press(&mut view, KeyCode::End, KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.cursor(), text.len());
+        assert_eq!(render(&view) as usize, height - 1);
+        press(&mut view, KeyCode::Home, KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.cursor(), 0);
+    }
+
+    #[test]
+    fn ctrl_f_search_cycles_matches_and_esc_keeps_view_open() {
+        let text: String = (0..30)
+            .map(|i| {
+                if i % 10 == 5 {
+                    "Model = x\n".to_string()
+                } else {
+                    format!("line {i}\n")
+                }
+            })
+            .collect();
+        let mut view = view_with(&text);
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('f'), KeyModifiers::CONTROL);
+        type_str(&mut view, "model");
+        let matches = text_ops::find_all(&text, "model");
+        assert_eq!(matches.len(), 3);
+
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), matches[0]);
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), matches[2]);
+        assert!(render_rows(&view, 40).concat().contains("3 of 3"));
+
+        // The match is scrolled into view inside the text area.
+        let area = Rect::new(0, 0, 40, view.desired_height(40));
+        let rect = view.textarea_rect(area).unwrap();
+        view.prompt = None;
+        let (_, y) = view.cursor_pos(area).unwrap();
+        assert!(rect.y <= y && y < rect.bottom());
+
+        press(&mut view, KeyCode::Char('f'), KeyModifiers::CONTROL);
+        type_str(&mut view, "model");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), matches[2]);
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), matches[0]);
+
+        assert_eq!(view.on_esc(), CancellationEvent::Handled);
+        assert!(view.prompt.is_none());
+        assert!(!view.is_complete());
+    }
+
+    #[test]
+    fn alt_y_duplicates_selection_with_separator_and_count() {
+        let mut view = view_with("[a]");
+        view.set_duplicate_separator(", ".to_string());
+        view.selection_anchor = Some(1);
+        view.textarea.set_cursor(2);
+        press(&mut view, KeyCode::Char('y'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "[a, a]");
+        assert_eq!(view.selection_range(), Some(4..5));
+
+        press(
+            &mut view,
+            KeyCode::Char('Y'),
+            KeyModifiers::ALT | KeyModifiers::SHIFT,
+        );
+        type_str(&mut view, "2");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "[a, a, a, a]");
+        assert_eq!(view.selection_range(), Some(10..11));
+        assert_eq!(view.textarea.cursor(), 11);
+    }
+
+    #[test]
+    fn undo_history_is_capped() {
+        let mut view = view_with("");
+        for _ in 0..UNDO_LIMIT + 10 {
+            type_str(&mut view, "x");
+        }
+        assert_eq!(view.undo_stack.len(), UNDO_LIMIT);
+    }
+
+    #[test]
+    fn key_completion_suggests_and_tab_accepts() {
+        let mut view = view_with("");
+        view.set_completion_keys(vec!["model".to_string(), "model_provider".to_string()]);
+        type_str(&mut view, "mo");
+        assert!(render_rows(&view, 40).concat().contains("model_provider"));
+
+        press(&mut view, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "model_provider");
+        assert!(view.key_completion.is_none());
+
+        // Esc dismisses without closing, and typing goes on as usual.
+        type_str(&mut view, "\nmo");
+        assert_eq!(view.on_esc(), CancellationEvent::Handled);
+        assert!(view.key_completion.is_none());
+        press(&mut view, KeyCode::Tab, KeyModifiers::NONE);
+        assert!(!view.is_complete());
+        assert!(view.textarea.text().starts_with("model_provider\nmo"));
+    }
+
+    #[test]
+    fn tab_inserts_spaces_and_shift_tab_dedents_the_line() {
+        let mut view = view_with("  a: 1\nb: 2");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "    a: 1\nb: 2");
+        assert_eq!(view.textarea.cursor(), 2);
+
+        view.textarea.set_cursor(5);
+        press(&mut view, KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(view.textarea.text(), "  a: 1\nb: 2");
+        assert_eq!(view.textarea.cursor(), 3);
+
+        view.set_tab_spaces(4);
+        view.textarea.set_cursor(8);
+        press(&mut view, KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(view.textarea.text(), "  a: 1\nb: 2");
+        press(&mut view, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "  a: 1\nb    : 2");
+    }
+
+    #[test]
+    fn append_only_keeps_saved_entries_and_cursor_at_end() {
+        let mut view = view_with("entry 1\n");
+        view.set_append_only(true);
+        press(&mut view, KeyCode::Up, KeyModifiers::NONE);
+        type_str(&mut view, "entry 2");
+        assert_eq!(view.textarea.text(), "entry 1\nentry 2");
+
+        press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "entry 1\nentry ");
+        for _ in 0.."entry \n".len() {
+            press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        }
+        assert_eq!(view.textarea.text(), "entry 1\n");
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Append-only: saved entries cannot be changed")
+        );
+        assert!(render_rows(&view, 60).concat().contains("APPEND-ONLY"));
+    }
+
+    #[test]
+    fn status_row_shows_cursor_line_and_column_on_the_right() {
+        let mut view = view_with("a: 1\nwidth: 80\n");
+        view.textarea.set_cursor(8);
+        let rows = render_rows(&view, 40);
+        assert!(rows[2].trim_end().ends_with("Ln 2, Col 4"));
+
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        assert!(render_rows(&view, 40)[2].contains("Ln 2, Col 5"));
+    }
+
+    #[test]
+    fn busy_spinner_shows_until_it_resolves_to_a_status() {
+        let mut view = view_with("a");
+        view.begin_busy("Syncing…".to_string());
+        let status = view.status_span().content.to_string();
+        assert!(status.ends_with(" Syncing…"), "{status}");
+        assert!(status.starts_with(|ch: char| "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏".contains(ch)));
+
+        view.end_busy(Ok("Synced".to_string()));
+        assert!(view.busy.is_none());
+        assert_eq!(view.status_span().content, "Synced");
+        view.begin_busy("Syncing…".to_string());
+        view.end_busy(Err("Sync failed".to_string()));
+        assert_eq!(view.status_span().content, "Sync failed");
+    }
+
+    #[test]
+    fn ctrl_x_p_shows_the_parsed_buffer_in_an_overlay() {
+        let mut view = view_with("width: 80\n");
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('p'), KeyModifiers::NONE);
+        assert!(view.overlay.is_none());
+        assert!(view.status_span().content.starts_with("No parser"));
+
+        view.set_parse_preview(Some(Box::new(|text: &str| {
+            let (key, value) = text.trim().split_once(": ").ok_or("expected key: value")?;
+            Ok(format!("{key}\n  = {value}"))
+        })));
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('p'), KeyModifiers::NONE);
+        let rendered = render_rows(&view, 40).concat();
+        assert!(rendered.contains("Parsed preferences"));
+        assert!(rendered.contains("= 80"));
+
+        assert_eq!(view.on_esc(), CancellationEvent::Handled);
+        view.textarea.set_text("nonsense");
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('p'), KeyModifiers::NONE);
+        assert!(view.overlay.is_none());
+        assert_eq!(
+            view.status_span().content,
+            "Failed to parse: expected key: value"
+        );
+    }
+
+    #[test]
+    fn ctrl_x_comma_toggles_between_list_and_csv() {
+        let mut view = view_with("tags:\n- a\n- b\n\nend\n");
+        view.textarea.set_cursor(8);
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char(','), KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "tags:\na, b\n\nend\n");
+
+        press(&mut view, KeyCode::Char('.'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "tags:\n- a\n- b\n\nend\n");
+    }
+
+    #[test]
+    fn mixed_indentation_is_counted_and_fixed_with_ctrl_x_i() {
+        let mut view = view_with("a:\n \tb: 1\n\t c: 2\n\td: 3\n");
+        view.status_message = None;
+        assert_eq!(view.mixed_indent.len(), 2);
+        assert!(
+            render_rows(&view, 60)
+                .concat()
+                .contains("2 lines mix tabs and spaces")
+        );
+
+        view.set_indent_style(IndentStyle::Spaces(2));
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('i'), KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "a:\n  b: 1\n   c: 2\n\td: 3\n");
+        assert!(view.mixed_indent.is_empty());
+    }
+
+    #[test]
+    fn ctrl_x_ctrl_t_transposes_lines_and_moves_down() {
+        let mut view = view_with("a = 1\nb = 2\nc = 3");
+        view.textarea.set_cursor("a = 1\nb = ".len());
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "b = 2\na = 1\nc = 3");
+        assert_eq!(view.textarea.cursor(), "b = 2\na = 1\nc = ".len());
+
+        view.textarea.set_cursor(1);
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "b = 2\na = 1\nc = 3");
+    }
+
+    #[test]
+    fn solo_section_comments_out_the_rest_until_unsolo() {
+        let text = "# A\na = 1\n# B\nb = 2\n";
+        let mut view = view_with(text);
+        view.set_comment_token("//".to_string());
+        view.textarea.set_cursor(text.find("b =").unwrap());
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "// # A\n// a = 1\n# B\nb = 2\n");
+
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::Char('u'), KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), text);
+        assert!(view.solo.is_none());
+    }
+
+    #[test]
+    fn moving_into_a_fold_unfolds_it() {
+        let mut view = view_with("# A\na1\n# B\nb1");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::Char('z'), KeyModifiers::NONE);
+        assert_eq!(view.folded, vec!["# A".to_string()]);
+
+        press(&mut view, KeyCode::End, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        assert!(view.folded.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_prompt_inserts_output_only_when_enabled() {
+        let mut view = view_with("opts: ");
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::ALT);
+        assert!(view.prompt.is_none());
+
+        view.set_allow_shell_commands(true);
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::ALT);
+        type_str(&mut view, "echo a b");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "opts: a b");
+
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::ALT);
+        type_str(&mut view, "echo bad >&2; false");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "opts: a b");
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("`echo bad >&2; false` failed: bad")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_output_arrives_on_a_later_tick_with_a_frame_requester() {
+        let mut view = view_with("opts: ");
+        view.set_frame_requester(FrameRequester::test_dummy());
+        view.set_allow_shell_commands(true);
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::ALT);
+        type_str(&mut view, "sleep 0.05; echo late");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "opts: ");
+        assert!(view.status_span().content.contains("Running"));
+
+        while view.shell_run.is_some() {
+            std::thread::sleep(Duration::from_millis(5));
+            view.tick();
+        }
+        assert_eq!(view.textarea.text(), "opts: late");
+    }
+
+    #[test]
+    fn env_var_prompt_inserts_value_or_warns_when_unset() {
+        let mut view = view_with("");
+        press(&mut view, KeyCode::Char('v'), KeyModifiers::ALT);
+        assert!(view.prompt.is_some());
+        type_str(&mut view, "CODEX_PREFERENCES_EDITOR_UNSET_VAR");
+        assert_eq!(view.textarea.text(), "");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+
+        assert!(view.prompt.is_none());
+        assert_eq!(view.textarea.text(), "");
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Environment variable CODEX_PREFERENCES_EDITOR_UNSET_VAR is not set")
+        );
+    }
+
+    #[test]
+    fn esc_cancels_prompt_without_closing() {
+        let mut view = view_with("");
+        press(&mut view, KeyCode::Char('v'), KeyModifiers::ALT);
+        view.on_esc();
+        assert!(view.prompt.is_none());
+        assert!(!view.is_complete());
+    }
+
+    #[test]
+    fn event_sender_reports_edits_saves_and_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        view.set_event_sender(tx);
+
+        type_str(&mut view, "a");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        type_str(&mut view, "b");
+        view.on_esc();
+        press(&mut view, KeyCode::Char('d'), KeyModifiers::NONE);
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        assert!(matches!(
+            events.pop(),
+            Some(PreferencesEditorEvent::SessionEnded { .. })
+        ));
+        assert_eq!(
+            events,
+            vec![
+                PreferencesEditorEvent::Opened { path: path.clone() },
+                PreferencesEditorEvent::Edited,
+                // Adding the final newline on save.
+                PreferencesEditorEvent::Edited,
+                PreferencesEditorEvent::Saved { path },
+                PreferencesEditorEvent::Edited,
+                PreferencesEditorEvent::Discarded,
+            ]
+        );
+    }
+
+    #[test]
+    fn ctrl_up_and_down_jump_between_paragraphs() {
+        let mut view = view_with("a\nb\n\nc\n");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Down, KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.cursor(), 5);
+        press(&mut view, KeyCode::Down, KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.cursor(), 7);
+        press(
+            &mut view,
+            KeyCode::Char('{'),
+            KeyModifiers::ALT | KeyModifiers::SHIFT,
+        );
+        assert_eq!(view.textarea.cursor(), 5);
+        press(&mut view, KeyCode::Up, KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.cursor(), 0);
+    }
+
+    #[test]
+    fn fenced_paste_wraps_the_next_paste_only() {
+        let mut view = view_with("intro");
+        press(&mut view, KeyCode::Char('`'), KeyModifiers::ALT);
+        type_str(&mut view, "sh");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        view.handle_paste("\techo hi\n".to_string());
+        assert_eq!(view.textarea.text(), "intro\n```sh\n    echo hi\n```\n");
+
+        view.handle_paste("plain".to_string());
+        assert_eq!(
+            view.textarea.text(),
+            "intro\n```sh\n    echo hi\n```\nplain"
+        );
+    }
+
+    #[test]
+    fn save_on_blur_writes_dirty_buffer_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        type_str(&mut view, "a");
+        view.on_focus_changed(false);
+        assert!(!path.exists());
+
+        view.set_save_on_blur(true);
+        view.on_focus_changed(true);
+        assert!(!path.exists());
+        view.on_focus_changed(false);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn ascii_command_normalizes_selection_only() {
+        let mut view = view_with("“quoted” — ok\nit’s");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::End, KeyModifiers::SHIFT);
+        press(&mut view, KeyCode::Char('a'), KeyModifiers::ALT);
+
+        assert_eq!(view.textarea.text(), "\"quoted\" -- ok\nit’s");
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Replaced 3 typographic characters with ASCII")
+        );
+    }
+
+    #[test]
+    fn failed_save_leaves_the_original_file_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        fs::write(&path, "original\n").unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "original\n".to_string());
+        type_str(&mut view, "more");
+
+        // A directory where the temp file should go makes the write fail.
+        let tmp = dir
+            .path()
+            .join(format!("preferences.md.tmp-{}", std::process::id()));
+        fs::create_dir(&tmp).unwrap();
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original\n");
+        assert!(view.dirty);
+        assert!(
+            view.status_message
+                .as_ref()
+                .is_some_and(|m| m.text.starts_with("Failed to save preferences"))
+        );
+
+        fs::remove_dir(&tmp).unwrap();
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original\nmore\n");
+        assert!(!tmp.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_keeps_the_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        fs::write(&path, "secret\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "secret\n".to_string());
+        type_str(&mut view, "more");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret\nmore\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_through_a_symlink_replaces_its_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("real.md");
+        let link = dir.path().join("preferences.md");
+        fs::write(&target, "a\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let mut view = PreferencesEditorView::new(link.clone(), "a\n".to_string());
+        type_str(&mut view, "b");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(&target).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn strip_comments_on_save_writes_clean_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_strip_comments_on_save(true);
+        type_str(&mut view, "# about\nkey = \"a # b\" # why\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "key = \"a # b\"\n");
+        assert_eq!(view.textarea.text(), "key = \"a # b\"\n");
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn save_trims_trailing_whitespace_and_adds_a_final_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        type_str(&mut view, "a  \nb\t");
+        view.textarea.set_cursor(1);
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+        assert_eq!(view.last_saved_text, "a\nb\n");
+        assert_eq!(view.textarea.cursor(), 1);
+        assert!(!view.dirty);
+
+        view.set_normalize_whitespace_on_save(false);
+        type_str(&mut view, " ");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a \nb\n");
+    }
+
+    #[test]
+    fn max_lines_trims_the_oldest_lines_on_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_max_lines(Some(2), LineCapDrop::Oldest);
+        type_str(&mut view, "a\nb\nc\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b\nc\n");
+        assert_eq!(
+            view.status_span().content,
+            format!(
+                "Saved to {} (dropped 1 oldest line to stay at 2)",
+                path.display()
+            )
+        );
+    }
+
+    fn uppercase_formatter() -> Formatter {
+        Box::new(|text: &str| Ok(text.to_uppercase()))
+    }
+
+    #[test]
+    fn large_format_changes_wait_for_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_format_on_save(Some(uppercase_formatter()));
+        view.set_format_confirm_threshold(Some(1));
+        type_str(&mut view, "a");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        type_str(&mut view, "b");
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(view.pending_format.is_some());
+        assert!(!path.exists());
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "A\nB\n");
+        assert_eq!(view.textarea.text(), "A\nB\n");
+
+        type_str(&mut view, "\nc\nd");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        view.on_esc();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "A\nB\nc\nd\n");
+        assert!(!view.is_complete());
+    }
+
+    #[test]
+    fn small_format_changes_apply_without_asking() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_format_on_save(Some(uppercase_formatter()));
+        view.set_format_confirm_threshold(Some(1));
+        type_str(&mut view, "a");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "A\n");
+    }
+
+    #[test]
+    fn formatter_errors_are_noted_only_when_the_save_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let failing = || -> Formatter { Box::new(|_: &str| Err("bad syntax".to_string())) };
+
+        // A file where the directory should be: the save cannot happen.
+        std::fs::write(dir.path().join("blocked"), "").unwrap();
+        let path = dir.path().join("blocked").join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_format_on_save(Some(failing()));
+        type_str(&mut view, "a");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(!path.exists());
+        assert!(view.dirty);
+        let status = view.status_span().content.to_string();
+        assert!(status.starts_with("Failed to save preferences"), "{status}");
+
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_format_on_save(Some(failing()));
+        type_str(&mut view, "a");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+        assert_eq!(
+            view.status_span().content,
+            "Saved without formatting: bad syntax"
+        );
+    }
+
+    #[test]
+    fn saving_past_a_formatter_failure_still_validates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_format_on_save(Some(Box::new(|_: &str| Err("bad syntax".to_string()))));
+        view.set_validator(Some(Arc::new(|text: &str| {
+            text.lines()
+                .enumerate()
+                .filter(|(_, line)| line.contains("bad"))
+                .map(|(i, _)| ValidationError {
+                    line: i + 1,
+                    message: "not allowed".to_string(),
+                })
+                .collect()
+        })));
+        type_str(&mut view, "bad");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(!path.exists());
+        assert!(
+            view.status_span()
+                .content
+                .starts_with("Not saved: 1 validation problem")
+        );
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "bad\n");
+        assert_eq!(
+            view.status_span().content,
+            "Saved without formatting: bad syntax"
+        );
+    }
+
+    #[test]
+    fn validation_errors_mark_gutter_and_show_at_cursor() {
+        let mut view = view_with("ok\nbad\n");
+        view.set_gutter_glyphs(GutterGlyphs::Unicode);
+        view.set_validator(Some(Arc::new(|text: &str| {
+            text.lines()
+                .enumerate()
+                .filter(|(_, line)| line.contains("bad"))
+                .map(|(i, _)| ValidationError {
+                    line: i + 1,
+                    message: "not allowed".to_string(),
+                })
+                .collect()
+        })));
+        assert_eq!(view.validation_errors.len(), 1);
+
+        let area = Rect::new(0, 0, 30, view.desired_height(30));
+        let mut buf = Buffer::empty(area);
+        view.render(area, &mut buf);
+        let rect = view.textarea_rect(area).unwrap();
+        assert_eq!(buf[(0, rect.y)].symbol(), "▌");
+        assert_eq!(buf[(0, rect.y + 1)].symbol(), "✗");
+
+        view.textarea.set_cursor(4);
+        assert_eq!(view.status_span().content, "Line 2: not allowed");
+    }
+
+    #[test]
+    fn ctrl_x_e_jumps_to_the_next_validation_error() {
+        let mut view = view_with("ok\nbad\nok\nbad too\n");
+        view.set_validator(Some(Arc::new(|text: &str| {
+            text.lines()
+                .enumerate()
+                .filter(|(_, line)| line.contains("bad"))
+                .map(|(i, _)| ValidationError {
+                    line: i + 1,
+                    message: format!("line {} is bad", i + 1),
+                })
+                .collect()
+        })));
+        view.textarea.set_cursor(8);
+
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('e'), KeyModifiers::NONE);
+        assert_eq!(view.cursor_line(), 4);
+
+        // Past the last error it wraps to the first.
+        view.textarea.set_cursor(view.textarea.text().len());
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('e'), KeyModifiers::NONE);
+        assert_eq!(view.cursor_line(), 2);
+        assert_eq!(view.textarea.cursor(), 3);
+    }
+
+    #[test]
+    fn dirty_debounce_ignores_reverted_edits_until_it_settles() {
+        let mut view = view_with("a");
+        view.set_dirty_debounce(Some(Duration::from_secs(60)));
+        type_str(&mut view, "b");
+        press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        assert!(!view.dirty);
+        assert!(view.dirty_pending_since.is_none());
+
+        type_str(&mut view, "b");
+        assert!(!view.dirty);
+        view.dirty_pending_since = Instant::now().checked_sub(Duration::from_secs(61));
+        view.tick();
+        assert!(view.dirty);
+
+        // Closing never waits for the debounce.
+        let mut view = view_with("a");
+        view.set_dirty_debounce(Some(Duration::from_secs(60)));
+        type_str(&mut view, "b");
+        view.on_esc();
+        assert!(view.confirm_close.is_some());
+        assert!(!view.is_complete());
+    }
+
+    #[test]
+    fn auto_save_writes_after_a_pause_unless_the_file_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        std::fs::write(&path, "a\n").unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "a\n".to_string());
+        view.set_auto_save(Some(Duration::from_secs(2)));
+        assert!(render_rows(&view, 60).concat().contains("auto-save 2s"));
+
+        type_str(&mut view, "b");
+        view.tick();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+        view.last_edit_at = Instant::now().checked_sub(Duration::from_secs(3));
+        view.tick();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            view.textarea.text()
+        );
+        assert!(!view.dirty);
+
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(&path, "other\n").unwrap();
+        type_str(&mut view, "c");
+        view.last_edit_at = Instant::now().checked_sub(Duration::from_secs(3));
+        view.tick();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "other\n");
+        assert!(view.status_span().content.contains("not auto-saving"));
+        assert!(view.dirty);
+    }
+
+    #[test]
+    fn auto_save_normalizes_only_what_it_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_auto_save(Some(Duration::from_secs(2)));
+
+        type_str(&mut view, "hello ");
+        let undo_depth = view.undo_stack.len();
+        view.last_edit_at = Instant::now().checked_sub(Duration::from_secs(3));
+        view.tick();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+        assert_eq!(view.textarea.text(), "hello ");
+        assert_eq!(view.textarea.cursor(), "hello ".len());
+        assert_eq!(view.undo_stack.len(), undo_depth);
+        assert!(!view.dirty);
+
+        type_str(&mut view, "world");
+        assert_eq!(view.textarea.text(), "hello world");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world\n");
+    }
+
+    #[test]
+    fn saving_keeps_the_previous_contents_in_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let backup = dir.path().join("preferences.md.bak");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        type_str(&mut view, "first\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(path.exists());
+        assert!(!backup.exists());
+
+        std::fs::write(&path, "on disk\r\n").unwrap();
+        let mut view = PreferencesEditorView::new(path.clone(), "on disk\r\n".to_string());
+        type_str(&mut view, "new ");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "on disk\r\n");
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("on disk\r\nnew")
+        );
+
+        press(&mut view, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "on disk\n");
+        assert!(view.dirty);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_editor_changes_come_back_and_failures_keep_the_buffer() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut view = view_with("a: 1\n");
+        view.start_external_edit("true");
+        assert!(view.status_span().content.contains("not available"));
+        assert!(view.external_edit.is_none());
+
+        view.set_app_event_sender(AppEventSender::new(tx));
+        // The app runs the command and reports how it exited.
+        let mut edit = |view: &mut PreferencesEditorView, editor: &str| {
+            view.start_external_edit(editor);
+            if let Ok(AppEvent::RunExternalCommand(mut command)) = rx.try_recv() {
+                view.on_external_command_finished(command.status());
+            }
+        };
+
+        edit(&mut view, "sh -c 'printf \"a: 2\\n\" > \"$1\"' sh");
+        assert_eq!(view.textarea.text(), "a: 2\n");
+        assert!(view.dirty);
+
+        edit(&mut view, "false");
+        assert_eq!(view.textarea.text(), "a: 2\n");
+        assert!(view.status_span().content.contains("false: exited with"));
+
+        edit(&mut view, "");
+        assert!(view.status_span().content.contains("$EDITOR is not set"));
+
+        edit(&mut view, "sh -c 'printf \"a: 1\\n\" > \"$1\"' sh");
+        assert_eq!(view.textarea.text(), "a: 1\n");
+        assert!(!view.dirty);
+        assert!(view.external_edit.is_none());
+    }
+
+    #[test]
+    fn brackets_and_quotes_type_in_pairs_unless_disabled() {
+        let mut view = view_with("");
+        type_str(&mut view, "(a");
+        assert_eq!(view.textarea.text(), "(a)");
+        type_str(&mut view, ") `b` ");
+        assert_eq!(view.textarea.text(), "(a) `b` ");
+        assert_eq!(view.textarea.cursor(), view.textarea.text().len());
+
+        // Nothing pairs up in front of a word.
+        view.textarea.set_cursor(0);
+        type_str(&mut view, "[");
+        assert_eq!(view.textarea.text(), "[(a) `b` ");
+
+        let mut view = view_with("");
+        view.set_auto_pair(false);
+        type_str(&mut view, "{\"");
+        assert_eq!(view.textarea.text(), "{\"");
+    }
+
+    #[test]
+    fn ctrl_t_inserts_the_local_date_and_shift_the_time() {
+        use chrono::TimeZone;
+
+        let now = Local
+            .with_ymd_and_hms(2024, 3, 9, 7, 5, 0)
+            .single()
+            .expect("timestamp");
+        let mut view = view_with("## Changelog\n- ");
+        view.insert_time(now, DATE_FORMAT);
+        assert_eq!(view.textarea.text(), "## Changelog\n- 2024-03-09");
+        type_str(&mut view, " / ");
+        view.insert_time(now, TIMESTAMP_FORMAT);
+        assert_eq!(
+            view.textarea.text(),
+            "## Changelog\n- 2024-03-09 / 2024-03-09 07:05"
+        );
+
+        let mut view = view_with("");
+        press(&mut view, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        let date = view.textarea.text().to_string();
+        assert!(
+            chrono::NaiveDate::parse_from_str(&date, DATE_FORMAT).is_ok(),
+            "{date}"
+        );
+        press(&mut view, KeyCode::Char('T'), KeyModifiers::CONTROL);
+        assert!(
+            chrono::NaiveDateTime::parse_from_str(
+                &view.textarea.text()[date.len()..],
+                TIMESTAMP_FORMAT
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn changed_lines_marks_added_and_edited_lines() {
+        assert_eq!(changed_lines("a\nb\nc\n", "a\nB\nc\nd\n"), vec![1, 3]);
+        assert!(changed_lines("a\nb\n", "a\n").is_empty());
+    }
+
+    #[test]
+    fn split_panes_keep_own_cursors_and_share_edits() {
+        let text: String = (1..=30).map(|i| format!("k{i}\n")).collect();
+        let mut view = view_with(&text);
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('t'), KeyModifiers::ALT);
+        press(&mut view, KeyCode::F(6), KeyModifiers::NONE);
+        view.textarea.set_cursor(text.find("k30").unwrap());
+        let rows = render_rows(&view, 30);
+        assert!(rows.iter().any(|row| row.contains(" 1 k1")), "{rows:?}");
+        assert!(rows.iter().any(|row| row.contains("30 k30")), "{rows:?}");
+
+        // Typing in the bottom pane shows up in the top one, whose cursor
+        // stays where it was.
+        type_str(&mut view, "#");
+        press(&mut view, KeyCode::F(6), KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), 0);
+        type_str(&mut view, "#");
+        assert!(view.textarea.text().starts_with("#k1\n"));
+        assert!(view.textarea.text().ends_with("#k30\n"));
+        let rows = render_rows(&view, 30);
+        assert!(rows.iter().any(|row| row.contains("#k30")), "{rows:?}");
+        assert_eq!(
+            view.split.as_ref().map(|s| s.cursor),
+            Some(text.find("k30").unwrap() + 2)
+        );
+
+        press(&mut view, KeyCode::Char('t'), KeyModifiers::ALT);
+        assert!(view.split.is_none());
+    }
+
+    #[test]
+    fn line_numbers_widen_the_gutter_and_toggle_with_alt_g() {
+        let text: String = (1..=12).map(|i| format!("k{i}\n")).collect();
+        let mut view = view_with(text.trim_end());
+        let rows = render_rows(&view, 30);
+        let first = rows.iter().position(|row| row.contains("k1")).unwrap();
+        assert!(rows[first].contains(" 1 k1"), "{rows:?}");
+        assert!(rows[first + 11].contains("12 k12"), "{rows:?}");
+        let area = Rect::new(0, 0, 30, view.desired_height(30));
+        assert_eq!(view.textarea_rect(area).unwrap().x, 5);
+
+        press(&mut view, KeyCode::Char('g'), KeyModifiers::ALT);
+        assert_eq!(view.textarea_rect(area).unwrap().x, 2);
+        assert!(!render_rows(&view, 30).concat().contains("12 k12"));
+    }
+
+    #[test]
+    fn ctrl_shift_w_toggles_soft_wrap_and_the_height_follows() {
+        let mut view = view_with(&format!("note: {}\n", "word ".repeat(30)));
+        view.textarea.set_cursor(100);
+        let wrapped = view.desired_height(40);
+        press(
+            &mut view,
+            KeyCode::Char('W'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+        assert!(!view.textarea.wraps());
+        assert!(view.desired_height(40) < wrapped);
+        let area = Rect::new(0, 0, 40, view.desired_height(40));
+        let rect = view.textarea_rect(area).unwrap();
+        let (x, _) = view.cursor_pos(area).unwrap();
+        assert!(x < rect.right());
+
+        press(
+            &mut view,
+            KeyCode::Char('w'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+        assert!(view.textarea.wraps());
+        assert_eq!(view.desired_height(40), wrapped);
+    }
+
+    #[test]
+    fn clicks_place_the_cursor_and_the_wheel_scrolls() {
+        let text: String = (0..40).map(|i| format!("line {i}\n")).collect();
+        let mut view = view_with(&text);
+        assert!(!view.wants_mouse());
+        view.set_mouse_support(true);
+        assert!(view.wants_mouse());
+        view.textarea.set_cursor(0);
+        let area = Rect::new(0, 10, 40, view.desired_height(40));
+        let rect = view.textarea_rect(area).unwrap();
+        let mouse = |kind, column, row| MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        let click = MouseEventKind::Down(MouseButton::Left);
+
+        assert!(view.handle_mouse_event(mouse(click, rect.x + 3, rect.y + 1), area));
+        assert_eq!(view.textarea.cursor(), text.find("line 1").unwrap() + 3);
+        assert!(!view.handle_mouse_event(mouse(click, rect.x, area.y), area));
+        assert_eq!(view.textarea.cursor(), text.find("line 1").unwrap() + 3);
+        assert!(!view.dirty);
+
+        view.handle_mouse_event(mouse(MouseEventKind::ScrollDown, rect.x, rect.y), area);
+        assert_eq!(view.textarea_state.borrow().scroll(), 3);
+        assert_eq!(view.textarea.cursor(), text.find("line 3").unwrap());
+        view.render(area, &mut Buffer::empty(area));
+        assert_eq!(view.textarea_state.borrow().scroll(), 3);
+    }
+
+    #[test]
+    fn remapped_save_key_saves_and_ctrl_s_no_longer_does() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_keymap(EditorKeymap {
+            save: KeyBinding::ctrl('o'),
+            ..EditorKeymap::default()
+        });
+        type_str(&mut view, "a: 1");
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(!path.exists());
+        assert!(render_rows(&view, 60).concat().contains("Ctrl+O save"));
+
+        press(&mut view, KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a: 1");
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn ctrl_g_jumps_to_a_line_and_keeps_the_prompt_open_on_bad_input() {
+        let mut view = view_with("a: 1\nb: 2\nc: 3\n");
+        press(&mut view, KeyCode::Char('g'), KeyModifiers::CONTROL);
+        type_str(&mut view, "two");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(view.prompt.is_some());
+        assert!(render_rows(&view, 60)[2].contains("Not a line number: two"));
+
+        for _ in 0..3 {
+            press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        }
+        type_str(&mut view, "2");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(view.prompt.is_none());
+        assert_eq!(view.textarea.cursor(), 5);
+
+        press(&mut view, KeyCode::Char('g'), KeyModifiers::CONTROL);
+        type_str(&mut view, "40");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.cursor(), 15);
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn hint_line_counts_characters_and_words() {
+        let mut view = view_with("name: José");
+        assert!(
+            render_rows(&view, 60)
+                .concat()
+                .contains("10 chars · 2 words")
+        );
+
+        type_str(&mut view, " 👍");
+        assert!(
+            render_rows(&view, 60)
+                .concat()
+                .contains("12 chars · 3 words")
+        );
+    }
+
+    #[test]
+    fn alt_slash_hides_hint_line_and_reclaims_its_rows() {
+        let mut view = view_with("hello");
+        let shown = view.desired_height(40);
+        press(&mut view, KeyCode::Char('/'), KeyModifiers::ALT);
+        assert_eq!(view.desired_height(40), shown - 2);
+
+        assert!(!render_rows(&view, 40).concat().contains("Esc close"));
+
+        press(&mut view, KeyCode::Char('/'), KeyModifiers::ALT);
+        assert_eq!(view.desired_height(40), shown);
+    }
+
+    #[test]
+    fn alt_m_inserts_front_matter_once() {
+        let mut view = view_with("Prefer tabs.\n");
+        press(&mut view, KeyCode::Char('m'), KeyModifiers::ALT);
+        assert_eq!(
+            view.textarea.text(),
+            format!("{DEFAULT_FRONT_MATTER}Prefer tabs.\n")
+        );
+        assert_eq!(view.textarea.cursor(), "---\ntitle: ".len());
+
+        press(&mut view, KeyCode::Char('m'), KeyModifiers::ALT);
+        assert_eq!(
+            view.textarea.text(),
+            format!("{DEFAULT_FRONT_MATTER}Prefer tabs.\n")
+        );
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("This file already has front matter")
+        );
+    }
+
+    #[test]
+    fn discarded_buffer_can_be_recovered_in_a_new_editor() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = dir.path().join("trash.md");
+
+        let mut view = view_with("saved\n");
+        view.set_trash_path(trash.clone());
+        type_str(&mut view, "draft");
+        view.on_esc();
+        press(&mut view, KeyCode::Char('d'), KeyModifiers::NONE);
+        assert!(view.is_complete());
+
+        let mut reopened = view_with("saved\n");
+        reopened.set_trash_path(trash);
+        press(&mut reopened, KeyCode::Char('r'), KeyModifiers::ALT);
+        assert_eq!(reopened.textarea.text(), "saved\ndraft");
+        assert!(reopened.dirty);
+
+        press(&mut reopened, KeyCode::Char('r'), KeyModifiers::ALT);
+        assert_eq!(reopened.textarea.text(), "saved\n");
+    }
+
+    #[test]
+    fn reloading_over_unsaved_changes_keeps_them_in_the_trash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let trash = dir.path().join("trash.md");
+        fs::write(&path, "saved\n").unwrap();
+        let mut view = PreferencesEditorView::new(path, "saved\n".to_string());
+        view.set_trash_path(trash.clone());
+        type_str(&mut view, "draft");
+        press(&mut view, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(view.textarea.text(), "saved\n");
+        assert_eq!(fs::read_to_string(&trash).unwrap(), "saved\ndraft");
+    }
+
+    #[test]
+    fn alt_plus_and_minus_step_the_number_after_the_cursor() {
+        let mut view = view_with("retries: 9\n");
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('+'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "retries: 10\n");
+        assert_eq!(view.textarea.cursor(), "retries: 1".len());
+
+        for _ in 0..11 {
+            press(&mut view, KeyCode::Char('-'), KeyModifiers::ALT);
+        }
+        assert_eq!(view.textarea.text(), "retries: -1\n");
+    }
+
+    fn reject_lines_containing_bad() -> Validator {
+        Arc::new(|text: &str| {
+            text.lines()
+                .enumerate()
+                .filter(|(_, line)| line.contains("bad"))
+                .map(|(i, _)| ValidationError {
+                    line: i + 1,
+                    message: "not allowed".to_string(),
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn validation_problems_hold_back_save_until_confirmed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_validator(Some(reject_lines_containing_bad()));
+        type_str(&mut view, "bad");
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(!path.exists());
+        assert!(view.confirm_invalid_save);
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bad\n");
+    }
+
+    #[test]
+    fn background_validation_saves_when_finished_and_can_be_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        view.set_frame_requester(FrameRequester::test_dummy());
+        view.set_validator(Some(reject_lines_containing_bad()));
+        view.set_validate_in_background(true);
+        type_str(&mut view, "good");
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(view.status_span().content.contains("Validating"));
+        assert_eq!(view.on_ctrl_c(), CancellationEvent::Handled);
+        assert!(!view.is_complete());
+        view.tick();
+        assert!(!path.exists());
+
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        while view.validation_run.is_some() {
+            std::thread::sleep(Duration::from_millis(5));
+            view.tick();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "good\n");
+    }
+
+    #[test]
+    fn theme_colors_the_gutter_status_and_current_line() {
+        use ratatui::style::Color;
+
+        let mut view = view_with("a = 1\nb = 2");
+        view.set_theme(EditorTheme {
+            gutter: Color::Blue,
+            info: Color::LightBlue,
+            accent: Color::Black,
+            current_line: Some(Color::Gray),
+            ..EditorTheme::default()
+        });
+        type_str(&mut view, "!");
+        let area = Rect::new(0, 0, 30, view.desired_height(30));
+        let mut buf = Buffer::empty(area);
+        view.render(area, &mut buf);
+        let rect = view.textarea_rect(area).unwrap();
+
+        assert_eq!(buf[(0, rect.y)].fg, Color::Blue);
+        assert_eq!(buf[(2, 2)].fg, Color::Black);
+        assert_eq!(buf[(rect.x, rect.y + 1)].bg, Color::Gray);
+        assert_ne!(buf[(rect.x, rect.y)].bg, Color::Gray);
+
+        view.status_message = Some(StatusMessage::info("Noted".to_string()));
+        assert_eq!(view.status_span().style.fg, Some(Color::LightBlue));
+    }
+
+    #[test]
+    fn ascii_gutter_keeps_the_layout_width() {
+        assert!(locale_is_utf8("en_US.UTF-8"));
+        assert!(locale_is_utf8("C.utf8"));
+        assert!(!locale_is_utf8("C"));
+
+        let mut view = view_with("hello");
+        view.set_gutter_glyphs(GutterGlyphs::Ascii);
+        let area = Rect::new(0, 0, 30, view.desired_height(30));
+        let mut buf = Buffer::empty(area);
+        view.render(area, &mut buf);
+        let rect = view.textarea_rect(area).unwrap();
+        assert_eq!(buf[(0, rect.y)].symbol(), "|");
+        assert_eq!(buf[(rect.x, rect.y)].symbol(), "h");
+    }
+
+    #[test]
+    fn scroll_indicator_shows_only_when_the_buffer_overflows() {
+        assert_eq!(scroll_thumb(10, 10, 0), None);
+        assert_eq!(scroll_thumb(40, 10, 0), Some(0..2));
+        assert_eq!(scroll_thumb(40, 10, 15), Some(4..6));
+        assert_eq!(scroll_thumb(40, 10, 30), Some(8..10));
+
+        let thumb_rows = |view: &PreferencesEditorView| {
+            let area = Rect::new(0, 0, 30, view.desired_height(30));
+            let mut buf = Buffer::empty(area);
+            view.render(area, &mut buf);
+            let rect = view.textarea_rect(area).unwrap();
+            (0..rect.height)
+                .filter(|row| buf[(rect.x - 1, rect.y + row)].symbol() == "#")
+                .collect::<Vec<_>>()
+        };
+        let mut view = view_with("short\n");
+        view.set_gutter_glyphs(GutterGlyphs::Ascii);
+        assert!(thumb_rows(&view).is_empty());
+
+        let mut view = view_with(&"line\n".repeat(100));
+        view.set_gutter_glyphs(GutterGlyphs::Ascii);
+        view.textarea.set_cursor(view.textarea.text().len());
+        let rows = thumb_rows(&view);
+        assert_eq!(rows.first(), Some(&15));
+        assert_eq!(rows.last(), Some(&17));
+    }
+
+    #[test]
+    fn alt_o_sorts_front_matter_keys_only() {
+        let mut view = view_with("---\nb: 1\na: 2\n---\nz: body\ny: body\n");
+        press(&mut view, KeyCode::Char('o'), KeyModifiers::ALT);
+        assert_eq!(
+            view.textarea.text(),
+            "---\na: 2\nb: 1\n---\nz: body\ny: body\n"
+        );
+
+        let mut view = view_with("---\n- a\n---\n");
+        press(&mut view, KeyCode::Char('o'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "---\n- a\n---\n");
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Front matter left unsorted: line 2 is not a key")
+        );
+    }
+
+    #[test]
+    fn alt_s_surrounds_selection_or_word() {
+        let mut view = view_with("make it bold");
+        view.textarea.set_cursor(9);
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::ALT);
+        type_str(&mut view, "**");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "make it **bold**");
+
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::End, KeyModifiers::SHIFT);
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::ALT);
+        type_str(&mut view, "(");
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(view.textarea.text(), "(make it **bold**)");
+    }
+
+    #[test]
+    fn alt_c_toggles_comment_with_token_from_extension() {
+        assert_eq!(default_comment_token(Path::new("config.toml")), "#");
+        assert_eq!(default_comment_token(Path::new("main.RS")), "//");
+        assert_eq!(default_comment_token(Path::new("setup.ini")), ";");
+        assert_eq!(default_comment_token(Path::new("notes")), "#");
+
+        let mut view = PreferencesEditorView::new(PathBuf::from("init.js"), "a()\n".to_string());
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('c'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "// a()\n");
+
+        view.set_comment_token("#".to_string());
+        press(&mut view, KeyCode::Char('c'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "# // a()\n");
+    }
+
+    #[test]
+    fn alt_digits_resolve_the_conflict_at_the_cursor() {
+        let conflicted = "a\n<<<<<<< HEAD\nmine\n=======\ntheirs\n>>>>>>> b\nz\n";
+        let mut view = view_with(conflicted);
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('3'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), conflicted);
+
+        view.status_message = None;
+        view.textarea.set_cursor(conflicted.find("mine").unwrap());
+        assert_eq!(
+            view.status_span().content,
+            "Conflict 1 of 1 — Alt+1 ours · Alt+2 theirs · Alt+3 both"
+        );
+        press(&mut view, KeyCode::Char('3'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "a\nmine\ntheirs\nz\n");
+
+        let mut view = view_with(conflicted);
+        view.textarea.set_cursor(conflicted.find("theirs").unwrap());
+        press(&mut view, KeyCode::Char('2'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), "a\ntheirs\nz\n");
+    }
+
+    #[test]
+    fn alt_d_shows_leading_comments_until_esc() {
+        let mut view = view_with("# Set width in columns.\nwidth: 80\n");
+        press(&mut view, KeyCode::Char('d'), KeyModifiers::ALT);
+        assert!(view.overlay.is_some());
+
+        let rendered = render_rows(&view, 40).concat();
+        assert!(rendered.contains("Set width in columns."));
+        assert!(!rendered.contains("width: 80"));
+        assert_eq!(view.cursor_pos(Rect::new(0, 0, 40, 20)), None);
+
+        type_str(&mut view, "x");
+        assert_eq!(view.textarea.text(), "# Set width in columns.\nwidth: 80\n");
+        view.on_esc();
+        assert!(view.overlay.is_none());
+        assert!(!view.is_complete());
+    }
+
+    #[test]
+    fn open_uses_default_contents_only_for_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("new.md");
+        let template = Some("# Options\n".to_string());
+
+        let view = PreferencesEditorView::open(missing.clone(), template.clone(), false).unwrap();
+        assert_eq!(view.textarea.text(), "# Options\n");
+        assert!(view.dirty);
+        let view = PreferencesEditorView::open(missing, template.clone(), true).unwrap();
+        assert!(!view.dirty);
+
+        let existing = dir.path().join("existing.md");
+        fs::write(&existing, "mine\n").unwrap();
+        let view = PreferencesEditorView::open(existing, template, false).unwrap();
+        assert_eq!(view.textarea.text(), "mine\n");
+        assert!(!view.dirty);
+    }
+
+    #[test]
+    fn alt_w_wraps_the_line_and_alt_j_joins_it_back() {
+        let line = "servers: one.example.com, two.example.com, three.example.com";
+        let mut view = view_with(&format!("{line}\nnext\n"));
+        view.set_hard_wrap_width(30);
+        view.textarea.set_cursor(0);
+        press(&mut view, KeyCode::Char('w'), KeyModifiers::ALT);
+        assert_eq!(
+            view.textarea.text(),
+            "servers: one.example.com,\n  two.example.com,\n  three.example.com\nnext\n"
+        );
+
+        press(&mut view, KeyCode::Char('j'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), format!("{line}\nnext\n"));
+    }
+
+    #[test]
+    fn closing_reports_session_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut view = PreferencesEditorView::new(
+            dir.path().join("preferences.md"),
+            "keep\ndrop\n".to_string(),
+        );
+        view.set_event_sender(tx);
+        press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        for _ in 0..4 {
+            press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        }
+        type_str(&mut view, "new\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        view.on_esc();
+
+        let mut stats = None;
+        while let Ok(event) = rx.try_recv() {
+            if let PreferencesEditorEvent::SessionEnded { stats: s } = event {
+                stats = Some(s);
+            }
+        }
+        let stats = stats.unwrap();
+        assert_eq!((stats.lines_added, stats.lines_removed), (1, 1));
+        assert_eq!(stats.chars_changed, 9);
+        assert_eq!(stats.saves, 1);
+    }
+
+    #[test]
+    fn alt_l_edits_every_occurrence_until_esc() {
+        let mut view = view_with("theme: dark\n# theme applies\nthemes: 2\n");
+        view.textarea.set_cursor(2);
+        press(&mut view, KeyCode::Char('l'), KeyModifiers::ALT);
+        assert!(
+            view.status_span()
+                .content
+                .starts_with("Editing 2 occurrences")
+        );
+
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        type_str(&mut view, "_name");
+        assert_eq!(
+            view.textarea.text(),
+            "theme_name: dark\n# theme_name applies\nthemes: 2\n"
+        );
+        assert_eq!(view.textarea.cursor(), "theme_name".len());
+
+        view.on_esc();
+        assert!(!view.is_complete());
+        type_str(&mut view, "!");
+        assert_eq!(
+            view.textarea.text(),
+            "theme_name!: dark\n# theme_name applies\nthemes: 2\n"
+        );
+    }
+
+    #[test]
+    fn discard_at_close_confirmation_closes_only_the_editor() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut view = view_with("a");
+        view.set_event_sender(tx);
+        type_str(&mut view, "b");
+        assert_eq!(view.on_ctrl_c(), CancellationEvent::Handled);
+        assert!(!view.is_complete());
+        press(&mut view, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(view.is_complete());
+        let events: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert!(events.contains(&PreferencesEditorEvent::Discarded));
+        assert!(!events.contains(&PreferencesEditorEvent::QuitRequested));
+    }
+
+    #[test]
+    fn second_ctrl_c_at_discard_prompt_escalates_to_quit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut view = view_with("a");
+        view.set_trash_path(dir.path().join("trash.md"));
+        view.set_event_sender(tx);
+        type_str(&mut view, "b");
+        assert_eq!(view.on_ctrl_c(), CancellationEvent::Handled);
+        assert_eq!(view.on_ctrl_c(), CancellationEvent::Escalate);
+        assert!(!view.is_complete());
+        assert_eq!(view.textarea.text(), "ab");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("trash.md")).unwrap(),
+            "ab"
+        );
+        let events: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert!(events.contains(&PreferencesEditorEvent::QuitRequested));
+        assert!(!events.contains(&PreferencesEditorEvent::Discarded));
+    }
+
+    #[test]
+    fn alt_p_pretty_prints_the_json_fence_at_the_cursor() {
+        let text = "Example:\n```json\n{\"a\": [1,2],  \"b\":{}}\n```\nAfter {\"x\":1}\n";
+        let mut view = view_with(text);
+        view.textarea.set_cursor(text.find("\"a\"").unwrap());
+        press(&mut view, KeyCode::Char('p'), KeyModifiers::ALT);
+        assert_eq!(
+            view.textarea.text(),
+            "Example:\n```json\n{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {}\n}\n```\nAfter {\"x\":1}\n"
+        );
+
+        let text = "```json\n{\n  \"a\": 1,\n}\n```\n";
+        let mut view = view_with(text);
+        view.textarea.set_cursor(10);
+        press(&mut view, KeyCode::Char('p'), KeyModifiers::ALT);
+        assert_eq!(view.textarea.text(), text);
+        let message = view
+            .status_message
+            .as_ref()
+            .map(|m| m.text.clone())
+            .unwrap();
+        assert!(message.starts_with("Invalid JSON on line 4"), "{message}");
+    }
+
+    #[test]
+    fn relative_save_status_reports_path_and_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path, String::new());
+        view.set_relative_save_status(true);
+        type_str(&mut view, "x");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        view.status_message = None;
+        view.last_saved_at = Instant::now().checked_sub(Duration::from_secs(180));
+        let expected = format!("Saved to {} 3m ago", view.display_path);
+        assert_eq!(view.status_span().content, expected);
+    }
+
+    #[test]
+    fn alt_i_shows_docs_url_when_headless() {
+        let mut view = view_with("");
+        view.headless = true;
+        press(&mut view, KeyCode::Char('i'), KeyModifiers::ALT);
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("No documentation link for this file")
+        );
+
+        view.set_docs_url(Some("https://example.com/schema".to_string()));
+        press(&mut view, KeyCode::Char('i'), KeyModifiers::ALT);
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Docs: https://example.com/schema")
+        );
+    }
+
+    #[test]
+    fn loading_view_ignores_edits_until_contents_arrive() {
+        let mut view = PreferencesEditorView::new_loading(
+            PathBuf::from("preferences.md"),
+            FrameRequester::test_dummy(),
+        );
+        type_str(&mut view, "x");
+        assert!(!view.handle_paste("pasted".to_string()));
+        assert_eq!(view.textarea.text(), "");
+
+        view.finish_loading(Ok("loaded\n".to_string()));
+        assert!(!view.dirty);
+        type_str(&mut view, "x");
+        assert_eq!(view.textarea.text(), "loaded\nx");
+    }
+
+    #[test]
+    fn failed_load_keeps_editor_disabled() {
+        let mut view = PreferencesEditorView::new_loading(
+            PathBuf::from("preferences.md"),
+            FrameRequester::test_dummy(),
+        );
+        view.finish_loading(Err("timed out".to_string()));
+        type_str(&mut view, "x");
+        assert_eq!(view.textarea.text(), "");
+        assert_eq!(
+            view.status_message.as_ref().map(|m| m.text.as_str()),
+            Some("Failed to load preferences: timed out")
+        );
+        view.on_ctrl_c();
+        assert!(view.is_complete());
+    }
+
+    #[test]
+    fn save_hook_receives_path_and_contents() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let saves = Rc::new(RefCell::new(Vec::new()));
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        let seen = Rc::clone(&saves);
+        view.set_on_save(Some(Box::new(move |path, contents| {
+            seen.borrow_mut()
+                .push((path.to_path_buf(), contents.to_string()));
+        })));
+
+        type_str(&mut view, "theme = dark");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(
+            *saves.borrow(),
+            vec![(path.clone(), "theme = dark\n".to_string())]
+        );
+
+        // A save that stops to ask first reports nothing.
+        press(&mut view, KeyCode::Char('a'), KeyModifiers::CONTROL);
+        press(&mut view, KeyCode::Backspace, KeyModifiers::NONE);
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(saves.borrow().len(), 1);
+    }
+
+    #[test]
+    fn save_hook_is_skipped_for_windowed_files() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let head: String = (0..100).map(|i| format!("entry {i}\n")).collect();
+        std::fs::write(&path, head).unwrap();
+        let mut view = PreferencesEditorView::open_windowed(path.clone(), 64).unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let seen = Rc::clone(&calls);
+        view.set_on_save(Some(Box::new(move |_, _| seen.set(seen.get() + 1))));
+
+        type_str(&mut view, "entry 100\n");
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(!view.dirty);
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .ends_with("entry 99\nentry 100\n")
+        );
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn new_at_places_the_cursor_and_scrolls_to_it() {
+        let contents: String = (1..=100).map(|i| format!("key{i} = {i}\n")).collect();
+        let view = PreferencesEditorView::new_at(PathBuf::from("p.toml"), contents, 60, 4);
+        assert_eq!(view.cursor_line(), 60);
+        assert_eq!(&view.textarea.text()[view.textarea.cursor()..][..4], "60 =");
+        let rows = render_rows(&view, 40);
+        assert!(rows.iter().any(|row| row.contains("key60 = 60")));
+        assert!(!rows.iter().any(|row| row.contains("key1 = 1")));
+
+        let view = PreferencesEditorView::new_at(PathBuf::from("p.toml"), "a\nb".to_string(), 9, 9);
+        assert_eq!(view.textarea.cursor(), 3);
+    }
+
+    #[test]
+    fn close_confirmation_includes_change_summary() {
+        let mut view = view_with("theme = dark\n");
+        type_str(&mut view, "x");
+        view.request_close();
+
+        assert!(!view.is_complete());
+        let rows = render_rows(&view, 80);
+        assert!(
+            rows[4].contains("Discard unsaved changes (+1 −0 lines, first change at line 2: x)?")
+        );
+        assert!(rows[6].contains("Save     Discard     Cancel"));
+        assert_eq!(view.cursor_pos(Rect::new(0, 0, 80, 20)), None);
+
+        // Esc backs out to the buffer as it was.
+        view.on_esc();
+        assert!(view.confirm_close.is_none());
+        assert!(!view.is_complete());
+        type_str(&mut view, "y");
+        assert_eq!(view.textarea.text(), "theme = dark\nxy");
+    }
+
+    #[test]
+    fn save_at_close_confirmation_saves_then_closes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preferences.md");
+        let mut view = PreferencesEditorView::new(path.clone(), String::new());
+        type_str(&mut view, "a");
+        view.on_esc();
+        press(&mut view, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+        assert!(view.is_complete());
+
+        // A save that stops to ask first leaves the editor open.
+        let mut view = PreferencesEditorView::new(path.clone(), "a\n".to_string());
+        type_str(&mut view, "b");
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(&path, "changed elsewhere\n").unwrap();
+        view.on_esc();
+        press(&mut view, KeyCode::Char('s'), KeyModifiers::NONE);
+        assert!(!view.is_complete());
+        assert!(view.status_span().content.contains("changed on disk"));
+    }
 }
@@ -0,0 +1,134 @@
+//! Inline completion of preference keys typed at the start of a line, from a
+//! list of known keys supplied by the host.
+
+use std::ops::Range;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Clear;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Widget;
+use unicode_width::UnicodeWidthStr;
+
+/// Suggestions shown in the popup at once.
+const MAX_SUGGESTIONS: usize = 5;
+
+pub(super) struct KeyCompletion {
+    /// The partial key being completed.
+    pub(super) prefix: Range<usize>,
+    matches: Vec<String>,
+    selected: usize,
+}
+
+impl KeyCompletion {
+    /// Suggestions for the key ending at `cursor`, when the cursor is at the
+    /// end of the first word on its line. Matching ignores ASCII case and
+    /// skips a key that is already typed out in full.
+    pub(super) fn find(text: &str, cursor: usize, keys: &[String]) -> Option<Self> {
+        if text[cursor..].chars().next().is_some_and(is_key_char) {
+            return None;
+        }
+        let line_start = text[..cursor].rfind('\n').map_or(0, |i| i + 1);
+        let before = &text[line_start..cursor];
+        let typed = before.trim_start();
+        if typed.is_empty() || !typed.chars().all(is_key_char) {
+            return None;
+        }
+        let typed_lower = typed.to_ascii_lowercase();
+        let matches: Vec<String> = keys
+            .iter()
+            .filter(|key| {
+                key.len() > typed.len() && key.to_ascii_lowercase().starts_with(&typed_lower)
+            })
+            .take(MAX_SUGGESTIONS)
+            .cloned()
+            .collect();
+        (!matches.is_empty()).then(|| Self {
+            prefix: cursor - typed.len()..cursor,
+            matches,
+            selected: 0,
+        })
+    }
+
+    pub(super) fn selected(&self) -> &str {
+        &self.matches[self.selected]
+    }
+
+    /// Move the highlight by one, wrapping around.
+    pub(super) fn step(&mut self, forward: bool) {
+        let len = self.matches.len();
+        self.selected = if forward {
+            (self.selected + 1) % len
+        } else {
+            (self.selected + len - 1) % len
+        };
+    }
+
+    /// Draw the suggestions just below `(x, y)`, kept inside `bounds`.
+    pub(super) fn render(&self, (x, y): (u16, u16), bounds: Rect, buf: &mut Buffer) {
+        let width = self
+            .matches
+            .iter()
+            .map(|key| key.width() as u16 + 2)
+            .max()
+            .unwrap_or(0)
+            .min(bounds.width);
+        let top = y.saturating_add(1);
+        let height = (self.matches.len() as u16).min(bounds.bottom().saturating_sub(top));
+        if width == 0 || height == 0 {
+            return;
+        }
+        let left = x.min(bounds.right().saturating_sub(width));
+        let area = Rect::new(left, top, width, height);
+        Clear.render(area, buf);
+        let lines: Vec<Line<'static>> = self
+            .matches
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let label = format!(" {key} ");
+                if i == self.selected {
+                    label.reversed().into()
+                } else {
+                    label.dim().into()
+                }
+            })
+            .collect();
+        Paragraph::new(lines).render(area, buf);
+    }
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn find_matches_the_first_word_of_the_line() {
+        let keys: Vec<String> = ["model", "model_provider", "approval_policy"]
+            .iter()
+            .map(|key| (*key).to_string())
+            .collect();
+        let text = "x = 1\n  Mod";
+        let completion = KeyCompletion::find(text, text.len(), &keys).unwrap();
+        assert_eq!(completion.prefix, 8..11);
+        assert_eq!(completion.selected(), "model");
+
+        // A full key still offers the longer ones.
+        let text = "model";
+        let mut completion = KeyCompletion::find(text, text.len(), &keys).unwrap();
+        assert_eq!(completion.selected(), "model_provider");
+        completion.step(false);
+        assert_eq!(completion.selected(), "model_provider");
+
+        assert!(KeyCompletion::find("x = mod", 7, &keys).is_none());
+        assert!(KeyCompletion::find("modx", 3, &keys).is_none());
+        assert!(KeyCompletion::find("\n", 1, &keys).is_none());
+    }
+}
@@ -0,0 +1,149 @@
+//! Save / Discard / Cancel choice shown when the editor is closed with
+//! unsaved changes, so throwing edits away is always an explicit step.
+
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyModifiers;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::text::Span;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Widget;
+
+use crate::bottom_pane::CancellationEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum CloseChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+impl CloseChoice {
+    const ALL: [Self; 3] = [Self::Save, Self::Discard, Self::Cancel];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Save => "Save",
+            Self::Discard => "Discard",
+            Self::Cancel => "Cancel",
+        }
+    }
+}
+
+pub(super) struct ConfirmClose {
+    /// What would be lost, e.g. `+1 −0 lines, first change at line 2`.
+    summary: Option<String>,
+    /// Color of the question.
+    warning: Color,
+    selected: usize,
+    choice: Option<CloseChoice>,
+}
+
+impl ConfirmClose {
+    pub(super) fn new(summary: Option<String>, warning: Color) -> Self {
+        Self {
+            summary,
+            warning,
+            selected: 0,
+            choice: None,
+        }
+    }
+
+    /// The option picked, once one has been.
+    pub(super) fn choice(&self) -> Option<CloseChoice> {
+        self.choice
+    }
+
+    /// Arrows move between the options and Enter picks one; the first letter
+    /// of an option picks it directly.
+    pub(super) fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if !(key_event.modifiers - KeyModifiers::SHIFT).is_empty() {
+            return;
+        }
+        let last = CloseChoice::ALL.len() - 1;
+        match key_event.code {
+            KeyCode::Left | KeyCode::Up | KeyCode::BackTab => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(last);
+            }
+            KeyCode::Right | KeyCode::Down | KeyCode::Tab => {
+                self.selected = if self.selected == last {
+                    0
+                } else {
+                    self.selected + 1
+                };
+            }
+            KeyCode::Enter => self.choice = Some(CloseChoice::ALL[self.selected]),
+            KeyCode::Char(ch) => {
+                self.choice = CloseChoice::ALL
+                    .into_iter()
+                    .find(|choice| choice.label().starts_with(ch.to_ascii_uppercase()));
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn on_esc(&mut self) -> CancellationEvent {
+        self.choice = Some(CloseChoice::Cancel);
+        CancellationEvent::Handled
+    }
+
+    pub(super) fn render(&self, area: Rect, buf: &mut Buffer) {
+        let question = match &self.summary {
+            Some(summary) => format!("Discard unsaved changes ({summary})?"),
+            None => "Discard unsaved changes?".to_string(),
+        };
+        let mut options: Vec<Span<'static>> = Vec::new();
+        for (i, choice) in CloseChoice::ALL.into_iter().enumerate() {
+            if i > 0 {
+                options.push("   ".into());
+            }
+            let label = format!(" {} ", choice.label());
+            options.push(if i == self.selected {
+                label.reversed().bold()
+            } else {
+                label.into()
+            });
+        }
+        Paragraph::new(vec![
+            Line::from(question.fg(self.warning)),
+            Line::default(),
+            Line::from(options),
+        ])
+        .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn press(view: &mut ConfirmClose, code: KeyCode) {
+        view.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn arrows_wrap_around_and_letters_pick_directly() {
+        let mut view = ConfirmClose::new(None, Color::Yellow);
+        press(&mut view, KeyCode::Left);
+        press(&mut view, KeyCode::Enter);
+        assert_eq!(view.choice(), Some(CloseChoice::Cancel));
+
+        let mut view = ConfirmClose::new(None, Color::Yellow);
+        press(&mut view, KeyCode::Right);
+        assert_eq!(view.choice(), None);
+        press(&mut view, KeyCode::Enter);
+        assert_eq!(view.choice(), Some(CloseChoice::Discard));
+
+        let mut view = ConfirmClose::new(None, Color::Yellow);
+        press(&mut view, KeyCode::Char('s'));
+        assert_eq!(view.choice(), Some(CloseChoice::Save));
+        let mut view = ConfirmClose::new(None, Color::Yellow);
+        view.on_esc();
+        assert_eq!(view.choice(), Some(CloseChoice::Cancel));
+    }
+}
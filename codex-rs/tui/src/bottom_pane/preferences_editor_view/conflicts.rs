@@ -0,0 +1,128 @@
+//! Git merge-conflict regions (`<<<<<<<` … `=======` … `>>>>>>>`) and the
+//! text that resolving one would leave behind. diff3-style `|||||||` base
+//! sections are recognised and dropped on resolution.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Conflict {
+    /// Every line of the region, markers included, up to and including the
+    /// newline after `>>>>>>>`.
+    pub(super) range: Range<usize>,
+    pub(super) ours: Range<usize>,
+    pub(super) theirs: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+impl Conflict {
+    /// The lines that belong to neither side: the markers and any diff3 base.
+    pub(super) fn marker_lines(&self, text: &str) -> Vec<Range<usize>> {
+        let mut markers = Vec::new();
+        let mut offset = self.range.start;
+        for line in text[self.range.clone()].split_inclusive('\n') {
+            let end = offset + line.len();
+            if !self.ours.contains(&offset) && !self.theirs.contains(&offset) {
+                markers.push(offset..end);
+            }
+            offset = end;
+        }
+        markers
+    }
+
+    pub(super) fn resolve(&self, text: &str, resolution: Resolution) -> String {
+        match resolution {
+            Resolution::Ours => text[self.ours.clone()].to_string(),
+            Resolution::Theirs => text[self.theirs.clone()].to_string(),
+            Resolution::Both => {
+                format!("{}{}", &text[self.ours.clone()], &text[self.theirs.clone()])
+            }
+        }
+    }
+}
+
+/// Every complete conflict region in `text`, in order. Unterminated regions
+/// are ignored.
+pub(super) fn conflicts(text: &str) -> Vec<Conflict> {
+    enum State {
+        Outside,
+        Ours {
+            start: usize,
+            ours: usize,
+        },
+        Base {
+            start: usize,
+            ours: Range<usize>,
+        },
+        Theirs {
+            start: usize,
+            ours: Range<usize>,
+            theirs: usize,
+        },
+    }
+    let mut found = Vec::new();
+    let mut state = State::Outside;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let end = offset + line.len();
+        state = match state {
+            State::Outside if line.starts_with("<<<<<<<") => State::Ours {
+                start: offset,
+                ours: end,
+            },
+            State::Ours { start, ours } if line.starts_with("|||||||") => State::Base {
+                start,
+                ours: ours..offset,
+            },
+            State::Ours { start, ours } if line.starts_with("=======") => State::Theirs {
+                start,
+                ours: ours..offset,
+                theirs: end,
+            },
+            State::Base { start, ours } if line.starts_with("=======") => State::Theirs {
+                start,
+                ours,
+                theirs: end,
+            },
+            State::Theirs {
+                start,
+                ours,
+                theirs,
+            } if line.starts_with(">>>>>>>") => {
+                found.push(Conflict {
+                    range: start..end,
+                    ours,
+                    theirs: theirs..offset,
+                });
+                State::Outside
+            }
+            state => state,
+        };
+        offset = end;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn finds_and_resolves_conflicts() {
+        let text = "a\n<<<<<<< HEAD\nmine\n||||||| base\nold\n=======\ntheirs\n>>>>>>> branch\nz\n";
+        let found = conflicts(text);
+        assert_eq!(found.len(), 1);
+        let conflict = &found[0];
+        assert_eq!(&text[conflict.ours.clone()], "mine\n");
+        assert_eq!(&text[conflict.theirs.clone()], "theirs\n");
+        assert_eq!(conflict.marker_lines(text).len(), 5);
+        assert_eq!(conflict.resolve(text, Resolution::Both), "mine\ntheirs\n");
+        assert!(conflicts("<<<<<<< HEAD\nmine\n=======\n").is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+//! Typed notifications the preferences editor can push to its host, for
+//! logging, analytics, or asserting behaviour in tests.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PreferencesEditorEvent {
+    /// An event sender was attached to the editor.
+    Opened {
+        path: PathBuf,
+    },
+    /// The buffer changed.
+    Edited,
+    Saved {
+        path: PathBuf,
+    },
+    SaveFailed {
+        error: String,
+    },
+    /// The validator reported problems.
+    ValidationFailed {
+        errors: usize,
+    },
+    /// The editor closed while it still had unsaved changes.
+    Discarded,
+    /// The editor closed with everything saved.
+    Closed,
+    /// Ctrl+C was pressed again at the discard prompt: the user is quitting
+    /// rather than just closing the editor. The buffer is kept in the session
+    /// trash and the host decides what to do.
+    QuitRequested,
+    /// Sent right after `Closed` or `Discarded`.
+    SessionEnded {
+        stats: SessionStats,
+    },
+}
+
+/// What happened to the buffer between opening and closing the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SessionStats {
+    /// Lines added and removed, comparing the final buffer with what was
+    /// loaded.
+    pub(crate) lines_added: usize,
+    pub(crate) lines_removed: usize,
+    /// Characters inserted plus characters deleted, summed over every edit.
+    pub(crate) chars_changed: usize,
+    pub(crate) saves: usize,
+    pub(crate) duration: Duration,
+}
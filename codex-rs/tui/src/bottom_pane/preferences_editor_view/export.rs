@@ -0,0 +1,59 @@
+//! Render the preferences buffer as HTML for pasting into rich documents.
+
+use std::path::PathBuf;
+
+use pulldown_cmark::Options;
+use pulldown_cmark::Parser;
+use pulldown_cmark::html;
+use tempfile::Builder;
+
+use crate::clipboard_copy;
+
+/// Where an HTML export ended up.
+#[derive(Debug)]
+pub(super) enum HtmlExport {
+    Clipboard,
+    /// The clipboard was unavailable, so the HTML was written here instead.
+    TempFile(PathBuf),
+}
+
+pub(super) fn markdown_to_html(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(markdown, options));
+    out
+}
+
+/// Put the rendered HTML on the clipboard, falling back to a temp file that
+/// outlives the editor.
+pub(super) fn export_html(markdown: &str) -> Result<HtmlExport, String> {
+    let html = markdown_to_html(markdown);
+    if clipboard_copy::copy_html(&html, markdown).is_ok() {
+        return Ok(HtmlExport::Clipboard);
+    }
+    let tmp = Builder::new()
+        .prefix("codex-preferences-")
+        .suffix(".html")
+        .tempfile()
+        .map_err(|e| e.to_string())?;
+    std::fs::write(tmp.path(), &html).map_err(|e| e.to_string())?;
+    let (_file, path) = tmp.keep().map_err(|e| e.error.to_string())?;
+    Ok(HtmlExport::TempFile(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn renders_markdown_blocks() {
+        assert_eq!(
+            markdown_to_html("# Title\n\nHello"),
+            "<h1>Title</h1>\n<p>Hello</p>\n"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Handing the buffer to the user's `$EDITOR` for a while and taking back
+//! what they saved there.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::ExitStatus;
+
+use tempfile::TempPath;
+
+use super::file_format::FileFormat;
+
+/// A copy of the buffer out with the user's editor. The app runs the editor,
+/// since only it can hand over the terminal, and reports back how it exited.
+pub(super) struct ExternalEdit {
+    /// The editor program, for status messages.
+    pub(super) name: String,
+    /// Removed once the edit is finished or abandoned.
+    file: TempPath,
+}
+
+impl ExternalEdit {
+    /// Write `text` to a temp file named like `path`, so the editor picks the
+    /// same syntax, and return the command that opens it with `editor`.
+    /// Errors are ready for the status line.
+    pub(super) fn start(editor: &str, path: &Path, text: &str) -> Result<(Self, Command), String> {
+        let mut command = command(editor)?;
+        let stem = path
+            .file_stem()
+            .map(|stem| format!("{}-", stem.to_string_lossy()))
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+        let mut file = tempfile::Builder::new()
+            .prefix(&stem)
+            .suffix(&extension)
+            .tempfile()
+            .map_err(|err| format!("could not create a temp file: {err}"))?;
+        file.write_all(text.as_bytes())
+            .map_err(|err| format!("could not write the temp file: {err}"))?;
+        // Close our handle before the editor opens the file; the path is
+        // still removed when the edit is dropped.
+        let file = file.into_temp_path();
+        command.arg(&file);
+        let name = command.get_program().to_string_lossy().into_owned();
+        Ok((Self { name, file }, command))
+    }
+
+    /// What the editor saved, given how it exited.
+    pub(super) fn finish(self, status: io::Result<ExitStatus>) -> Result<String, String> {
+        let status = status.map_err(|err| format!("could not start: {err}"))?;
+        if !status.success() {
+            return Err(format!("exited with {status}"));
+        }
+        let contents = fs::read_to_string(&self.file)
+            .map_err(|err| format!("could not read the temp file: {err}"))?;
+        Ok(FileFormat::decode(contents).1)
+    }
+}
+
+/// Split `editor` like a shell would, so values such as `code --wait` work.
+fn command(editor: &str) -> Result<Command, String> {
+    let words = shlex::split(editor).ok_or_else(|| format!("could not parse {editor:?}"))?;
+    let (program, args) = words.split_first().ok_or("$EDITOR is not set")?;
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn empty_or_unbalanced_editors_are_rejected() {
+        assert_eq!(command("  ").err(), Some("$EDITOR is not set".to_string()));
+        assert_eq!(
+            command("vim 'oops").err(),
+            Some("could not parse \"vim 'oops\"".to_string())
+        );
+        let command = command("code --wait").unwrap();
+        assert_eq!(command.get_program(), "code");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["--wait"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn returns_what_the_editor_saved_and_reports_failures() {
+        let path = Path::new("/prefs/preferences.md");
+        let (edit, mut command) =
+            ExternalEdit::start("sh -c 'printf \"a: 2\\r\\n\" > \"$1\"' sh", path, "a: 1\n")
+                .unwrap();
+        let file = command.get_args().last().unwrap().to_owned();
+        assert!(file.to_string_lossy().ends_with(".md"));
+        let status = command.status();
+        assert_eq!(edit.finish(status), Ok("a: 2\n".to_string()));
+        assert!(!Path::new(&file).exists());
+
+        let (edit, mut command) = ExternalEdit::start("false", path, "a: 1\n").unwrap();
+        let status = command.status();
+        assert_eq!(
+            edit.finish(status),
+            Err("exited with exit status: 1".to_string())
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! How the file on disk differs from the buffer. Line endings are
+//! normalized to `\n` while editing and a leading byte order mark is hidden;
+//! both are restored on save, so the file doesn't come back with every line
+//! changed.
+
+use std::borrow::Cow;
+
+const BOM: char = '\u{feff}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The ending most lines of `text` use. Ties, and text without line
+    /// breaks, count as LF.
+    fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf { Self::CrLf } else { Self::Lf }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) struct FileFormat {
+    pub(super) line_ending: LineEnding,
+    /// The file starts with a UTF-8 byte order mark.
+    pub(super) bom: bool,
+}
+
+impl FileFormat {
+    /// Split file contents into their format and the text for the buffer.
+    pub(super) fn decode(mut contents: String) -> (Self, String) {
+        let bom = contents.starts_with(BOM);
+        if bom {
+            contents.drain(..BOM.len_utf8());
+        }
+        let line_ending = LineEnding::detect(&contents);
+        let text = if contents.contains("\r\n") {
+            contents.replace("\r\n", "\n")
+        } else {
+            contents
+        };
+        (Self { line_ending, bom }, text)
+    }
+
+    /// The bytes to write for buffer `text`. Mixed files come back with
+    /// the ending most of their lines had.
+    pub(super) fn encode<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = match self.line_ending {
+            LineEnding::Lf => Cow::Borrowed(text),
+            LineEnding::CrLf => Cow::Owned(text.replace('\n', "\r\n")),
+        };
+        if self.bom {
+            Cow::Owned(format!("{BOM}{text}"))
+        } else {
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn round_trips_the_dominant_line_ending() {
+        let (format, text) = FileFormat::decode("a\r\nb\r\nc\n".to_string());
+        assert_eq!(format.line_ending, LineEnding::CrLf);
+        assert_eq!(text, "a\nb\nc\n");
+        assert_eq!(format.encode(&text), "a\r\nb\r\nc\r\n");
+
+        let (format, text) = FileFormat::decode("a\nb\r\nc\n".to_string());
+        assert_eq!(format.line_ending, LineEnding::Lf);
+        assert_eq!(format.encode(&text), "a\nb\nc\n");
+
+        let (format, _) = FileFormat::decode("no newline".to_string());
+        assert_eq!(format, FileFormat::default());
+    }
+
+    #[test]
+    fn strips_and_restores_a_byte_order_mark() {
+        let (format, text) = FileFormat::decode("\u{feff}a\r\n".to_string());
+        assert!(format.bom);
+        assert_eq!(text, "a\n");
+        assert_eq!(format.encode(&text), "\u{feff}a\r\n");
+    }
+}
@@ -0,0 +1,95 @@
+//! Markdown heading sections that the preferences editor can fold.
+
+use std::ops::Range;
+
+/// An ATX heading and the lines it owns: everything up to the next heading
+/// of the same or a higher level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Section {
+    /// The heading line, without its newline.
+    pub(super) heading: Range<usize>,
+    /// Whole lines after the heading; empty when the heading has no body.
+    pub(super) body: Range<usize>,
+    pub(super) level: usize,
+}
+
+impl Section {
+    /// Number of lines hidden when the section is folded.
+    pub(super) fn body_lines(&self, text: &str) -> usize {
+        let body = &text[self.body.clone()];
+        body.lines().count()
+    }
+
+    /// True when `pos` is on the heading line or inside the body.
+    pub(super) fn contains(&self, pos: usize) -> bool {
+        (self.heading.start..self.body.end.max(self.heading.end + 1)).contains(&pos)
+    }
+}
+
+/// Heading level of `line` (1-6), if it is an ATX heading.
+pub(super) fn heading_level(line: &str) -> Option<usize> {
+    let level = line.bytes().take_while(|b| *b == b'#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))).then_some(level)
+}
+
+/// All heading sections in `text`, in document order. Headings inside fenced
+/// code blocks are ignored.
+pub(super) fn sections(text: &str) -> Vec<Section> {
+    let mut headings: Vec<(Range<usize>, usize, usize)> = Vec::new();
+    let mut in_fence = false;
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        if content.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence && let Some(level) = heading_level(content) {
+            headings.push((start..start + content.len(), level, start + line.len()));
+        }
+        start += line.len();
+    }
+
+    headings
+        .iter()
+        .enumerate()
+        .map(|(i, (heading, level, body_start))| {
+            let body_end = headings[i + 1..]
+                .iter()
+                .find(|(_, next_level, _)| next_level <= level)
+                .map_or(text.len(), |(next, _, _)| next.start);
+            Section {
+                heading: heading.clone(),
+                body: *body_start..body_end.max(*body_start),
+                level: *level,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn sections_nest_by_level_and_skip_code_fences() {
+        let text = "# A\na1\n## B\nb1\n```\n# not\n```\n# C\n";
+        let found = sections(text);
+        let headings: Vec<&str> = found.iter().map(|s| &text[s.heading.clone()]).collect();
+        assert_eq!(headings, vec!["# A", "## B", "# C"]);
+        assert_eq!(
+            &text[found[0].body.clone()],
+            "a1\n## B\nb1\n```\n# not\n```\n"
+        );
+        assert_eq!(&text[found[1].body.clone()], "b1\n```\n# not\n```\n");
+        assert_eq!(found[2].body, text.len()..text.len());
+        assert_eq!(found[1].body_lines(text), 4);
+    }
+
+    #[test]
+    fn heading_level_requires_space() {
+        assert_eq!(heading_level("## Tools"), Some(2));
+        assert_eq!(heading_level("#hashtag"), None);
+        assert_eq!(heading_level("####### seven"), None);
+    }
+}
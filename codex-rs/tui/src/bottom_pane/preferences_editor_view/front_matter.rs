@@ -0,0 +1,131 @@
+//! Line-based handling of the YAML (`---`) or TOML (`+++`) block at the top
+//! of a preferences file. Only top-level keys are interpreted; anything
+//! indented under a key travels with it untouched.
+
+use std::ops::Range;
+
+/// Byte range of the lines between the opening and closing fences, and
+/// whether the block is TOML.
+pub(super) fn body_range(text: &str) -> Option<(Range<usize>, bool)> {
+    let first_end = text.find('\n')?;
+    let fence = text[..first_end].trim_end();
+    let toml = match fence {
+        "---" => false,
+        "+++" => true,
+        _ => return None,
+    };
+    let start = first_end + 1;
+    let mut offset = start;
+    for line in text[start..].split_inclusive('\n') {
+        if line.trim_end() == fence {
+            return Some((start..offset, toml));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// One top-level key with the comments above it and the indented lines
+/// below it.
+struct Entry<'a> {
+    key: &'a str,
+    lines: Vec<&'a str>,
+}
+
+/// `body` with its top-level keys sorted alphabetically. TOML tables keep
+/// their order and have their own keys sorted. Errors name the first line
+/// that is not a key, comment, or continuation.
+pub(super) fn sort_keys(body: &str, toml: bool) -> Result<String, String> {
+    let mut out = String::with_capacity(body.len());
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut pending: Vec<&str> = Vec::new();
+    for (index, line) in body.split_inclusive('\n').enumerate() {
+        let content = line.trim_end();
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            pending.push(line);
+        } else if content.len() != trimmed.len() || (toml && is_toml_continuation(trimmed)) {
+            let Some(entry) = entries.last_mut() else {
+                return Err(format!("line {} is indented but has no key", index + 2));
+            };
+            entry.lines.append(&mut pending);
+            entry.lines.push(line);
+        } else if toml && trimmed.starts_with('[') {
+            flush(&mut out, &mut entries)?;
+            out.extend(pending.drain(..));
+            out.push_str(line);
+        } else if let Some(key) = top_level_key(trimmed, toml) {
+            pending.push(line);
+            entries.push(Entry {
+                key,
+                lines: std::mem::take(&mut pending),
+            });
+        } else {
+            return Err(format!("line {} is not a key", index + 2));
+        }
+    }
+    flush(&mut out, &mut entries)?;
+    out.extend(pending);
+    Ok(out)
+}
+
+fn flush(out: &mut String, entries: &mut Vec<Entry>) -> Result<(), String> {
+    entries.sort_by(|a, b| {
+        a.key
+            .to_lowercase()
+            .cmp(&b.key.to_lowercase())
+            .then(a.key.cmp(b.key))
+    });
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].key == pair[1].key) {
+        return Err(format!("duplicate key `{}`", pair[0].key));
+    }
+    for entry in entries.drain(..) {
+        for line in entry.lines {
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+    }
+    Ok(())
+}
+
+fn top_level_key(line: &str, toml: bool) -> Option<&str> {
+    let separator = if toml { '=' } else { ':' };
+    let (key, _) = line.split_once(separator)?;
+    let key = key.trim();
+    (!key.is_empty() && !key.starts_with('-')).then_some(key)
+}
+
+/// Closing lines of multi-line TOML arrays, inline tables, and strings.
+fn is_toml_continuation(line: &str) -> bool {
+    line.starts_with(']') || line.starts_with('}') || line.starts_with("\"\"\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn sorts_yaml_keys_keeping_nested_values_and_comments() {
+        let text = "---\ntitle: x\n# shown in lists\nalias: y\nlist:\n  - b\n  - a\n---\nbody\n";
+        let (range, toml) = body_range(text).unwrap();
+        assert!(!toml);
+        assert_eq!(
+            sort_keys(&text[range], toml).unwrap(),
+            "# shown in lists\nalias: y\nlist:\n  - b\n  - a\ntitle: x\n"
+        );
+        assert!(sort_keys("- a\n- b\n", false).is_err());
+        assert!(sort_keys("a: 1\na: 2\n", false).is_err());
+    }
+
+    #[test]
+    fn sorts_toml_keys_within_each_table() {
+        let body = "b = 1\na = [\n  1,\n]\n[table]\nz = 1\ny = 2\n";
+        assert_eq!(
+            sort_keys(body, true).unwrap(),
+            "a = [\n  1,\n]\nb = 1\n[table]\ny = 2\nz = 1\n"
+        );
+    }
+}
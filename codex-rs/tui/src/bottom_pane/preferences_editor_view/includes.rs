@@ -0,0 +1,62 @@
+//! References from the preferences buffer to other files, e.g.
+//! `include: other.md`, which Ctrl+O follows.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use regex_lite::Regex;
+
+use super::text_ops;
+
+/// The file referenced on the line at `pos`: the first capture group of the
+/// `pattern` match covering the cursor, or of the line's only match.
+pub(super) fn include_at<'a>(text: &'a str, pos: usize, pattern: &Regex) -> Option<&'a str> {
+    let start = text_ops::line_start(text, pos);
+    let line = &text[start..text_ops::line_end(text, pos)];
+    let col = pos - start;
+    let matches: Vec<_> = pattern.captures_iter(line).collect();
+    let captures = match matches.as_slice() {
+        [only] => only,
+        all => all.iter().find(|captures| {
+            captures
+                .get(0)
+                .is_some_and(|m| (m.start()..=m.end()).contains(&col))
+        })?,
+    };
+    let reference = captures.get(1)?.as_str().trim();
+    (!reference.is_empty()).then_some(reference)
+}
+
+/// Where `reference` points, read relative to the directory of `from`.
+pub(super) fn resolve(from: &Path, reference: &str) -> PathBuf {
+    let reference = Path::new(reference);
+    match from.parent() {
+        Some(dir) if reference.is_relative() => dir.join(reference),
+        _ => reference.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn include_at_picks_the_reference_under_the_cursor() {
+        let pattern = Regex::new(r"include:\s*(\S+)").unwrap();
+        let text = "a: 1\ninclude: other.md\ninclude: a.md include: b.md";
+        assert_eq!(include_at(text, 0, &pattern), None);
+        assert_eq!(include_at(text, 5, &pattern), Some("other.md"));
+        assert_eq!(include_at(text, text.len(), &pattern), Some("b.md"));
+        assert_eq!(include_at(text, text.len() - 20, &pattern), Some("a.md"));
+
+        assert_eq!(
+            resolve(Path::new("/prefs/main.md"), "extra/other.md"),
+            PathBuf::from("/prefs/extra/other.md")
+        );
+        assert_eq!(
+            resolve(Path::new("/prefs/main.md"), "/etc/other.md"),
+            PathBuf::from("/etc/other.md")
+        );
+    }
+}
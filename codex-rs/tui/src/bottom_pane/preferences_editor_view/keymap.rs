@@ -0,0 +1,119 @@
+//! Remappable bindings for the preferences editor's main commands, for
+//! terminals that swallow keys like Ctrl+S (flow control).
+
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyModifiers;
+
+/// A key and the modifiers held with it. Super counts as Control, and an
+/// uppercase letter counts as Shift plus the letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub(crate) fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let (code, modifiers) = normalize(code, modifiers);
+        Self { code, modifiers }
+    }
+
+    /// Control plus `ch`.
+    pub(crate) fn ctrl(ch: char) -> Self {
+        Self::new(KeyCode::Char(ch), KeyModifiers::CONTROL)
+    }
+
+    pub(crate) fn matches(&self, key_event: KeyEvent) -> bool {
+        normalize(key_event.code, key_event.modifiers) == (self.code, self.modifiers)
+    }
+
+    /// How the hint line names the binding, e.g. `Ctrl+S`.
+    pub(crate) fn label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("Alt+");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            label.push_str("Shift+");
+        }
+        match self.code {
+            KeyCode::Char(ch) => label.extend(ch.to_uppercase()),
+            code => label.push_str(&code.to_string()),
+        }
+        label
+    }
+}
+
+fn normalize(code: KeyCode, mut modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    if modifiers.contains(KeyModifiers::SUPER) {
+        modifiers = (modifiers - KeyModifiers::SUPER) | KeyModifiers::CONTROL;
+    }
+    match code {
+        KeyCode::Char(ch) if ch.is_uppercase() => (
+            KeyCode::Char(ch.to_lowercase().next().unwrap_or(ch)),
+            modifiers | KeyModifiers::SHIFT,
+        ),
+        code => (code, modifiers),
+    }
+}
+
+/// Keys for the editor's main commands. Esc always closes, whatever `close`
+/// is set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EditorKeymap {
+    pub(crate) save: KeyBinding,
+    pub(crate) save_as: KeyBinding,
+    /// Close the editor, or copy the selection, per the Ctrl+C behavior.
+    pub(crate) close: KeyBinding,
+    pub(crate) search: KeyBinding,
+    pub(crate) go_to_line: KeyBinding,
+}
+
+impl Default for EditorKeymap {
+    fn default() -> Self {
+        Self {
+            save: KeyBinding::ctrl('s'),
+            save_as: KeyBinding::new(
+                KeyCode::Char('s'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            ),
+            close: KeyBinding::ctrl('c'),
+            search: KeyBinding::ctrl('f'),
+            go_to_line: KeyBinding::ctrl('g'),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn bindings_match_shifted_and_super_variants() {
+        let keymap = EditorKeymap::default();
+        let key = |ch, modifiers| KeyEvent::new(KeyCode::Char(ch), modifiers);
+
+        assert!(keymap.save.matches(key('s', KeyModifiers::CONTROL)));
+        assert!(keymap.save.matches(key('s', KeyModifiers::SUPER)));
+        assert!(!keymap.save.matches(key('s', KeyModifiers::NONE)));
+        assert!(keymap.save_as.matches(key('S', KeyModifiers::CONTROL)));
+        assert!(
+            keymap
+                .save_as
+                .matches(key('S', KeyModifiers::CONTROL | KeyModifiers::SHIFT))
+        );
+        assert!(!keymap.save.matches(key('S', KeyModifiers::CONTROL)));
+
+        assert_eq!(keymap.save.label(), "Ctrl+S");
+        assert_eq!(keymap.save_as.label(), "Ctrl+Shift+S");
+        assert_eq!(
+            KeyBinding::new(KeyCode::F(2), KeyModifiers::NONE).label(),
+            "F2"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Editing every occurrence of a word at once. All occurrences hold the same
+//! text, so an edit is made to one copy of the word and then written back to
+//! each of them.
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Whole-word matches of one word, with a cursor at the same offset in each.
+pub(super) struct Occurrences {
+    /// Byte offsets where the occurrences start, in buffer order.
+    starts: Vec<usize>,
+    /// The occurrence holding the primary cursor.
+    primary: usize,
+    word: String,
+    /// Cursor position within the word, in bytes.
+    offset: usize,
+}
+
+/// A change to make at the cursor of every occurrence.
+pub(super) enum OccurrenceEdit {
+    Insert(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+}
+
+impl Occurrences {
+    /// Every whole-word occurrence of the word in `word_range`, with the
+    /// cursor placed at `cursor`, which must lie inside or at the end of it.
+    pub(super) fn find(text: &str, word_range: Range<usize>, cursor: usize) -> Self {
+        let word = text[word_range.clone()].to_string();
+        let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+        let starts: Vec<usize> = text
+            .match_indices(&word)
+            .map(|(start, _)| start)
+            .filter(|start| {
+                let before = text[..*start].chars().next_back();
+                let after = text[start + word.len()..].chars().next();
+                !before.is_some_and(is_word) && !after.is_some_and(is_word)
+            })
+            .collect();
+        let primary = starts
+            .iter()
+            .position(|start| *start == word_range.start)
+            .unwrap_or_default();
+        Self {
+            starts,
+            primary,
+            offset: cursor - word_range.start,
+            word,
+        }
+    }
+
+    pub(super) fn count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The byte ranges of the occurrences, for highlighting.
+    pub(super) fn ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.starts
+            .iter()
+            .map(|start| *start..start + self.word.len())
+    }
+
+    /// Where the primary cursor sits in the buffer.
+    pub(super) fn cursor(&self) -> usize {
+        self.starts[self.primary] + self.offset
+    }
+
+    /// Apply `edit` and return the rewritten buffer, or `None` when the edit
+    /// only moved the cursors. Edits that reach past a word's edges are
+    /// ignored so the occurrences stay in step.
+    pub(super) fn apply(&mut self, text: &str, edit: OccurrenceEdit) -> Option<String> {
+        let before = &self.word[..self.offset];
+        let after = &self.word[self.offset..];
+        let previous = before.graphemes(true).next_back().map_or(0, str::len);
+        let next = after.graphemes(true).next().map_or(0, str::len);
+        let (word, offset) = match edit {
+            OccurrenceEdit::Left => {
+                self.offset -= previous;
+                return None;
+            }
+            OccurrenceEdit::Right => {
+                self.offset += next;
+                return None;
+            }
+            OccurrenceEdit::Insert(ch) => {
+                (format!("{before}{ch}{after}"), self.offset + ch.len_utf8())
+            }
+            OccurrenceEdit::Backspace if previous > 0 => (
+                format!("{}{after}", &before[..before.len() - previous]),
+                self.offset - previous,
+            ),
+            OccurrenceEdit::Delete if next > 0 => {
+                (format!("{before}{}", &after[next..]), self.offset)
+            }
+            OccurrenceEdit::Backspace | OccurrenceEdit::Delete => return None,
+        };
+        let mut rewritten = String::with_capacity(text.len());
+        let mut copied = 0;
+        let mut starts = Vec::with_capacity(self.starts.len());
+        for start in &self.starts {
+            rewritten.push_str(&text[copied..*start]);
+            starts.push(rewritten.len());
+            rewritten.push_str(&word);
+            copied = start + self.word.len();
+        }
+        rewritten.push_str(&text[copied..]);
+        self.starts = starts;
+        self.word = word;
+        self.offset = offset;
+        Some(rewritten)
+    }
+
+    /// Whether every occurrence has been deleted.
+    pub(super) fn is_empty(&self) -> bool {
+        self.word.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn edits_every_whole_word_occurrence() {
+        let text = "color: red\nbg_color: red\ncolor_fg: color\n";
+        let mut occurrences = Occurrences::find(text, 0..5, 5);
+        assert_eq!(occurrences.count(), 2);
+
+        let text = occurrences.apply(text, OccurrenceEdit::Backspace).unwrap();
+        let text = occurrences
+            .apply(&text, OccurrenceEdit::Insert('u'))
+            .unwrap();
+        let text = occurrences
+            .apply(&text, OccurrenceEdit::Insert('r'))
+            .unwrap();
+        assert_eq!(text, "colour: red\nbg_color: red\ncolor_fg: colour\n");
+        assert_eq!(occurrences.cursor(), 6);
+
+        assert!(occurrences.apply(&text, OccurrenceEdit::Delete).is_none());
+        assert!(occurrences.apply(&text, OccurrenceEdit::Left).is_none());
+        assert_eq!(occurrences.cursor(), 5);
+    }
+}
@@ -0,0 +1,49 @@
+//! Read-only panel drawn in place of the text area, for views of the buffer
+//! that should not be edited directly.
+
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::text::Line;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Widget;
+
+pub(super) struct Overlay {
+    /// Shown in the status line while the overlay is open.
+    pub(super) title: String,
+    lines: Vec<Line<'static>>,
+    scroll: u16,
+}
+
+impl Overlay {
+    pub(super) fn new(title: String, lines: Vec<Line<'static>>) -> Self {
+        Self {
+            title,
+            lines,
+            scroll: 0,
+        }
+    }
+
+    /// Scroll with the arrow and page keys. Returns `false` for keys the
+    /// overlay does not use.
+    pub(super) fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        let last = self.lines.len().saturating_sub(1) as u16;
+        self.scroll = match key_event.code {
+            KeyCode::Up => self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll.saturating_add(1).min(last),
+            KeyCode::PageUp => self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll.saturating_add(10).min(last),
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => return false,
+        };
+        true
+    }
+
+    pub(super) fn render(&self, area: Rect, buf: &mut Buffer) {
+        Paragraph::new(self.lines.clone())
+            .scroll((self.scroll, 0))
+            .render(area, buf);
+    }
+}
@@ -0,0 +1,141 @@
+//! Single-line prompt shown in the preferences editor's status line, used by
+//! commands that need a short argument before they run.
+
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyModifiers;
+
+/// What the prompt's answer will be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PromptKind {
+    EnvVar,
+    FenceLanguage,
+    Surround,
+    ShellCommand,
+    Search,
+    DuplicateCount,
+    SaveAs,
+    GoToLine,
+}
+
+impl PromptKind {
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            PromptKind::EnvVar => "Insert env var: ",
+            PromptKind::FenceLanguage => "Code fence language (Enter for none): ",
+            PromptKind::Surround => "Surround with (e.g. ( or ** or <b> </b>): ",
+            PromptKind::ShellCommand => "Insert output of: ",
+            PromptKind::Search => "Search: ",
+            PromptKind::DuplicateCount => "Duplicate selection how many times: ",
+            PromptKind::SaveAs => "Save as: ",
+            PromptKind::GoToLine => "Go to line: ",
+        }
+    }
+}
+
+pub(super) enum PromptOutcome {
+    Pending,
+    Submitted(String),
+    Cancelled,
+}
+
+pub(super) struct Prompt {
+    pub(super) kind: PromptKind,
+    pub(super) input: String,
+    /// Values offered by Tab, filtered by what was typed before the first Tab.
+    candidates: Vec<String>,
+    completion: Option<Completion>,
+}
+
+struct Completion {
+    matches: Vec<String>,
+    index: usize,
+}
+
+impl Prompt {
+    pub(super) fn new(kind: PromptKind, candidates: Vec<String>) -> Self {
+        Self {
+            kind,
+            input: String::new(),
+            candidates,
+            completion: None,
+        }
+    }
+
+    pub(super) fn handle_key(&mut self, key_event: KeyEvent) -> PromptOutcome {
+        if key_event.code != KeyCode::Tab {
+            self.completion = None;
+        }
+        match key_event.code {
+            KeyCode::Enter => return PromptOutcome::Submitted(self.input.clone()),
+            KeyCode::Esc => return PromptOutcome::Cancelled,
+            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                return PromptOutcome::Cancelled;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Tab => self.complete(),
+            KeyCode::Char(ch)
+                if (key_event.modifiers - KeyModifiers::SHIFT).is_empty() && !ch.is_control() =>
+            {
+                self.input.push(ch);
+            }
+            _ => {}
+        }
+        PromptOutcome::Pending
+    }
+
+    /// Cycle through the candidates that start with the typed prefix.
+    fn complete(&mut self) {
+        let completion = match self.completion.take() {
+            Some(mut completion) => {
+                completion.index = (completion.index + 1) % completion.matches.len();
+                completion
+            }
+            None => {
+                let matches: Vec<String> = self
+                    .candidates
+                    .iter()
+                    .filter(|candidate| candidate.starts_with(&self.input))
+                    .cloned()
+                    .collect();
+                if matches.is_empty() {
+                    return;
+                }
+                Completion { matches, index: 0 }
+            }
+        };
+        self.input = completion.matches[completion.index].clone();
+        self.completion = Some(completion);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn tab_cycles_matching_candidates() {
+        let candidates = vec!["HOME".to_string(), "HOST".to_string(), "PATH".to_string()];
+        let mut prompt = Prompt::new(PromptKind::EnvVar, candidates);
+        prompt.handle_key(key(KeyCode::Char('H')));
+        prompt.handle_key(key(KeyCode::Tab));
+        assert_eq!(prompt.input, "HOME");
+        prompt.handle_key(key(KeyCode::Tab));
+        assert_eq!(prompt.input, "HOST");
+        prompt.handle_key(key(KeyCode::Tab));
+        assert_eq!(prompt.input, "HOME");
+
+        prompt.handle_key(key(KeyCode::Backspace));
+        assert!(matches!(
+            prompt.handle_key(key(KeyCode::Enter)),
+            PromptOutcome::Submitted(input) if input == "HOM"
+        ));
+    }
+}
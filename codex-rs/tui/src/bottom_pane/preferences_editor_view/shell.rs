@@ -0,0 +1,191 @@
+//! Running a shell command whose output is inserted into the preferences
+//! buffer, with a time limit and a cap on how much output is kept.
+
+use std::io;
+use std::io::Read;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long a command may run before it is killed.
+pub(super) const SHELL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest stdout accepted for insertion.
+pub(super) const SHELL_MAX_OUTPUT: usize = 64 * 1024;
+
+/// A command running on a worker thread so the UI keeps drawing; its
+/// output is inserted once it finishes.
+pub(super) struct ShellRun {
+    pub(super) command: String,
+    pub(super) since: Instant,
+    result: mpsc::Receiver<Result<String, String>>,
+}
+
+impl ShellRun {
+    /// Start `command` with the default limits. `on_done` is called on the
+    /// worker once the result is ready, e.g. to schedule a redraw.
+    pub(super) fn start(command: String, on_done: impl FnOnce() + Send + 'static) -> Self {
+        let (tx, result) = mpsc::channel();
+        let worker_command = command.clone();
+        thread::spawn(move || {
+            let output = run(&worker_command, SHELL_TIMEOUT, SHELL_MAX_OUTPUT);
+            // A cancelled run has dropped the receiver.
+            if tx.send(output).is_ok() {
+                on_done();
+            }
+        });
+        Self {
+            command,
+            since: Instant::now(),
+            result,
+        }
+    }
+
+    /// The command's result, once it has finished.
+    pub(super) fn try_result(&self) -> Option<Result<String, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err("the worker stopped".to_string())),
+        }
+    }
+}
+
+/// Run `command` through the platform shell and return its stdout. Errors
+/// are ready for the status line: stderr of a failed command, a timeout, or
+/// output over `max_output` bytes.
+pub(super) fn run(command: &str, timeout: Duration, max_output: usize) -> Result<String, String> {
+    let mut child = shell(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("could not start shell: {err}"))?;
+    // Both pipes are drained on their own threads so a chatty command cannot
+    // block on a full pipe; reading stops once the limit is passed.
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| read_bounded(pipe, max_output));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| read_bounded(pipe, max_output));
+
+    let status = wait(&mut child, timeout)
+        .ok_or_else(|| format!("timed out after {}s", timeout.as_secs_f32()))?;
+    let stdout = stdout
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        let message = stderr.trim();
+        return Err(if message.is_empty() {
+            format!("exited with {status}")
+        } else {
+            message.to_string()
+        });
+    }
+    if stdout.len() > max_output {
+        return Err(format!("output is over {max_output} bytes"));
+    }
+    String::from_utf8(stdout).map_err(|_| "output is not UTF-8".to_string())
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+/// Keep the first `max_output + 1` bytes of `pipe`. The rest is read and
+/// thrown away so the child never blocks on a full pipe.
+fn read_bounded(
+    mut pipe: impl Read + Send + 'static,
+    max_output: usize,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe
+            .by_ref()
+            .take(max_output as u64 + 1)
+            .read_to_end(&mut buf);
+        let _ = io::copy(&mut pipe, &mut io::sink());
+        buf
+    })
+}
+
+/// Wait for `child` to exit, killing it once `timeout` passes.
+fn wait(child: &mut Child, timeout: Duration) -> Option<std::process::ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn run_reports_output_failures_and_limits() {
+        assert_eq!(
+            run("printf 'a\\nb\\n'", SHELL_TIMEOUT, 16),
+            Ok("a\nb\n".to_string())
+        );
+        assert_eq!(
+            run("echo nope >&2; exit 3", SHELL_TIMEOUT, 16),
+            Err("nope".to_string())
+        );
+        assert_eq!(
+            run("printf '%040d' 0", SHELL_TIMEOUT, 16),
+            Err("output is over 16 bytes".to_string())
+        );
+        assert_eq!(
+            run("sleep 5", Duration::from_millis(50), 16),
+            Err("timed out after 0.05s".to_string())
+        );
+    }
+
+    #[test]
+    fn output_past_a_full_pipe_is_reported_as_too_long() {
+        // Far more than a pipe buffer holds, so the child would block on
+        // write if the reader stopped at the limit.
+        assert_eq!(
+            run("head -c 1000000 /dev/zero", Duration::from_secs(5), 16),
+            Err("output is over 16 bytes".to_string())
+        );
+    }
+
+    #[test]
+    fn shell_run_delivers_its_result_off_the_calling_thread() {
+        let (done_tx, done_rx) = mpsc::channel();
+        let run = ShellRun::start("printf hi".to_string(), move || {
+            let _ = done_tx.send(());
+        });
+        done_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(run.try_result(), Some(Ok("hi".to_string())));
+    }
+}
@@ -0,0 +1,113 @@
+//! Soloing a heading section: every other section is commented out so only
+//! it takes effect, and unsoloing uncomments them again.
+
+use std::ops::Range;
+
+use super::folding::Section;
+use super::text_ops;
+
+/// Where a solo commented text out, so it can be restored. The tail is
+/// measured from the end of the buffer so edits inside the soloed section
+/// do not invalidate it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Solo {
+    /// The heading that stays active, for the status line.
+    pub(super) heading: String,
+    /// Commented-out sections before the soloed one.
+    head: Range<usize>,
+    /// Length of the commented-out sections after the soloed one.
+    tail_len: usize,
+}
+
+/// Comment out every section in `sections` except `keep`. Text before the
+/// first heading (such as front matter) is left alone. Returns `None` when
+/// there is nothing else to comment out.
+pub(super) fn solo(
+    text: &str,
+    sections: &[Section],
+    keep: &Section,
+    token: &str,
+) -> Option<(String, Solo)> {
+    let first = sections.first()?.heading.start;
+    let kept = keep.heading.start..keep.body.end.max(keep.heading.end);
+    if first == kept.start && kept.end == text.len() {
+        return None;
+    }
+    let head = text_ops::comment_lines(&text[first..kept.start], token);
+    let tail = text_ops::comment_lines(&text[kept.end..], token);
+    let soloed = format!("{}{head}{}{tail}", &text[..first], &text[kept]);
+    let solo = Solo {
+        heading: text[keep.heading.clone()].to_string(),
+        head: first..first + head.len(),
+        tail_len: tail.len(),
+    };
+    Some((soloed, solo))
+}
+
+/// Undo `solo` on `text`. Returns `None` when the commented-out regions have
+/// been edited so that they no longer line up.
+pub(super) fn unsolo(text: &str, solo: &Solo, token: &str) -> Option<String> {
+    let tail_start = text.len().checked_sub(solo.tail_len)?;
+    if solo.head.end > tail_start
+        || !text.is_char_boundary(solo.head.start)
+        || !text.is_char_boundary(solo.head.end)
+        || !text.is_char_boundary(tail_start)
+    {
+        return None;
+    }
+    let head = &text[solo.head.clone()];
+    let tail = &text[tail_start..];
+    if !is_commented(head, token) || !is_commented(tail, token) {
+        return None;
+    }
+    Some(format!(
+        "{}{}{}{}",
+        &text[..solo.head.start],
+        text_ops::uncomment_lines(head, token),
+        &text[solo.head.end..tail_start],
+        text_ops::uncomment_lines(tail, token)
+    ))
+}
+
+fn is_commented(block: &str, token: &str) -> bool {
+    block
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .all(|line| line.trim_start().starts_with(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::folding;
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn solo_comments_other_sections_and_unsolo_restores() {
+        let text = "intro\n# A\na = 1\n# B\nb = 2\n\n# C\n# old\nc = 3\n";
+        let sections = folding::sections(text);
+        let (soloed, solo) = solo(text, &sections, &sections[1], "//").unwrap();
+        assert_eq!(
+            soloed,
+            "intro\n// # A\n// a = 1\n# B\nb = 2\n\n// # C\n// # old\n// c = 3\n"
+        );
+        assert_eq!(solo.heading, "# B");
+
+        // Edits inside the soloed section are kept.
+        let edited = soloed.replace("b = 2", "b = 22");
+        assert_eq!(
+            unsolo(&edited, &solo, "//").unwrap(),
+            text.replace("b = 2", "b = 22")
+        );
+
+        let broken = soloed.replace("// c = 3", "c = 3");
+        assert_eq!(unsolo(&broken, &solo, "//"), None);
+    }
+
+    #[test]
+    fn solo_needs_another_section() {
+        let text = "intro\n# Only\nx\n";
+        let sections = folding::sections(text);
+        assert_eq!(solo(text, &sections, &sections[0], "#"), None);
+    }
+}
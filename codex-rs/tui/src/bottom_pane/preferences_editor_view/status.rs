@@ -0,0 +1,200 @@
+//! Messages shown on the preferences editor's status line and how long each
+//! kind stays visible.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use ratatui::style::Stylize;
+use ratatui::text::Span;
+
+use super::theme::EditorTheme;
+
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// How often a spinner advances a frame.
+pub(super) const SPINNER_INTERVAL: Duration = Duration::from_millis(80);
+
+/// The spinner frame for an operation that started at `since`.
+pub(super) fn spinner_frame(since: Instant) -> &'static str {
+    let frame = (since.elapsed().as_millis() / SPINNER_INTERVAL.as_millis()) as usize;
+    SPINNER_FRAMES[frame % SPINNER_FRAMES.len()]
+}
+
+/// An operation in progress, shown with a spinner until it ends with a
+/// success or error message.
+pub(super) struct Busy {
+    message: String,
+    since: Instant,
+}
+
+impl Busy {
+    pub(super) fn new(message: String) -> Self {
+        Self {
+            message,
+            since: Instant::now(),
+        }
+    }
+
+    pub(super) fn as_span(&self) -> Span<'static> {
+        format!("{} {}", spinner_frame(self.since), self.message).dim()
+    }
+}
+
+pub(super) struct StatusMessage {
+    pub(super) text: String,
+    pub(super) kind: StatusKind,
+    shown_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum StatusKind {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// How long each status kind stays on screen. `None` keeps the message until
+/// it is replaced or the buffer is edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StatusPersistence {
+    pub(crate) info: Option<Duration>,
+    pub(crate) success: Option<Duration>,
+    pub(crate) warning: Option<Duration>,
+    pub(crate) error: Option<Duration>,
+}
+
+impl Default for StatusPersistence {
+    fn default() -> Self {
+        Self {
+            info: Some(Duration::from_secs(4)),
+            success: Some(Duration::from_secs(4)),
+            warning: Some(Duration::from_secs(10)),
+            error: None,
+        }
+    }
+}
+
+impl StatusPersistence {
+    fn lifetime(&self, kind: StatusKind) -> Option<Duration> {
+        match kind {
+            StatusKind::Info => self.info,
+            StatusKind::Success => self.success,
+            StatusKind::Warning => self.warning,
+            StatusKind::Error => self.error,
+        }
+    }
+}
+
+impl StatusMessage {
+    fn new(text: String, kind: StatusKind) -> Self {
+        Self {
+            text,
+            kind,
+            shown_at: Instant::now(),
+        }
+    }
+
+    pub(super) fn info(text: String) -> Self {
+        Self::new(text, StatusKind::Info)
+    }
+
+    pub(super) fn success(text: String) -> Self {
+        Self::new(text, StatusKind::Success)
+    }
+
+    pub(super) fn warning(text: String) -> Self {
+        Self::new(text, StatusKind::Warning)
+    }
+
+    pub(super) fn error(text: String) -> Self {
+        Self::new(text, StatusKind::Error)
+    }
+
+    /// Time left before the message expires under `policy`, or `None` when it
+    /// persists.
+    pub(super) fn remaining(&self, policy: &StatusPersistence, now: Instant) -> Option<Duration> {
+        let lifetime = policy.lifetime(self.kind)?;
+        Some(lifetime.saturating_sub(now.saturating_duration_since(self.shown_at)))
+    }
+
+    pub(super) fn is_expired(&self, policy: &StatusPersistence, now: Instant) -> bool {
+        self.remaining(policy, now)
+            .is_some_and(|remaining| remaining.is_zero())
+    }
+
+    pub(super) fn as_span(&self, theme: &EditorTheme) -> Span<'static> {
+        match self.kind {
+            StatusKind::Info => self.text.clone().fg(theme.info),
+            StatusKind::Success => self.text.clone().fg(theme.success),
+            StatusKind::Warning => self.text.clone().fg(theme.warning),
+            StatusKind::Error => self.text.clone().fg(theme.error),
+        }
+    }
+}
+
+/// A coarse "how long ago" label for `elapsed`, and how long until the label
+/// would change.
+pub(super) fn relative_time(elapsed: Duration) -> (String, Duration) {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    let secs = elapsed.as_secs();
+    let (label, unit) = match secs {
+        0..10 => return ("just now".to_string(), Duration::from_secs(10) - elapsed),
+        10..MINUTE => (format!("{secs}s ago"), 1),
+        MINUTE..HOUR => (format!("{}m ago", secs / MINUTE), MINUTE),
+        HOUR..DAY => (format!("{}h ago", secs / HOUR), HOUR),
+        _ => (format!("{}d ago", secs / DAY), DAY),
+    };
+    let next = Duration::from_secs((secs / unit + 1) * unit);
+    (label, next.saturating_sub(elapsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn kinds_expire_according_to_policy() {
+        let policy = StatusPersistence::default();
+        let info = StatusMessage::info("hi".to_string());
+        let warning = StatusMessage::warning("careful".to_string());
+        let error = StatusMessage::error("boom".to_string());
+        let later = info.shown_at + Duration::from_secs(5);
+
+        assert!(info.is_expired(&policy, later));
+        assert!(!warning.is_expired(&policy, later));
+        assert!(!error.is_expired(&policy, later + Duration::from_secs(3600)));
+        assert_eq!(error.remaining(&policy, later), None);
+    }
+
+    #[test]
+    fn info_messages_use_the_theme_and_default_to_green() {
+        use ratatui::style::Color;
+
+        let message = StatusMessage::info("hi".to_string());
+        assert_eq!(
+            message.as_span(&EditorTheme::default()).style.fg,
+            Some(Color::Green)
+        );
+        let theme = EditorTheme {
+            info: Color::Blue,
+            ..EditorTheme::default()
+        };
+        assert_eq!(message.as_span(&theme).style.fg, Some(Color::Blue));
+    }
+
+    #[test]
+    fn relative_time_labels_and_next_change() {
+        let secs = Duration::from_secs;
+        assert_eq!(relative_time(secs(3)), ("just now".to_string(), secs(7)));
+        assert_eq!(relative_time(secs(42)), ("42s ago".to_string(), secs(1)));
+        assert_eq!(relative_time(secs(150)), ("2m ago".to_string(), secs(30)));
+        assert_eq!(
+            relative_time(secs(7200)),
+            ("2h ago".to_string(), secs(3600))
+        );
+    }
+}
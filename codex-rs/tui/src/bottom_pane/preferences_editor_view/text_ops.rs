@@ -0,0 +1,1271 @@
+//! Pure text helpers behind the preferences editor's line-oriented commands.
+//! Everything here works on byte offsets into the buffer so the view can
+//! apply the results as a single `replace_range`.
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::IndentStyle;
+use super::LineCapDrop;
+
+/// Byte offset of the start of the line containing `pos`.
+pub(super) fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Byte offset of the end of the line containing `pos` (excluding the `\n`).
+pub(super) fn line_end(text: &str, pos: usize) -> usize {
+    text[pos..].find('\n').map_or(text.len(), |i| i + pos)
+}
+
+/// One-based line and column of `pos`, counting columns in graphemes.
+pub(super) fn line_col(text: &str, pos: usize) -> (usize, usize) {
+    let start = line_start(text, pos);
+    let line = text[..start].matches('\n').count() + 1;
+    (line, text[start..pos].graphemes(true).count() + 1)
+}
+
+/// Byte offset of the start of 1-based `line`, or of the last line when the
+/// text is shorter.
+pub(super) fn line_offset(text: &str, line: usize) -> usize {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    line_start(text, start)
+}
+
+/// Byte offset of 1-based `line` and grapheme `col`, the inverse of
+/// [`line_col`]. Both clamp: past the last line lands on the last line, past
+/// the end of a line on its end.
+pub(super) fn line_col_offset(text: &str, line: usize, col: usize) -> usize {
+    let start = line_offset(text, line);
+    let end = line_end(text, start);
+    text[start..end]
+        .grapheme_indices(true)
+        .nth(col.saturating_sub(1))
+        .map_or(end, |(i, _)| start + i)
+}
+
+/// Characters (grapheme clusters, newlines included) and whitespace-separated
+/// words in `text`.
+pub(super) fn char_and_word_count(text: &str) -> (usize, usize) {
+    (
+        text.graphemes(true).count(),
+        text.split_whitespace().count(),
+    )
+}
+
+/// The run of non-blank lines around `pos`, or just the current line when it
+/// is blank.
+pub(super) fn paragraph_range(text: &str, pos: usize) -> Range<usize> {
+    let mut start = line_start(text, pos);
+    let mut end = line_end(text, pos);
+    if text[start..end].trim().is_empty() {
+        return start..end;
+    }
+    while start > 0 {
+        let prev_start = line_start(text, start - 1);
+        if text[prev_start..start - 1].trim().is_empty() {
+            break;
+        }
+        start = prev_start;
+    }
+    while end < text.len() {
+        let next_end = line_end(text, end + 1);
+        if text[end + 1..next_end].trim().is_empty() {
+            break;
+        }
+        end = next_end;
+    }
+    start..end
+}
+
+/// Byte offsets of the first line of every blank-line-delimited paragraph.
+fn paragraph_starts(text: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut previous_blank = true;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        if !blank && previous_blank {
+            starts.push(offset);
+        }
+        previous_blank = blank;
+        offset += line.len();
+    }
+    starts
+}
+
+/// Start of the next paragraph after `pos`, or the end of the text.
+pub(super) fn next_paragraph_start(text: &str, pos: usize) -> usize {
+    paragraph_starts(text)
+        .into_iter()
+        .find(|start| *start > pos)
+        .unwrap_or(text.len())
+}
+
+/// Start of the paragraph before `pos` (or of the one containing it when
+/// `pos` is past its first character), or the start of the text.
+pub(super) fn previous_paragraph_start(text: &str, pos: usize) -> usize {
+    paragraph_starts(text)
+        .into_iter()
+        .rev()
+        .find(|start| *start < pos)
+        .unwrap_or(0)
+}
+
+/// Wrap `code` in a fenced code block tagged with `language`, expanding
+/// leading tabs to `tab_width` spaces. The result ends with a newline.
+pub(super) fn code_fence(code: &str, language: &str, tab_width: usize) -> String {
+    let mut out = format!("```{language}\n");
+    for line in code.trim_end_matches(['\n', '\r']).lines() {
+        let tabs = line.len() - line.trim_start_matches('\t').len();
+        out.push_str(&" ".repeat(tabs * tab_width));
+        out.push_str(&line[tabs..]);
+        out.push('\n');
+    }
+    out.push_str("```\n");
+    out
+}
+
+/// Replace every character that has an entry in `table`. Returns the new
+/// block and the number of characters replaced.
+pub(super) fn replace_chars(block: &str, table: &[(char, String)]) -> (String, usize) {
+    let mut count = 0;
+    let mut out = String::with_capacity(block.len());
+    for ch in block.chars() {
+        match table.iter().find(|(from, _)| *from == ch) {
+            Some((_, to)) => {
+                out.push_str(to);
+                count += 1;
+            }
+            None => out.push(ch),
+        }
+    }
+    (out, count)
+}
+
+/// Pick the separator used by most `key<sep>value` lines in `block`, looking
+/// at the first `=` or `:` on each line. Ties favour `=`.
+pub(super) fn detect_separator(block: &str) -> Option<char> {
+    let mut equals = 0usize;
+    let mut colons = 0usize;
+    for line in block.lines() {
+        match line.find(['=', ':']).map(|i| &line[i..i + 1]) {
+            Some("=") => equals += 1,
+            Some(_) => colons += 1,
+            None => {}
+        }
+    }
+    match (equals, colons) {
+        (0, 0) => None,
+        (equals, colons) if colons > equals => Some(':'),
+        _ => Some('='),
+    }
+}
+
+/// Pad the keys of `key<sep>value` lines so their separators share a column,
+/// normalising to one space on each side of the separator. Lines without the
+/// separator are left untouched. Returns the rewritten block and the number of
+/// lines that changed.
+pub(super) fn align_on_separator(block: &str, separator: char) -> (String, usize) {
+    let split = |line: &str| {
+        line.find(separator).map(|i| {
+            (
+                line[..i].trim_end().to_string(),
+                line[i + separator.len_utf8()..].trim_start().to_string(),
+            )
+        })
+    };
+    let key_width = block
+        .split('\n')
+        .filter_map(|line| split(line).map(|(key, _)| key.width()))
+        .max()
+        .unwrap_or(0);
+
+    let mut changed = 0usize;
+    let lines: Vec<String> = block
+        .split('\n')
+        .map(|line| {
+            let Some((key, value)) = split(line) else {
+                return line.to_string();
+            };
+            let mut aligned = key.clone();
+            aligned.push_str(&" ".repeat(key_width - key.width() + 1));
+            aligned.push(separator);
+            if !value.is_empty() {
+                aligned.push(' ');
+                aligned.push_str(&value);
+            }
+            if aligned != line {
+                changed += 1;
+            }
+            aligned
+        })
+        .collect();
+    (lines.join("\n"), changed)
+}
+
+/// Collapse runs of blank lines in `block` to a single blank line, and drop
+/// blank lines at either end when `trim_edges` is set. A trailing newline is
+/// preserved. Returns the rewritten block and the number of lines removed.
+pub(super) fn squeeze_blank_lines(block: &str, trim_edges: bool) -> (String, usize) {
+    let (body, terminator) = match block.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (block, ""),
+    };
+    let is_blank = |line: &str| line.trim().is_empty();
+    let original: Vec<&str> = body.split('\n').collect();
+    let mut lines: Vec<&str> = Vec::with_capacity(original.len());
+    for line in &original {
+        if is_blank(line) && lines.last().is_some_and(|prev| is_blank(prev)) {
+            continue;
+        }
+        lines.push(line);
+    }
+    if trim_edges {
+        while lines.last().is_some_and(|line| is_blank(line)) {
+            lines.pop();
+        }
+        let leading = lines.iter().take_while(|line| is_blank(line)).count();
+        lines.drain(..leading);
+    }
+    let removed = original.len() - lines.len();
+    if lines.is_empty() {
+        return (String::new(), removed);
+    }
+    (format!("{}{terminator}", lines.join("\n")), removed)
+}
+
+/// Whether `text` opens with a `---` (YAML) or `+++` (TOML) front-matter
+/// block that is closed by a matching fence line.
+pub(super) fn has_front_matter(text: &str) -> bool {
+    let mut lines = text.lines();
+    let Some(fence) = lines.next().map(str::trim_end) else {
+        return false;
+    };
+    if fence != "---" && fence != "+++" {
+        return false;
+    }
+    lines.any(|line| line.trim_end() == fence)
+}
+
+/// Byte offset just past the first `key:` (or `key =`) line in `template`
+/// whose value is empty, where a user would start typing.
+pub(super) fn first_empty_field(template: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in template.split_inclusive('\n') {
+        let content = line.trim_end();
+        let key = content
+            .strip_suffix(':')
+            .or_else(|| content.strip_suffix('='))
+            .map(str::trim);
+        if key.is_some_and(|key| !key.is_empty() && !key.starts_with('#')) {
+            return Some(offset + line.trim_end_matches(['\r', '\n']).len());
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// The documentation comments of `text` with `token` stripped: the block of
+/// comment lines at the top of the file, or every comment line when the
+/// file does not start with one.
+pub(super) fn comment_docs(text: &str, token: &str) -> Vec<String> {
+    let strip = |line: &str| {
+        let rest = line.trim_start().strip_prefix(token)?;
+        Some(
+            rest.strip_prefix(' ')
+                .unwrap_or(rest)
+                .trim_end()
+                .to_string(),
+        )
+    };
+    let leading: Vec<String> = text
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .map_while(strip)
+        .collect();
+    if !leading.is_empty() {
+        return leading;
+    }
+    text.lines().filter_map(strip).collect()
+}
+
+/// Comment out every non-blank line of `block` with `token`, or uncomment
+/// them when they all already start with it. Tokens line up at the smallest
+/// indentation so nested blocks stay readable.
+pub(super) fn toggle_comment(block: &str, token: &str) -> String {
+    let commented = block
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .all(|line| line.trim_start().starts_with(token));
+    if commented {
+        uncomment_lines(block, token)
+    } else {
+        comment_lines(block, token)
+    }
+}
+
+/// Strip one `token` (and the space after it) from each commented line.
+pub(super) fn uncomment_lines(block: &str, token: &str) -> String {
+    block
+        .split('\n')
+        .map(|line| {
+            let indent = line.len() - line.trim_start().len();
+            match line[indent..].strip_prefix(token) {
+                Some(rest) => {
+                    let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                    format!("{}{rest}", &line[..indent])
+                }
+                None => (*line).to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Prefix every non-blank line with `token`, aligned to the block's
+/// smallest indentation.
+pub(super) fn comment_lines(block: &str, token: &str) -> String {
+    let lines: Vec<&str> = block.split('\n').collect();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| {
+            if line.trim().is_empty() {
+                (*line).to_string()
+            } else {
+                format!("{}{token} {}", &line[..indent], &line[indent..])
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `text` without its `token` comments: whole comment lines are dropped and
+/// trailing comments are cut along with the whitespace before them. A token
+/// only starts a trailing comment after whitespace and outside quoted
+/// strings, so `"a # b"` and `url = http://x` survive.
+pub(super) fn strip_comments(text: &str, token: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        if content.trim_start().starts_with(token) {
+            continue;
+        }
+        match trailing_comment_start(content, token) {
+            Some(start) => {
+                out.push_str(content[..start].trim_end());
+                out.push_str(&line[content.len()..]);
+            }
+            None => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// The item of a `-`, `*` or `+` list line, without its marker.
+fn list_item(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| trimmed.strip_prefix(marker))
+        .map(str::trim)
+}
+
+/// The run of list lines around `pos`, when its line is a list item.
+pub(super) fn list_range(text: &str, pos: usize) -> Option<Range<usize>> {
+    let is_item = |start: usize| list_item(&text[start..line_end(text, start)]).is_some();
+    let mut start = line_start(text, pos);
+    if !is_item(start) {
+        return None;
+    }
+    while start > 0 && is_item(line_start(text, start - 1)) {
+        start = line_start(text, start - 1);
+    }
+    let mut end = line_end(text, pos);
+    while end < text.len() && is_item(end + 1) {
+        end = line_end(text, end + 1);
+    }
+    Some(start..end)
+}
+
+/// Turn a list into one comma-separated line, or a comma-separated line
+/// into a `-` list, keeping the first line's indentation. Items are trimmed
+/// and empty ones dropped. Returns `None` when `block` is neither.
+pub(super) fn toggle_list_csv(block: &str) -> Option<String> {
+    let lines: Vec<&str> = block
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let first = lines.first()?;
+    let indent = &first[..first.len() - first.trim_start().len()];
+    if let Some(items) = lines
+        .iter()
+        .map(|line| list_item(line))
+        .collect::<Option<Vec<&str>>>()
+    {
+        let items: Vec<&str> = items.into_iter().filter(|item| !item.is_empty()).collect();
+        return Some(format!("{indent}{}", items.join(", ")));
+    }
+    if lines.len() != 1 || !first.contains(',') {
+        return None;
+    }
+    let items: Vec<String> = first
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| format!("{indent}- {item}"))
+        .collect();
+    Some(items.join("\n"))
+}
+
+/// `text` without spaces or tabs at the end of any line and with exactly one
+/// final newline. An empty buffer stays empty.
+pub(super) fn normalize_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        out.push_str(content.trim_end_matches([' ', '\t']));
+        out.push_str(&line[content.len()..]);
+    }
+    let body = out.trim_end_matches(['\n', '\r']);
+    if body.is_empty() {
+        return String::new();
+    }
+    let newline = if out[body.len()..].starts_with("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    format!("{body}{newline}")
+}
+
+/// `text` cut down to at most `max` lines, dropping from the end `drop`
+/// names. Returns the kept text and how many lines were dropped.
+pub(super) fn cap_lines(text: &str, max: usize, drop: LineCapDrop) -> (String, usize) {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let excess = lines.len().saturating_sub(max);
+    let kept = match drop {
+        LineCapDrop::Oldest => &lines[excess..],
+        LineCapDrop::Newest => &lines[..lines.len() - excess],
+    };
+    (kept.concat(), excess)
+}
+
+/// Byte offset of the first `token` on `line` that starts a comment.
+fn trailing_comment_start(line: &str, token: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut prev: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => {
+                // Quotes open a string only where a value can start, so an
+                // apostrophe inside a word does not hide a comment.
+                let at_value_start =
+                    prev.is_none_or(|p| p.is_whitespace() || matches!(p, '=' | ':' | '[' | ','));
+                if (c == '"' || c == '\'') && at_value_start {
+                    quote = Some(c);
+                } else if prev.is_some_and(char::is_whitespace) && line[i..].starts_with(token) {
+                    return Some(i);
+                }
+            }
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+/// The line at `pos` swapped with the one above it: the range covering both
+/// lines (without the final newline) and its replacement. `None` on the
+/// first line.
+pub(super) fn transpose_lines(text: &str, pos: usize) -> Option<(Range<usize>, String)> {
+    let start = line_start(text, pos);
+    if start == 0 {
+        return None;
+    }
+    let prev_start = line_start(text, start - 1);
+    let end = line_end(text, pos);
+    let prev = &text[prev_start..start - 1];
+    let (prev, newline) = match prev.strip_suffix('\r') {
+        Some(prev) => (prev, "\r\n"),
+        None => (prev, "\n"),
+    };
+    let current = &text[start..end];
+    let current = current.strip_suffix('\r').unwrap_or(current);
+    let end = start + current.len();
+    Some((prev_start..end, format!("{current}{newline}{prev}")))
+}
+
+/// Where `pos` in `before` ends up in `after`, for a position the edit did
+/// not move itself. Positions inside the changed span go to its start.
+pub(super) fn shift_position(before: &str, after: &str, pos: usize) -> usize {
+    let mut prefix = before
+        .bytes()
+        .zip(after.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !before.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let suffix = before[prefix..]
+        .bytes()
+        .rev()
+        .zip(after[prefix..].bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut shifted = if pos <= prefix {
+        pos
+    } else if pos >= before.len() - suffix {
+        pos + after.len() - before.len()
+    } else {
+        prefix
+    };
+    shifted = shifted.min(after.len());
+    while !after.is_char_boundary(shifted) {
+        shifted -= 1;
+    }
+    shifted
+}
+
+/// The leading whitespace of every line that indents with both tabs and
+/// spaces.
+pub(super) fn mixed_indentation(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let leading = &line[..indent];
+        if leading.contains(' ') && leading.contains('\t') {
+            ranges.push(start..start + indent);
+        }
+        start += line.len();
+    }
+    ranges
+}
+
+/// `indent` (spaces and tabs) rewritten in `style`, keeping its width with
+/// tab stops every `style.width()` columns. Leftover columns under `Tabs`
+/// stay spaces.
+pub(super) fn normalize_indent(indent: &str, style: IndentStyle) -> String {
+    let tab_width = style.width();
+    let columns = indent.chars().fold(0, |col, c| match c {
+        '\t' => (col / tab_width + 1) * tab_width,
+        _ => col + 1,
+    });
+    match style {
+        IndentStyle::Spaces(_) => " ".repeat(columns),
+        IndentStyle::Tabs(_) => {
+            format!(
+                "{}{}",
+                "\t".repeat(columns / tab_width),
+                " ".repeat(columns % tab_width)
+            )
+        }
+    }
+}
+
+/// Start of every match of `query` in `text`, ignoring ASCII case.
+/// Overlapping matches are skipped.
+pub(super) fn find_all(text: &str, query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    // ASCII lowercasing keeps byte offsets, so they index `text` directly.
+    let haystack = text.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
+    haystack.match_indices(&needle).map(|(i, _)| i).collect()
+}
+
+/// A fenced code block around `pos`: its info string (language) and the
+/// byte range of the lines between the fences. `pos` on a fence line does
+/// not count as inside.
+pub(super) fn fence_at(text: &str, pos: usize) -> Option<(String, Range<usize>)> {
+    let mut open: Option<(&str, String, usize)> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let end = offset + line.len();
+        let trimmed = line.trim();
+        match &open {
+            None => {
+                if let Some(info) = trimmed.strip_prefix("```") {
+                    open = Some(("```", info.trim().to_string(), end));
+                } else if let Some(info) = trimmed.strip_prefix("~~~") {
+                    open = Some(("~~~", info.trim().to_string(), end));
+                }
+            }
+            Some((marker, info, body_start)) if trimmed.starts_with(marker) => {
+                if (*body_start..offset).contains(&pos) {
+                    return Some((info.clone(), *body_start..offset));
+                }
+                open = None;
+            }
+            Some(_) => {}
+        }
+        offset = end;
+    }
+    None
+}
+
+/// The first obviously broken markdown construct in `text`, as a 1-based
+/// line number and a description: a code fence that is never closed, or a
+/// heading without a space after its `#`s. Front matter and code blocks are
+/// not checked for headings.
+pub(super) fn markdown_problem(text: &str) -> Option<(usize, &'static str)> {
+    let mut lines = text.lines().enumerate();
+    if has_front_matter(text)
+        && let Some((_, fence)) = lines.next()
+    {
+        let fence = fence.trim_end();
+        lines.find(|(_, line)| line.trim_end() == fence);
+    }
+    let mut open_fence: Option<(&str, usize)> = None;
+    for (index, line) in lines {
+        let trimmed = line.trim();
+        match open_fence {
+            Some((marker, _)) => {
+                if trimmed.starts_with(marker) {
+                    open_fence = None;
+                }
+            }
+            None => {
+                if let Some(marker) = ["```", "~~~"]
+                    .into_iter()
+                    .find(|marker| trimmed.starts_with(marker))
+                {
+                    open_fence = Some((marker, index));
+                    continue;
+                }
+                let hashes = line.len() - line.trim_start_matches('#').len();
+                let after = line[hashes..].chars().next();
+                if (1..=6).contains(&hashes) && after.is_some_and(|ch| !ch.is_whitespace()) {
+                    return Some((index + 1, "heading needs a space after #"));
+                }
+            }
+        }
+    }
+    open_fence.map(|(_, index)| (index + 1, "code fence is never closed"))
+}
+
+/// Break `line` at spaces so each piece fits in `width` columns, preferring
+/// breaks after commas. Continuation lines get the line's indentation plus
+/// `continuation`. Words longer than `width` are never split.
+pub(super) fn hard_wrap(line: &str, width: usize, continuation: &str) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let continuation = format!("{indent}{continuation}");
+    let mut pieces = Vec::new();
+    let mut prefix = "";
+    let mut rest = line;
+    let mut search_from = indent.len();
+    loop {
+        if prefix.width() + rest.width() <= width {
+            pieces.push(format!("{prefix}{rest}"));
+            break;
+        }
+        let breaks: Vec<usize> = rest
+            .char_indices()
+            .skip_while(|(i, _)| *i < search_from)
+            .filter(|(_, ch)| *ch == ' ')
+            .map(|(i, _)| i)
+            .collect();
+        let fitting: Vec<usize> = breaks
+            .iter()
+            .copied()
+            .take_while(|i| prefix.width() + rest[..*i].width() <= width)
+            .collect();
+        let Some(at) = fitting
+            .iter()
+            .rev()
+            .find(|i| rest[..**i].ends_with(','))
+            .or(fitting.last())
+            .or(breaks.first())
+            .copied()
+        else {
+            pieces.push(format!("{prefix}{rest}"));
+            break;
+        };
+        pieces.push(format!("{prefix}{}", rest[..at].trim_end()));
+        rest = rest[at..].trim_start();
+        prefix = &continuation;
+        search_from = 0;
+    }
+    pieces.join("\n")
+}
+
+/// The line containing `pos` together with the more deeply indented lines
+/// that follow it, as left by [`hard_wrap`].
+pub(super) fn continuation_range(text: &str, pos: usize) -> Range<usize> {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let start = line_start(text, pos);
+    let mut end = line_end(text, pos);
+    let base = indent(&text[start..end]);
+    while end < text.len() {
+        let next_end = line_end(text, end + 1);
+        let next = &text[end + 1..next_end];
+        if next.trim().is_empty() || indent(next) <= base {
+            break;
+        }
+        end = next_end;
+    }
+    start..end
+}
+
+/// Join a line and its continuation lines into one, with single spaces.
+pub(super) fn join_lines(block: &str) -> String {
+    let mut lines = block.split('\n');
+    let mut joined = lines.next().unwrap_or_default().trim_end().to_string();
+    for line in lines {
+        joined.push(' ');
+        joined.push_str(line.trim());
+    }
+    joined
+}
+
+/// The word (letters, digits, `_`) under or just before `pos`.
+pub(super) fn word_at(text: &str, pos: usize) -> Option<Range<usize>> {
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let start = text[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| is_word(*ch))
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let end = text[pos..]
+        .char_indices()
+        .find(|(_, ch)| !is_word(*ch))
+        .map_or(text.len(), |(i, _)| pos + i);
+    (start < end).then_some(start..end)
+}
+
+/// Whitespace, word characters (letters, digits, `_`) and punctuation;
+/// a word boundary is wherever the class changes.
+fn char_class(ch: char) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if ch.is_alphanumeric() || ch == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+/// Start of the word or punctuation run before `pos`, skipping whitespace
+/// but never past the start of the line. At the start of a line it is the
+/// end of the line above.
+pub(super) fn previous_word_start(text: &str, pos: usize) -> usize {
+    let start = line_start(text, pos);
+    if pos == start {
+        return pos.saturating_sub(1);
+    }
+    let mut chars = text[start..pos].char_indices().rev().peekable();
+    while chars.next_if(|(_, ch)| ch.is_whitespace()).is_some() {}
+    let Some(&(_, first)) = chars.peek() else {
+        return start;
+    };
+    let class = char_class(first);
+    let mut boundary = pos;
+    while let Some((i, _)) = chars.next_if(|(_, ch)| char_class(*ch) == class) {
+        boundary = start + i;
+    }
+    boundary
+}
+
+/// End of the word or punctuation run after `pos`, skipping whitespace but
+/// never past the end of the line. At the end of a line it is the start of
+/// the line below.
+pub(super) fn next_word_end(text: &str, pos: usize) -> usize {
+    let end = line_end(text, pos);
+    if pos == end {
+        return (pos + 1).min(text.len());
+    }
+    let mut chars = text[pos..end].char_indices().peekable();
+    while chars.next_if(|(_, ch)| ch.is_whitespace()).is_some() {}
+    let Some(&(_, first)) = chars.peek() else {
+        return end;
+    };
+    let class = char_class(first);
+    while chars.next_if(|(_, ch)| char_class(*ch) == class).is_some() {}
+    chars.peek().map_or(end, |(i, _)| pos + i)
+}
+
+/// Opening and closing text for a surround request. Brackets pair up, so
+/// `(` or `)` give `(`…`)`; two words separated by a space are used as
+/// given, as in `<b> </b>`; anything else mirrors, as in `**`.
+pub(super) fn surround_pair(input: &str) -> Option<(String, String)> {
+    const BRACKETS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    if let Some((open, close)) = input.split_once(' ') {
+        return Some((open.to_string(), close.trim().to_string()));
+    }
+    let mut chars = input.chars();
+    if let (Some(first), rest) = (chars.next(), chars.as_str())
+        && let Some((open, close)) = BRACKETS
+            .into_iter()
+            .find(|(open, close)| first == *open || first == *close)
+        && (rest.is_empty() || rest == close.to_string())
+    {
+        return Some((open.to_string(), close.to_string()));
+    }
+    Some((input.to_string(), input.to_string()))
+}
+
+/// The integer under or after `pos` on its line, including a leading `-`
+/// that is not part of a word like `a-1`.
+pub(super) fn number_at(text: &str, pos: usize) -> Option<Range<usize>> {
+    let start = line_start(text, pos);
+    let end = line_end(text, pos);
+    let bytes = text.as_bytes();
+    let mut i = start;
+    while i < end {
+        if !bytes[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let mut j = i;
+        while j < end && bytes[j].is_ascii_digit() {
+            j += 1;
+        }
+        if j > pos {
+            let negative = i > start
+                && bytes[i - 1] == b'-'
+                && !(i - 1 > start && bytes[i - 2].is_ascii_alphanumeric());
+            return Some(if negative { i - 1 } else { i }..j);
+        }
+        i = j;
+    }
+    None
+}
+
+/// `literal` plus `delta`. Zero-padded numbers keep their width, so `007`
+/// becomes `008` and `-01` becomes `00`.
+pub(super) fn step_number(literal: &str, delta: i64) -> Option<String> {
+    let value: i128 = literal.parse().ok()?;
+    let next = value.saturating_add(i128::from(delta));
+    let digits = literal.trim_start_matches('-');
+    let width = if digits.len() > 1 && digits.starts_with('0') {
+        digits.len()
+    } else {
+        0
+    };
+    let sign = if next < 0 { "-" } else { "" };
+    Some(format!("{sign}{:0width$}", next.unsigned_abs()))
+}
+
+/// What typing a bracket or quote does with auto-pairing on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AutoPair {
+    /// Insert the character and this closer, with the cursor between them.
+    Insert(char),
+    /// The character is already next; move past it.
+    StepOver,
+}
+
+/// How auto-pairing handles typing `ch` at `cursor`, or `None` to type it
+/// as is. Pairs only open before whitespace or the end of the text, and a
+/// quote or backtick after a letter or the same mark stays single, so
+/// apostrophes and code fences type normally.
+pub(super) fn auto_pair(text: &str, cursor: usize, ch: char) -> Option<AutoPair> {
+    let next = text[cursor..].chars().next();
+    if next == Some(ch) && matches!(ch, ')' | ']' | '}' | '`' | '"') {
+        return Some(AutoPair::StepOver);
+    }
+    let close = match ch {
+        '(' => ')',
+        '[' => ']',
+        '{' => '}',
+        '`' | '"' => ch,
+        _ => return None,
+    };
+    if next.is_some_and(|next| !next.is_whitespace()) {
+        return None;
+    }
+    let prev = text[..cursor].chars().next_back();
+    if close == ch && prev.is_some_and(|prev| prev == ch || prev.is_alphanumeric()) {
+        return None;
+    }
+    Some(AutoPair::Insert(close))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn number_at_finds_number_under_or_after_cursor() {
+        let text = "width: 80\nx-1 y -5";
+        assert_eq!(&text[number_at(text, 0).unwrap()], "80");
+        assert_eq!(&text[number_at(text, 8).unwrap()], "80");
+        assert_eq!(number_at(text, 9), None);
+        assert_eq!(&text[number_at(text, 10).unwrap()], "1");
+        assert_eq!(&text[number_at(text, 13).unwrap()], "-5");
+
+        assert_eq!(step_number("9", 1).as_deref(), Some("10"));
+        assert_eq!(step_number("0", -1).as_deref(), Some("-1"));
+        assert_eq!(step_number("007", 1).as_deref(), Some("008"));
+        assert_eq!(step_number("-5", 10).as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn comment_docs_prefers_the_leading_block() {
+        let text = "\n# Options\n#\n#   width: columns\nwidth: 80 # inline\n# later\n";
+        assert_eq!(
+            comment_docs(text, "#"),
+            vec!["Options", "", "  width: columns"]
+        );
+        assert_eq!(
+            comment_docs("a = 1\n; one\nb\n  ; two", ";"),
+            vec!["one", "two"]
+        );
+    }
+
+    #[test]
+    fn toggle_comment_adds_at_common_indent_and_removes() {
+        let block = "a = 1\n\n  b = 2";
+        let commented = toggle_comment(block, "#");
+        assert_eq!(commented, "# a = 1\n\n#   b = 2");
+        assert_eq!(toggle_comment(&commented, "#"), block);
+        assert_eq!(toggle_comment("  //x\n  y", "//"), "  // //x\n  // y");
+    }
+
+    #[test]
+    fn strip_comments_keeps_tokens_inside_strings() {
+        let text = "# header\nname = \"a # b\"  # trailing\ncolor = '#fff' # hex\nit's = 1 # note\nurl = \"x\" # \"quoted\"\n\nplain # c\r\n";
+        assert_eq!(
+            strip_comments(text, "#"),
+            "name = \"a # b\"\ncolor = '#fff'\nit's = 1\nurl = \"x\"\n\nplain\r\n"
+        );
+        assert_eq!(
+            strip_comments("esc = \"a \\\" // b\" // c\nurl = http://x\n", "//"),
+            "esc = \"a \\\" // b\"\nurl = http://x\n"
+        );
+    }
+
+    #[test]
+    fn transpose_lines_swaps_with_the_line_above() {
+        let text = "a = 1\nb = 2\nc";
+        assert_eq!(transpose_lines(text, 0), None);
+        assert_eq!(
+            transpose_lines(text, text.find('b').unwrap()),
+            Some((0..11, "b = 2\na = 1".to_string()))
+        );
+        let crlf = "one\r\ntwo\r\n";
+        assert_eq!(
+            transpose_lines(crlf, 6),
+            Some((0..8, "two\r\none".to_string()))
+        );
+    }
+
+    #[test]
+    fn shift_position_follows_edits_elsewhere() {
+        let before = "a = 1\nb = 2\n";
+        assert_eq!(shift_position(before, "x\na = 1\nb = 2\n", 6), 8);
+        assert_eq!(shift_position(before, "a = 1\nb = 2\nc\n", 6), 6);
+        assert_eq!(shift_position(before, "b = 2\n", 8), 2);
+        assert_eq!(shift_position(before, "b = 2\n", 3), 0);
+    }
+
+    #[test]
+    fn mixed_indentation_is_found_and_normalized() {
+        let text = "a\n \tb\n\t\tc\n  \td\n";
+        assert_eq!(mixed_indentation(text), vec![2..4, 10..13]);
+        assert_eq!(normalize_indent(" \t", IndentStyle::Spaces(4)), "    ");
+        assert_eq!(
+            normalize_indent("\t  \t ", IndentStyle::Spaces(2)),
+            "       "
+        );
+        assert_eq!(normalize_indent("  \t  ", IndentStyle::Tabs(4)), "\t  ");
+        assert_eq!(normalize_indent("    \t", IndentStyle::Tabs(4)), "\t\t");
+    }
+
+    #[test]
+    fn word_boundaries_split_on_whitespace_and_punctuation() {
+        let text = "key.sub = \"a b\"\nnext";
+        let end = "key.sub = \"a b\"".len();
+        assert_eq!(previous_word_start(text, end), end - 1);
+        assert_eq!(previous_word_start(text, end - 1), end - 2);
+        assert_eq!(previous_word_start(text, 7), 4);
+        assert_eq!(previous_word_start(text, 4), 3);
+        assert_eq!(previous_word_start(text, 3), 0);
+        assert_eq!(previous_word_start(text, end + 1), end);
+
+        assert_eq!(next_word_end(text, 0), 3);
+        assert_eq!(next_word_end(text, 3), 4);
+        assert_eq!(next_word_end(text, 7), 9);
+        assert_eq!(next_word_end(text, end), end + 1);
+        assert_eq!(next_word_end(text, text.len()), text.len());
+    }
+
+    #[test]
+    fn line_col_counts_graphemes_from_one() {
+        let text = "ab\ncafe\u{301} x";
+        assert_eq!(line_col(text, 0), (1, 1));
+        assert_eq!(line_col(text, 2), (1, 3));
+        assert_eq!(line_col(text, 3), (2, 1));
+        assert_eq!(line_col(text, text.len()), (2, 7));
+    }
+
+    #[test]
+    fn line_offset_clamps_to_the_last_line() {
+        let text = "a\nbb\nccc";
+        assert_eq!(line_offset(text, 1), 0);
+        assert_eq!(line_offset(text, 2), 2);
+        assert_eq!(line_offset(text, 3), 5);
+        assert_eq!(line_offset(text, 99), 5);
+        assert_eq!(line_offset("a\n", 99), 2);
+    }
+
+    #[test]
+    fn line_col_offset_inverts_line_col_and_clamps() {
+        let text = "a\nbéd\ne";
+        for pos in [0, 2, 3, 5, 6, 8] {
+            let (line, col) = line_col(text, pos);
+            assert_eq!(line_col_offset(text, line, col), pos);
+        }
+        assert_eq!(line_col_offset(text, 2, 99), 6);
+        assert_eq!(line_col_offset(text, 99, 99), text.len());
+        assert_eq!(line_col_offset(text, 0, 0), 0);
+    }
+
+    #[test]
+    fn char_and_word_count_counts_graphemes_and_unicode_whitespace() {
+        assert_eq!(char_and_word_count(""), (0, 0));
+        assert_eq!(char_and_word_count("cafe\u{301} 👍🏽\n  a\u{3000}b"), (12, 4));
+    }
+
+    #[test]
+    fn toggle_list_csv_converts_both_ways() {
+        assert_eq!(
+            toggle_list_csv("  - a \n  * b\n\n  +  c"),
+            Some("  a, b, c".to_string())
+        );
+        assert_eq!(
+            toggle_list_csv("  a ,b,, c "),
+            Some("  - a\n  - b\n  - c".to_string())
+        );
+        assert_eq!(toggle_list_csv("- a\nplain"), None);
+        assert_eq!(toggle_list_csv("no commas"), None);
+
+        let text = "tags:\n- a\n- b\n\n- c";
+        assert_eq!(list_range(text, 8), Some(6..13));
+        assert_eq!(list_range(text, 2), None);
+    }
+
+    #[test]
+    fn normalize_whitespace_trims_lines_and_ends_with_one_newline() {
+        assert_eq!(normalize_whitespace("a  \n\tb\t\n\n\n"), "a\n\tb\n");
+        assert_eq!(normalize_whitespace("a \r\nb"), "a\r\nb\n");
+        assert_eq!(normalize_whitespace("a\r\n\r\n"), "a\r\n");
+        assert_eq!(normalize_whitespace("  \n"), "");
+    }
+
+    #[test]
+    fn cap_lines_drops_from_the_chosen_end() {
+        let text = "1\n2\n3\n4";
+        assert_eq!(
+            cap_lines(text, 2, LineCapDrop::Oldest),
+            ("3\n4".to_string(), 2)
+        );
+        assert_eq!(
+            cap_lines(text, 3, LineCapDrop::Newest),
+            ("1\n2\n3\n".to_string(), 1)
+        );
+        assert_eq!(
+            cap_lines(text, 9, LineCapDrop::Oldest),
+            (text.to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn find_all_ignores_ascii_case() {
+        assert_eq!(
+            find_all("Model = x\nmodel_é = MODEL", "model"),
+            vec![0, 10, 21]
+        );
+        assert!(find_all("abc", "").is_empty());
+    }
+
+    #[test]
+    fn markdown_problem_finds_unclosed_fences_and_cramped_headings() {
+        assert_eq!(markdown_problem("# Title\n\n```sh\nls\n```\n"), None);
+        assert_eq!(
+            markdown_problem("# Title\n##Options\n"),
+            Some((2, "heading needs a space after #"))
+        );
+        assert_eq!(
+            markdown_problem("a\n```\n#not-a-heading\n"),
+            Some((2, "code fence is never closed"))
+        );
+        assert_eq!(markdown_problem("---\n#yaml: comment\n---\nbody\n"), None);
+        assert_eq!(markdown_problem("####### seven\n"), None);
+    }
+
+    #[test]
+    fn fence_at_finds_the_enclosing_block() {
+        let text = "intro\n```json\n{}\n```\n~~~\nx\n~~~\n";
+        let json = text.find("{}").unwrap();
+        assert_eq!(
+            fence_at(text, json),
+            Some(("json".to_string(), json..json + 3))
+        );
+        assert_eq!(fence_at(text, 0), None);
+        assert_eq!(fence_at(text, text.find("```").unwrap()), None);
+        let x = text.find('x').unwrap();
+        assert_eq!(fence_at(text, x), Some((String::new(), x..x + 2)));
+    }
+
+    #[test]
+    fn hard_wrap_prefers_commas_and_join_undoes_it() {
+        let line = "  plugins: alpha, beta gamma, delta";
+        let wrapped = hard_wrap(line, 20, "  ");
+        assert_eq!(wrapped, "  plugins: alpha,\n    beta gamma,\n    delta");
+        assert!(wrapped.lines().all(|piece| piece.width() <= 20));
+        assert_eq!(join_lines(&wrapped), line);
+        assert_eq!(
+            hard_wrap("averyveryverylongword x", 5, "  "),
+            "averyveryverylongword\n  x"
+        );
+
+        let text = "a: 1\n  b\n  c\nd";
+        assert_eq!(&text[continuation_range(text, 0)], "a: 1\n  b\n  c");
+        assert_eq!(&text[continuation_range(text, text.len())], "d");
+    }
+
+    #[test]
+    fn surround_pairs_brackets_and_mirrors_the_rest() {
+        let pair = |open: &str, close: &str| Some((open.to_string(), close.to_string()));
+        assert_eq!(surround_pair("("), pair("(", ")"));
+        assert_eq!(surround_pair("]"), pair("[", "]"));
+        assert_eq!(surround_pair("{}"), pair("{", "}"));
+        assert_eq!(surround_pair("**"), pair("**", "**"));
+        assert_eq!(surround_pair("<b> </b>"), pair("<b>", "</b>"));
+        assert_eq!(surround_pair(" "), None);
+
+        let text = "say hello_world!";
+        assert_eq!(&text[word_at(text, 6).unwrap()], "hello_world");
+        assert_eq!(&text[word_at(text, 15).unwrap()], "hello_world");
+        assert_eq!(word_at(text, 16), None);
+    }
+
+    #[test]
+    fn front_matter_detection_and_first_field() {
+        assert!(has_front_matter("---\ntitle: x\n---\nbody"));
+        assert!(has_front_matter("+++\ntitle = 1\n+++\n"));
+        assert!(!has_front_matter("---\nunterminated"));
+        assert!(!has_front_matter("# heading\n---\n"));
+
+        let template = "---\ntags: []\ntitle: \n---\n";
+        assert_eq!(
+            first_empty_field(template),
+            Some("---\ntags: []\ntitle: ".len())
+        );
+        assert_eq!(first_empty_field("---\n---\n"), None);
+    }
+
+    #[test]
+    fn paragraph_range_stops_at_blank_lines() {
+        let text = "a\n\nb\nc\n\nd";
+        let b = text.find('b').unwrap_or_default();
+        assert_eq!(&text[paragraph_range(text, b)], "b\nc");
+        assert_eq!(paragraph_range(text, 2), 2..2);
+        assert_eq!(&text[paragraph_range(text, 0)], "a");
+    }
+
+    #[test]
+    fn aligns_equals_and_leaves_other_lines() {
+        let block = "name = codex\n# comment\nlong_key=1\nx =";
+        let (aligned, changed) = align_on_separator(block, '=');
+        assert_eq!(
+            aligned,
+            "name     = codex\n# comment\nlong_key = 1\nx        ="
+        );
+        assert_eq!(changed, 3);
+    }
+
+    #[test]
+    fn aligns_colons_and_detects_separator() {
+        let (aligned, changed) = align_on_separator("a: 1\n  bbb:   2", ':');
+        assert_eq!(aligned, "a     : 1\n  bbb : 2");
+        assert_eq!(changed, 2);
+        assert_eq!(detect_separator("a: 1\nb: 2\nc = 3"), Some(':'));
+        assert_eq!(detect_separator("plain text"), None);
+    }
+
+    #[test]
+    fn squeezes_blank_runs_and_optionally_trims_edges() {
+        let block = "\n\na\n\n  \n\nb\n\n";
+        assert_eq!(
+            squeeze_blank_lines(block, false),
+            ("\na\n\nb\n\n".to_string(), 3)
+        );
+        assert_eq!(
+            squeeze_blank_lines(block, true),
+            ("a\n\nb\n".to_string(), 5)
+        );
+        assert_eq!(squeeze_blank_lines("a\nb", true), ("a\nb".to_string(), 0));
+        assert_eq!(squeeze_blank_lines("\n\n", true), (String::new(), 2));
+    }
+
+    #[test]
+    fn paragraph_motion_lands_on_paragraph_starts() {
+        let text = "one\ntwo\n\n\nthree\n\nfour";
+        let three = text.find("three").unwrap();
+        let four = text.find("four").unwrap();
+        assert_eq!(next_paragraph_start(text, 0), three);
+        assert_eq!(next_paragraph_start(text, three), four);
+        assert_eq!(next_paragraph_start(text, four), text.len());
+        assert_eq!(previous_paragraph_start(text, four + 2), four);
+        assert_eq!(previous_paragraph_start(text, four), three);
+        assert_eq!(previous_paragraph_start(text, 2), 0);
+    }
+
+    #[test]
+    fn code_fence_expands_leading_tabs_only() {
+        assert_eq!(
+            code_fence("fn main() {\n\tlet a =\t1;\n}\n", "rust", 4),
+            "```rust\nfn main() {\n    let a =\t1;\n}\n```\n"
+        );
+    }
+
+    #[test]
+    fn auto_pair_opens_before_whitespace_and_steps_over_closers() {
+        assert_eq!(auto_pair("", 0, '('), Some(AutoPair::Insert(')')));
+        assert_eq!(auto_pair("a b", 1, '['), Some(AutoPair::Insert(']')));
+        assert_eq!(auto_pair("ab", 1, '{'), None);
+        assert_eq!(auto_pair("()", 1, ')'), Some(AutoPair::StepOver));
+        assert_eq!(auto_pair("``", 1, '`'), Some(AutoPair::StepOver));
+        assert_eq!(auto_pair("``", 2, '`'), None);
+        assert_eq!(auto_pair("it", 2, '"'), None);
+        assert_eq!(auto_pair("= ", 2, '"'), Some(AutoPair::Insert('"')));
+        assert_eq!(auto_pair("", 0, 'x'), None);
+    }
+
+    #[test]
+    fn replace_chars_counts_replacements() {
+        let table = vec![('“', "\"".to_string()), ('—', "--".to_string())];
+        assert_eq!(replace_chars("“a”—b", &table), ("\"a”--b".to_string(), 2));
+    }
+}
@@ -0,0 +1,33 @@
+//! Colors for the editor's gutter, status line and highlights, so hosts on
+//! terminals where the defaults are hard to read can swap them out.
+
+use ratatui::style::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EditorTheme {
+    /// Gutter bar and scroll indicator.
+    pub(crate) gutter: Color,
+    /// Info messages in the status line.
+    pub(crate) info: Color,
+    pub(crate) success: Color,
+    pub(crate) warning: Color,
+    pub(crate) error: Color,
+    /// Unsaved-changes marker, the auto-save hint and occurrence underlines.
+    pub(crate) accent: Color,
+    /// Background of the line with the cursor; `None` leaves it unmarked.
+    pub(crate) current_line: Option<Color>,
+}
+
+impl Default for EditorTheme {
+    fn default() -> Self {
+        Self {
+            gutter: Color::Cyan,
+            info: Color::Green,
+            success: Color::Green,
+            warning: Color::Magenta,
+            error: Color::Red,
+            accent: Color::Cyan,
+            current_line: None,
+        }
+    }
+}
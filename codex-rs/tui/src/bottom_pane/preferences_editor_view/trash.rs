@@ -0,0 +1,128 @@
+//! Session-scoped trash for buffer contents thrown away by destructive
+//! operations, so the last one can be recovered after the editor closes.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use tempfile::TempPath;
+
+/// File behind the session trash: created on first use with a random name
+/// and owner-only permissions, and deleted by [`remove_session_trash`].
+static SESSION_TRASH: Mutex<Option<TempPath>> = Mutex::new(None);
+
+pub(super) struct Trash {
+    /// `None` for the session trash.
+    path: Option<PathBuf>,
+}
+
+impl Trash {
+    /// The trash shared by every editor opened in this process.
+    pub(super) fn for_session() -> Self {
+        Self { path: None }
+    }
+
+    pub(super) fn at(path: PathBuf) -> Self {
+        Self { path: Some(path) }
+    }
+
+    /// Keep `contents` as the most recently discarded text.
+    pub(super) fn put(&self, contents: &str) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            return write_private(path, contents);
+        }
+        let mut session = SESSION_TRASH.lock().unwrap_or_else(PoisonError::into_inner);
+        let path = match session.take() {
+            Some(path) => path,
+            None => tempfile::Builder::new()
+                .prefix("codex-preferences-trash-")
+                .suffix(".md")
+                .tempfile()?
+                .into_temp_path(),
+        };
+        let written = write_private(&path, contents);
+        *session = Some(path);
+        written
+    }
+
+    /// The most recently discarded text, if anything has been discarded.
+    pub(super) fn last(&self) -> io::Result<Option<String>> {
+        let Some(path) = self.path.clone().or_else(session_path) else {
+            return Ok(None);
+        };
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Delete the session trash. Called when the app exits.
+pub(crate) fn remove_session_trash() {
+    let session = SESSION_TRASH
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(path) = session
+        && let Err(err) = path.close()
+    {
+        tracing::warn!("failed to remove the preferences trash: {err}");
+    }
+}
+
+fn session_path() -> Option<PathBuf> {
+    SESSION_TRASH
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_deref()
+        .map(Path::to_path_buf)
+}
+
+/// Write `contents` to `path`, creating it readable only by the user.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn last_returns_most_recent_put() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = Trash::at(dir.path().join("trash.md"));
+        assert_eq!(trash.last().unwrap(), None);
+        trash.put("first").unwrap();
+        trash.put("second").unwrap();
+        assert_eq!(trash.last().unwrap(), Some("second".to_string()));
+    }
+
+    #[test]
+    fn session_trash_is_private_and_removed_on_exit() {
+        Trash::for_session().put("secret").unwrap();
+        let path = session_path().unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        remove_session_trash();
+        assert!(!path.exists());
+    }
+}
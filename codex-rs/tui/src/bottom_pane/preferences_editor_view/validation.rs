@@ -0,0 +1,29 @@
+//! Problems reported by a host-supplied validator for the preferences buffer.
+
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::Instant;
+
+/// One problem found in the buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ValidationError {
+    /// 1-based line the problem is on.
+    pub(crate) line: usize,
+    pub(crate) message: String,
+}
+
+/// Checks the buffer and returns every problem it finds. It is shared so a
+/// slow validator can run on a worker thread.
+pub(crate) type Validator = Arc<dyn Fn(&str) -> Vec<ValidationError> + Send + Sync>;
+
+/// A background validation the editor is waiting on before it saves.
+pub(super) struct ValidationRun {
+    /// The buffer being validated. If it changes, the result is stale.
+    pub(super) text: String,
+    pub(super) since: Instant,
+    /// Started by an auto-save rather than an explicit one.
+    pub(super) auto: bool,
+    /// Where the worker sends the problems it found. Dropping the run
+    /// cancels it: the result has nowhere to go.
+    pub(super) result: mpsc::Receiver<Vec<ValidationError>>,
+}
@@ -0,0 +1,83 @@
+//! Windowed access to preferences files too large to load whole: only the
+//! tail is read into the buffer, and saves append what was typed after it.
+
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+/// The part of the file that is on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct FileWindow {
+    /// Bytes of the file before the buffer.
+    pub(super) offset: u64,
+    /// Most bytes loaded, reused when the window is read again.
+    pub(super) max_bytes: u64,
+}
+
+/// Read at most the last `max_bytes` of `path`, starting at a line boundary
+/// so the buffer never opens on half a line. Returns `None` for the window
+/// when the whole file fits.
+pub(super) fn read_tail(path: &Path, max_bytes: u64) -> io::Result<(Option<FileWindow>, String)> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(max_bytes);
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let skip = if start == 0 {
+        0
+    } else {
+        bytes
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(bytes.len(), |i| i + 1)
+    };
+    let contents = String::from_utf8(bytes.split_off(skip))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let window = (start > 0).then(|| FileWindow {
+        offset: start + skip as u64,
+        max_bytes,
+    });
+    Ok((window, contents))
+}
+
+/// Add `text` to the end of `path`.
+pub(super) fn append(path: &Path, text: &str) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().append(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_tail_starts_on_a_line_and_append_extends_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.md");
+        fs::write(&path, "first\nsecond\nthird\n").unwrap();
+
+        let (window, contents) = read_tail(&path, 9).unwrap();
+        assert_eq!(contents, "third\n");
+        assert_eq!(
+            window,
+            Some(FileWindow {
+                offset: 13,
+                max_bytes: 9
+            })
+        );
+        assert_eq!(read_tail(&path, 100).unwrap().0, None);
+
+        append(&path, "fourth\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "first\nsecond\nthird\nfourth\n"
+        );
+    }
+}
@@ -4,6 +4,7 @@ use crossterm::event::KeyModifiers;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
 use ratatui::style::Color;
+use ratatui::style::Modifier;
 use ratatui::style::Style;
 use ratatui::widgets::StatefulWidgetRef;
 use ratatui::widgets::WidgetRef;
@@ -26,6 +27,25 @@ pub(crate) struct TextArea {
     wrap_cache: RefCell<Option<WrapCache>>,
     preferred_col: Option<usize>,
     elements: Vec<TextElement>,
+    /// Render each visual line right-aligned with graphemes in right-to-left
+    /// order. This is a basic paragraph direction; there is no bidi
+    /// reordering of embedded left-to-right runs.
+    rtl: bool,
+    /// Extra styles (e.g. a selection) overlaid on byte ranges when rendering.
+    highlights: Vec<(Range<usize>, Style)>,
+    /// Collapsed regions whose visual lines are skipped when wrapping.
+    folds: Vec<Fold>,
+    /// Soft-wrap long lines at the render width; when off they are cut off
+    /// at the right edge instead.
+    wrap: bool,
+}
+
+/// A run of whole lines hidden from display. `summary` is drawn dimmed after
+/// the visible line that precedes the region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fold {
+    pub(crate) range: Range<usize>,
+    pub(crate) summary: String,
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +60,13 @@ pub(crate) struct TextAreaState {
     scroll: u16,
 }
 
+impl TextAreaState {
+    /// Index into wrapped lines of the first visible line.
+    pub(crate) fn scroll(self) -> u16 {
+        self.scroll
+    }
+}
+
 impl TextArea {
     pub fn new() -> Self {
         Self {
@@ -48,6 +75,10 @@ impl TextArea {
             wrap_cache: RefCell::new(None),
             preferred_col: None,
             elements: Vec::new(),
+            rtl: false,
+            highlights: Vec::new(),
+            folds: Vec::new(),
+            wrap: true,
         }
     }
 
@@ -126,6 +157,105 @@ impl TextArea {
         self.preferred_col = None;
     }
 
+    pub fn set_rtl(&mut self, rtl: bool) {
+        self.rtl = rtl;
+    }
+
+    pub fn is_rtl(&self) -> bool {
+        self.rtl
+    }
+
+    /// Replace the styled overlays drawn on top of the text. Ranges are byte
+    /// offsets into the current text; callers refresh them after edits.
+    pub fn set_highlights(&mut self, highlights: Vec<(Range<usize>, Style)>) {
+        self.highlights = highlights;
+    }
+
+    /// Soft-wrap long lines at the render width, or cut them off when off.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        self.wrap_cache.replace(None);
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /// Replace the folded regions. Ranges must cover whole lines and are byte
+    /// offsets into the current text; callers refresh them after edits and
+    /// keep the cursor out of them.
+    pub fn set_folds(&mut self, folds: Vec<Fold>) {
+        self.folds = folds;
+        self.wrap_cache.replace(None);
+    }
+
+    /// Scroll `state` so the cursor's visual line lands on `row` of `area`,
+    /// as far as the content allows.
+    pub(crate) fn scroll_cursor_to_row(&self, state: &mut TextAreaState, area: Rect, row: u16) {
+        let lines = self.wrapped_lines(area.width);
+        let cursor_line = Self::wrapped_line_index_by_start(&lines, self.cursor_pos).unwrap_or(0);
+        let max_scroll = (lines.len() as u16).saturating_sub(area.height);
+        state.scroll = (cursor_line as u16).saturating_sub(row).min(max_scroll);
+    }
+
+    /// Byte offset of the grapheme drawn at screen cell (`x`, `y`) of `area`,
+    /// or the end of that visual line when the cell is past it. `None` below
+    /// the last line.
+    pub(crate) fn position_at(
+        &self,
+        area: Rect,
+        state: TextAreaState,
+        x: u16,
+        y: u16,
+    ) -> Option<usize> {
+        let lines = self.wrapped_lines(area.width);
+        let scroll = self.effective_scroll(area.height, &lines, state.scroll);
+        let row = y.checked_sub(area.y)?;
+        let line = lines.get(usize::from(scroll + row))?;
+        let content = line.start..line.end - 1;
+        let col = usize::from(x.saturating_sub(area.x));
+        let target = if self.rtl {
+            usize::from(area.width).saturating_sub(col + 1)
+        } else {
+            col
+        };
+        let mut width = 0;
+        for (offset, grapheme) in self.text[content.clone()].grapheme_indices(true) {
+            width += grapheme.width();
+            if width > target {
+                return Some(content.start + offset);
+            }
+        }
+        Some(content.end)
+    }
+
+    /// Scroll `state` by `delta` rows, then move the cursor onto the nearest
+    /// row still in view so rendering keeps the new scroll.
+    pub(crate) fn scroll_by(&mut self, state: &mut TextAreaState, area: Rect, delta: i32) {
+        let lines = self.wrapped_lines(area.width);
+        let current = self.effective_scroll(area.height, &lines, state.scroll);
+        let max_scroll = (lines.len() as u16).saturating_sub(area.height);
+        let scroll = (i32::from(current) + delta).clamp(0, i32::from(max_scroll)) as u16;
+        let cursor_line = Self::wrapped_line_index_by_start(&lines, self.cursor_pos).unwrap_or(0);
+        let last_visible = usize::from(scroll + area.height.saturating_sub(1));
+        let visible_line = cursor_line.clamp(usize::from(scroll), last_visible);
+        let cursor = (visible_line != cursor_line).then(|| lines[visible_line].start);
+        drop(lines);
+        state.scroll = scroll;
+        if let Some(cursor) = cursor {
+            self.set_cursor(cursor);
+        }
+    }
+
+    /// Byte offset at which each visual line starts when wrapped to `width`,
+    /// skipping folded lines.
+    pub fn visual_line_starts(&self, width: u16) -> Vec<usize> {
+        self.wrapped_lines(width)
+            .iter()
+            .map(|line| line.start)
+            .collect()
+    }
+
     pub fn desired_height(&self, width: u16) -> u16 {
         self.wrapped_lines(width).len() as u16
     }
@@ -141,7 +271,26 @@ impl TextArea {
         let effective_scroll = self.effective_scroll(area.height, &lines, state.scroll);
         let i = Self::wrapped_line_index_by_start(&lines, self.cursor_pos)?;
         let ls = &lines[i];
-        let col = self.text[ls.start..self.cursor_pos].width() as u16;
+        let mut col = self.text[ls.start..self.cursor_pos].width() as u16;
+        if self.rtl {
+            // The caret sits on the cell of the grapheme after it, which in a
+            // mirrored line is drawn to the left of everything before it.
+            let next_width = if self.cursor_pos < ls.end.saturating_sub(1) {
+                self.text[self.cursor_pos..]
+                    .graphemes(true)
+                    .next()
+                    .map_or(1, UnicodeWidthStr::width)
+            } else {
+                1
+            };
+            col = area
+                .width
+                .saturating_sub(col.saturating_add(next_width as u16));
+        }
+        if !self.wrap {
+            // Unwrapped lines can run past the edge; keep the caret on screen.
+            col = col.min(area.width.saturating_sub(1));
+        }
         let screen_row = i
             .saturating_sub(effective_scroll as usize)
             .try_into()
@@ -847,10 +996,36 @@ impl TextArea {
                 None => true,
             };
             if needs_recalc {
-                let lines = crate::wrapping::wrap_ranges(
-                    &self.text,
-                    Options::new(width as usize).wrap_algorithm(textwrap::WrapAlgorithm::FirstFit),
-                );
+                let mut lines = if self.wrap {
+                    crate::wrapping::wrap_ranges(
+                        &self.text,
+                        Options::new(width as usize)
+                            .wrap_algorithm(textwrap::WrapAlgorithm::FirstFit),
+                    )
+                } else {
+                    // One visual line per logical line, with the same extra
+                    // byte at the end that `wrap_ranges` leaves.
+                    let mut start = 0;
+                    self.text
+                        .split('\n')
+                        .map(|line| {
+                            let range = start..start + line.len() + 1;
+                            start = range.end;
+                            range
+                        })
+                        .collect()
+                };
+                if !self.folds.is_empty() {
+                    // The empty line after a trailing newline belongs to a
+                    // fold that runs to the end of the text.
+                    let text_len = self.text.len();
+                    lines.retain(|line| {
+                        !self.folds.iter().any(|fold| {
+                            fold.range.contains(&line.start)
+                                || (line.start == text_len && fold.range.end == text_len)
+                        })
+                    });
+                }
                 *cache = Some(WrapCache { width, lines });
             }
         }
@@ -869,6 +1044,17 @@ impl TextArea {
         area_height: u16,
         lines: &[Range<usize>],
         current_scroll: u16,
+    ) -> u16 {
+        self.effective_scroll_for(self.cursor_pos, area_height, lines, current_scroll)
+    }
+
+    /// `effective_scroll` for a cursor at `cursor` rather than the real one.
+    fn effective_scroll_for(
+        &self,
+        cursor: usize,
+        area_height: u16,
+        lines: &[Range<usize>],
+        current_scroll: u16,
     ) -> u16 {
         let total_lines = lines.len() as u16;
         if area_height >= total_lines {
@@ -877,8 +1063,7 @@ impl TextArea {
 
         // Where is the cursor within wrapped lines? Prefer assigning boundary positions
         // (where pos equals the start of a wrapped line) to that later line.
-        let cursor_line_idx =
-            Self::wrapped_line_index_by_start(lines, self.cursor_pos).unwrap_or(0) as u16;
+        let cursor_line_idx = Self::wrapped_line_index_by_start(lines, cursor).unwrap_or(0) as u16;
 
         let max_scroll = total_lines.saturating_sub(area_height);
         let mut scroll = current_scroll.min(max_scroll);
@@ -915,6 +1100,24 @@ impl StatefulWidgetRef for &TextArea {
 }
 
 impl TextArea {
+    /// Render as if the cursor were at `cursor`, scrolling `state` to keep
+    /// it visible. Used for a second view onto the same text.
+    pub(crate) fn render_with_cursor(
+        &self,
+        cursor: usize,
+        area: Rect,
+        buf: &mut Buffer,
+        state: &mut TextAreaState,
+    ) {
+        let lines = self.wrapped_lines(area.width);
+        let scroll = self.effective_scroll_for(cursor, area.height, &lines, state.scroll);
+        state.scroll = scroll;
+
+        let start = scroll as usize;
+        let end = (scroll + area.height).min(lines.len() as u16) as usize;
+        self.render_lines(area, buf, &lines, start..end);
+    }
+
     fn render_lines(
         &self,
         area: Rect,
@@ -926,8 +1129,20 @@ impl TextArea {
             let r = &lines[idx];
             let y = area.y + row as u16;
             let line_range = r.start..r.end - 1;
-            // Draw base line with default style.
-            buf.set_string(area.x, y, &self.text[line_range.clone()], Style::default());
+            if self.rtl {
+                self.render_line_rtl(area, buf, y, line_range);
+                continue;
+            }
+            // Draw base line with default style, cut off at the right edge
+            // when long lines are not wrapped.
+            let width = usize::from(area.width);
+            buf.set_stringn(
+                area.x,
+                y,
+                &self.text[line_range.clone()],
+                width,
+                Style::default(),
+            );
 
             // Overlay styled segments for elements that intersect this line.
             for elem in &self.elements {
@@ -938,10 +1153,72 @@ impl TextArea {
                     continue;
                 }
                 let styled = &self.text[overlap_start..overlap_end];
-                let x_off = self.text[line_range.start..overlap_start].width() as u16;
+                let x_off = self.text[line_range.start..overlap_start].width();
+                if x_off >= width {
+                    continue;
+                }
                 let style = Style::default().fg(Color::Cyan);
-                buf.set_string(area.x + x_off, y, styled, style);
+                buf.set_stringn(area.x + x_off as u16, y, styled, width - x_off, style);
+            }
+
+            for (range, style) in &self.highlights {
+                let overlap_start = range.start.max(line_range.start);
+                let overlap_end = range.end.min(line_range.end);
+                if overlap_start >= overlap_end
+                    || !self.text.is_char_boundary(overlap_start)
+                    || !self.text.is_char_boundary(overlap_end)
+                {
+                    continue;
+                }
+                let styled = &self.text[overlap_start..overlap_end];
+                let x_off = self.text[line_range.start..overlap_start].width();
+                if x_off >= width {
+                    continue;
+                }
+                buf.set_stringn(area.x + x_off as u16, y, styled, width - x_off, *style);
+            }
+
+            if let Some(fold) = self.folds.iter().find(|fold| fold.range.start == r.end) {
+                let x = area.x + (self.text[line_range.clone()].width() as u16).min(area.width);
+                let width = (area.x + area.width).saturating_sub(x) as usize;
+                buf.set_stringn(
+                    x,
+                    y,
+                    &fold.summary,
+                    width,
+                    Style::default().add_modifier(Modifier::DIM),
+                );
+            }
+        }
+    }
+
+    /// Draw one visual line right-aligned, with its graphemes mirrored so the
+    /// first logical grapheme lands in the rightmost cell.
+    fn render_line_rtl(&self, area: Rect, buf: &mut Buffer, y: u16, line_range: Range<usize>) {
+        let slice = &self.text[line_range.clone()];
+        let mut x = area.x + area.width.saturating_sub(slice.width() as u16);
+        for (offset, grapheme) in slice.grapheme_indices(true).rev() {
+            let pos = line_range.start + offset;
+            let mut style = if self
+                .elements
+                .iter()
+                .any(|e| pos >= e.range.start && pos < e.range.end)
+            {
+                Style::default().fg(Color::Cyan)
+            } else {
+                Style::default()
+            };
+            for (range, highlight) in &self.highlights {
+                if range.contains(&pos) {
+                    style = style.patch(*highlight);
+                }
+            }
+            let next = x.saturating_add(grapheme.width() as u16);
+            if next > area.right() {
+                break;
             }
+            buf.set_string(x, y, grapheme, style);
+            x = next;
         }
     }
 }
@@ -1042,6 +1319,44 @@ mod tests {
         assert_eq!(t.cursor(), 5);
     }
 
+    #[test]
+    fn unwrapped_lines_are_cut_off_and_keep_one_row_each() {
+        let mut t = ta_with("abcdefgh\nxy");
+        assert_eq!(t.desired_height(4), 3);
+        t.set_wrap(false);
+        assert_eq!(t.desired_height(4), 2);
+
+        let area = Rect::new(0, 0, 4, 2);
+        let mut buf = Buffer::empty(Rect::new(0, 0, 6, 2));
+        StatefulWidgetRef::render_ref(&(&t), area, &mut buf, &mut TextAreaState::default());
+        let row: String = (0..6).map(|x| buf[(x, 0)].symbol()).collect();
+        assert_eq!(row, "abcd  ");
+
+        t.set_cursor(7);
+        assert_eq!(t.cursor_pos(area), Some((3, 0)));
+    }
+
+    #[test]
+    fn position_at_and_scroll_by_map_screen_rows_to_text() {
+        let mut t = ta_with("a\nbé c\nd\ne\n");
+        t.set_cursor(0);
+        let area = Rect::new(2, 5, 10, 2);
+        let mut state = TextAreaState::default();
+        assert_eq!(t.position_at(area, state, 2, 5), Some(0));
+        assert_eq!(t.position_at(area, state, 4, 6), Some(5));
+        assert_eq!(t.position_at(area, state, 9, 6), Some(7));
+        assert_eq!(t.position_at(area, state, 2, 4), None);
+
+        t.scroll_by(&mut state, area, 2);
+        assert_eq!(state.scroll(), 2);
+        assert_eq!(t.cursor(), 8);
+        assert_eq!(t.position_at(area, state, 2, 6), Some(10));
+
+        t.scroll_by(&mut state, area, -5);
+        assert_eq!(state.scroll(), 0);
+        assert_eq!(t.cursor(), 2);
+    }
+
     #[test]
     fn delete_backward_and_forward_edges() {
         let mut t = ta_with("abc");
@@ -1460,6 +1775,77 @@ mod tests {
         assert_eq!(y, area.y);
     }
 
+    #[test]
+    fn rtl_renders_mirrored_and_right_aligned() {
+        let mut t = ta_with("abc");
+        t.set_rtl(true);
+        let area = Rect::new(0, 0, 6, 1);
+        let mut buf = Buffer::empty(area);
+        WidgetRef::render_ref(&(&t), area, &mut buf);
+        let row: String = (0..6).map(|x| buf[(x, 0)].symbol().to_string()).collect();
+        assert_eq!(row, "   cba");
+
+        t.set_cursor(0);
+        assert_eq!(t.cursor_pos(area), Some((5, 0)));
+        t.set_cursor(3);
+        assert_eq!(t.cursor_pos(area), Some((2, 0)));
+    }
+
+    #[test]
+    fn scroll_cursor_to_row_clamps_to_content() {
+        let mut t = TextArea::new();
+        t.set_text("0\n1\n2\n3\n4\n5\n6\n7\n8\n9");
+        t.set_cursor(t.text().find('6').unwrap());
+        let area = Rect::new(0, 0, 10, 4);
+        let mut state = TextAreaState::default();
+
+        t.scroll_cursor_to_row(&mut state, area, 1);
+        assert_eq!(state.scroll, 5);
+        assert_eq!(t.cursor_pos_with_state(area, state), Some((0, 1)));
+
+        t.scroll_cursor_to_row(&mut state, area, 0);
+        assert_eq!(state.scroll, 6);
+
+        t.set_cursor(t.text().len());
+        t.scroll_cursor_to_row(&mut state, area, 0);
+        assert_eq!(state.scroll, 6);
+    }
+
+    #[test]
+    fn highlights_overlay_style_on_ranges() {
+        let mut t = ta_with("hello world");
+        t.set_highlights(vec![(6..11, Style::default().fg(Color::Red))]);
+        let area = Rect::new(0, 0, 12, 1);
+        let mut buf = Buffer::empty(area);
+        WidgetRef::render_ref(&(&t), area, &mut buf);
+        assert_eq!(buf[(5, 0)].fg, Color::Reset);
+        assert_eq!(buf[(6, 0)].fg, Color::Red);
+        assert_eq!(buf[(10, 0)].fg, Color::Red);
+        assert_eq!(buf[(6, 0)].symbol(), "w");
+    }
+
+    #[test]
+    fn folds_hide_lines_and_draw_summary() {
+        let mut t = ta_with("a\nb\nc\nd");
+        t.set_folds(vec![Fold {
+            range: 2..6,
+            summary: " +2".to_string(),
+        }]);
+        assert_eq!(t.desired_height(10), 2);
+
+        let area = Rect::new(0, 0, 10, 2);
+        let mut buf = Buffer::empty(area);
+        WidgetRef::render_ref(&(&t), area, &mut buf);
+        let rows: Vec<String> = (0..2)
+            .map(|y| (0..4).map(|x| buf[(x, y)].symbol().to_string()).collect())
+            .collect();
+        assert_eq!(rows, vec!["a +2".to_string(), "d   ".to_string()]);
+
+        t.set_cursor(0);
+        t.move_cursor_down();
+        assert_eq!(t.cursor(), 6);
+    }
+
     #[test]
     fn wrapped_navigation_across_visual_lines() {
         let mut t = ta_with("abcdefghij");
@@ -47,6 +47,7 @@ use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
 use crossterm::event::KeyEventKind;
 use crossterm::event::KeyModifiers;
+use crossterm::event::MouseEvent;
 use rand::Rng;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Constraint;
@@ -84,6 +85,7 @@ use crate::markdown::append_markdown;
 use crate::slash_command::SlashCommand;
 use crate::status::RateLimitSnapshotDisplay;
 use crate::text_formatting::truncate_text;
+use crate::tui::CursorShape;
 use crate::tui::FrameRequester;
 mod interrupts;
 use self::interrupts::InterruptManager;
@@ -1187,6 +1189,33 @@ impl ChatWidget {
         self.bottom_pane.handle_paste(text);
     }
 
+    /// Route a mouse event to the bottom pane; `area` is where the widget was
+    /// last drawn.
+    pub(crate) fn handle_mouse_event(&mut self, mouse_event: MouseEvent, area: Rect) {
+        let [_, _, bottom_pane_area] = self.layout_areas(area);
+        self.bottom_pane
+            .handle_mouse_event(mouse_event, bottom_pane_area);
+    }
+
+    pub(crate) fn wants_mouse(&self) -> bool {
+        self.bottom_pane.wants_mouse()
+    }
+
+    pub(crate) fn on_focus_changed(&mut self, focused: bool) {
+        self.bottom_pane.on_focus_changed(focused);
+    }
+
+    pub(crate) fn on_external_command_finished(
+        &mut self,
+        status: std::io::Result<std::process::ExitStatus>,
+    ) {
+        self.bottom_pane.on_external_command_finished(status);
+    }
+
+    pub(crate) fn on_tick(&mut self) {
+        self.bottom_pane.on_tick();
+    }
+
     // Returns true if caller should skip rendering this frame (a future frame is scheduled).
     pub(crate) fn handle_paste_burst_tick(&mut self, frame_requester: FrameRequester) -> bool {
         if self.bottom_pane.flush_paste_burst_if_due() {
@@ -1988,6 +2017,10 @@ impl ChatWidget {
         let [_, _, bottom_pane_area] = self.layout_areas(area);
         self.bottom_pane.cursor_pos(bottom_pane_area)
     }
+
+    pub(crate) fn cursor_shape(&self) -> Option<CursorShape> {
+        self.bottom_pane.cursor_shape()
+    }
 }
 
 impl WidgetRef for &ChatWidget {
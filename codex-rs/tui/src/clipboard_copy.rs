@@ -0,0 +1,28 @@
+//! Copy text to the system clipboard.
+
+/// Place `text` on the system clipboard.
+#[cfg(not(target_os = "android"))]
+pub fn copy_text(text: &str) -> Result<(), String> {
+    let mut cb = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    cb.set_text(text.to_string()).map_err(|e| e.to_string())
+}
+
+/// Android/Termux does not support arboard; return a clear error.
+#[cfg(target_os = "android")]
+pub fn copy_text(_text: &str) -> Result<(), String> {
+    Err("clipboard copy is unsupported on Android".into())
+}
+
+/// Place `html` on the system clipboard, with `alt_text` as the plain-text
+/// fallback for targets that cannot paste rich text.
+#[cfg(not(target_os = "android"))]
+pub fn copy_html(html: &str, alt_text: &str) -> Result<(), String> {
+    let mut cb = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    cb.set_html(html, Some(alt_text)).map_err(|e| e.to_string())
+}
+
+/// Android/Termux does not support arboard; return a clear error.
+#[cfg(target_os = "android")]
+pub fn copy_html(_html: &str, _alt_text: &str) -> Result<(), String> {
+    Err("clipboard copy is unsupported on Android".into())
+}
@@ -348,6 +348,7 @@ pub(crate) async fn run_onboarding_app(
                 TuiEvent::Paste(text) => {
                     onboarding_screen.handle_paste(text);
                 }
+                TuiEvent::FocusChanged(_) | TuiEvent::Mouse(_) => {}
                 TuiEvent::Draw => {
                     if !did_full_clear_after_success
                         && onboarding_screen.steps.iter().any(|step| {
@@ -3,13 +3,19 @@ use std::io::Result;
 use std::io::Stdout;
 use std::io::stdout;
 use std::pin::Pin;
+use std::process::ExitStatus;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
 use std::sync::atomic::AtomicBool;
 #[cfg(unix)]
 use std::sync::atomic::AtomicU8;
 #[cfg(unix)]
 use std::sync::atomic::AtomicU16;
 use std::sync::atomic::Ordering;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
 use std::time::Duration;
 use std::time::Instant;
 
@@ -17,13 +23,18 @@ use crossterm::Command;
 use crossterm::SynchronizedUpdate;
 #[cfg(unix)]
 use crossterm::cursor::MoveTo;
+use crossterm::cursor::SetCursorStyle;
 use crossterm::event::DisableBracketedPaste;
 use crossterm::event::DisableFocusChange;
+use crossterm::event::DisableMouseCapture;
 use crossterm::event::EnableBracketedPaste;
 use crossterm::event::EnableFocusChange;
+use crossterm::event::EnableMouseCapture;
 use crossterm::event::Event;
+use crossterm::event::EventStream;
 use crossterm::event::KeyEvent;
 use crossterm::event::KeyboardEnhancementFlags;
+use crossterm::event::MouseEvent;
 use crossterm::event::PopKeyboardEnhancementFlags;
 use crossterm::event::PushKeyboardEnhancementFlags;
 use crossterm::terminal::EnterAlternateScreen;
@@ -117,8 +128,10 @@ pub fn restore() -> Result<()> {
     let _ = execute!(stdout(), PopKeyboardEnhancementFlags);
     execute!(stdout(), DisableBracketedPaste)?;
     let _ = execute!(stdout(), DisableFocusChange);
+    let _ = execute!(stdout(), DisableMouseCapture);
     disable_raw_mode()?;
     let _ = execute!(stdout(), crossterm::cursor::Show);
+    let _ = execute!(stdout(), SetCursorStyle::DefaultUserShape);
     Ok(())
 }
 
@@ -149,6 +162,10 @@ pub enum TuiEvent {
     Key(KeyEvent),
     Paste(String),
     Draw,
+    /// The terminal window gained (`true`) or lost (`false`) focus.
+    FocusChanged(bool),
+    /// Only reported while mouse capture is on; see `Tui::set_mouse_capture`.
+    Mouse(MouseEvent),
 }
 
 pub struct Tui {
@@ -166,6 +183,58 @@ pub struct Tui {
     // True when terminal/tab is focused; updated internally from crossterm events
     terminal_focused: Arc<AtomicBool>,
     enhanced_keys_supported: bool,
+    // Cursor shape last requested via DECSCUSR; `None` means the user's default.
+    cursor_shape: Option<CursorShape>,
+    // Whether mouse events are being captured instead of handled by the terminal.
+    mouse_capture: bool,
+    // Crossterm's reader behind `event_stream`, dropped while a child process
+    // owns the terminal so that it gets every key.
+    input: Arc<Mutex<TerminalInput>>,
+}
+
+#[derive(Default)]
+struct TerminalInput {
+    // `None` while paused; crossterm stops reading stdin once it is dropped.
+    events: Option<EventStream>,
+    // The event stream's task, parked while input is paused.
+    waker: Option<Waker>,
+}
+
+/// Crossterm events from the reader in `TerminalInput`, pending while it is
+/// paused.
+struct TerminalEvents(Arc<Mutex<TerminalInput>>);
+
+impl Stream for TerminalEvents {
+    type Item = Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut input = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match input.events.as_mut() {
+            Some(events) => Pin::new(events).poll_next(cx),
+            None => {
+                input.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Terminal cursor shapes that views can request while they are active.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum CursorShape {
+    Block,
+    Bar,
+    Underline,
+}
+
+impl CursorShape {
+    fn style(self) -> SetCursorStyle {
+        match self {
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+            CursorShape::Underline => SetCursorStyle::SteadyUnderScore,
+        }
+    }
 }
 
 #[cfg(unix)]
@@ -293,6 +362,36 @@ impl Tui {
             alt_screen_active: Arc::new(AtomicBool::new(false)),
             terminal_focused: Arc::new(AtomicBool::new(true)),
             enhanced_keys_supported,
+            cursor_shape: None,
+            mouse_capture: false,
+            input: Arc::default(),
+        }
+    }
+
+    /// Switch the terminal cursor shape, emitting DECSCUSR only when the
+    /// requested shape differs from the current one. `None` restores the
+    /// user's default shape.
+    pub(crate) fn set_cursor_shape(&mut self, shape: Option<CursorShape>) -> Result<()> {
+        if self.cursor_shape == shape {
+            return Ok(());
+        }
+        self.cursor_shape = shape;
+        let style = shape.map_or(SetCursorStyle::DefaultUserShape, CursorShape::style);
+        execute!(self.terminal.backend_mut(), style)
+    }
+
+    /// Turn mouse reporting on or off, emitting the escape sequence only when
+    /// the setting changes. While it is on the terminal's own text selection
+    /// and wheel scrolling are unavailable.
+    pub(crate) fn set_mouse_capture(&mut self, enabled: bool) -> Result<()> {
+        if self.mouse_capture == enabled {
+            return Ok(());
+        }
+        self.mouse_capture = enabled;
+        if enabled {
+            execute!(self.terminal.backend_mut(), EnableMouseCapture)
+        } else {
+            execute!(self.terminal.backend_mut(), DisableMouseCapture)
         }
     }
 
@@ -308,7 +407,8 @@ impl Tui {
 
     pub fn event_stream(&self) -> Pin<Box<dyn Stream<Item = TuiEvent> + Send + 'static>> {
         use tokio_stream::StreamExt;
-        let mut crossterm_events = crossterm::event::EventStream::new();
+        self.resume_input();
+        let mut crossterm_events = TerminalEvents(self.input.clone());
         let mut draw_rx = self.draw_tx.subscribe();
         #[cfg(unix)]
         let resume_pending = self.resume_pending.clone();
@@ -362,9 +462,14 @@ impl Tui {
                             }
                             Event::FocusGained => {
                                 terminal_focused.store(true, Ordering::Relaxed);
+                                yield TuiEvent::FocusChanged(true);
                             }
                             Event::FocusLost => {
                                 terminal_focused.store(false, Ordering::Relaxed);
+                                yield TuiEvent::FocusChanged(false);
+                            }
+                            Event::Mouse(mouse_event) => {
+                                yield TuiEvent::Mouse(mouse_event);
                             }
                             _ => {}
                         }
@@ -388,6 +493,64 @@ impl Tui {
         };
         Box::pin(event_stream)
     }
+    /// Stop reading terminal input: dropping crossterm's reader ends its
+    /// stdin thread, so keys go to whoever owns the terminal next.
+    fn pause_input(&self) {
+        self.input
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .events = None;
+    }
+
+    /// Start reading terminal input again and wake the event stream.
+    fn resume_input(&self) {
+        let mut input = self.input.lock().unwrap_or_else(PoisonError::into_inner);
+        input.events = Some(EventStream::new());
+        if let Some(waker) = input.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Hand the terminal to `command`, e.g. the user's editor, until it
+    /// exits. Input stops being read so the child gets every key, and our
+    /// modes and the alternate screen are undone for it. Afterwards they are
+    /// set again, along with mouse capture and the cursor shape, and the
+    /// screen is repainted from scratch.
+    pub(crate) async fn run_external(
+        &mut self,
+        command: &mut tokio::process::Command,
+    ) -> Result<ExitStatus> {
+        self.pause_input();
+        let status = self.lend_terminal(command).await;
+        self.resume_input();
+        self.frame_requester().schedule_frame();
+        status
+    }
+
+    async fn lend_terminal(&mut self, command: &mut tokio::process::Command) -> Result<ExitStatus> {
+        let alt_screen = self.alt_screen_active.load(Ordering::Relaxed);
+        if alt_screen {
+            let _ = execute!(self.terminal.backend_mut(), DisableAlternateScroll);
+            let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        }
+        restore()?;
+        let status = command.status().await;
+        set_modes()?;
+        if alt_screen {
+            execute!(self.terminal.backend_mut(), EnterAlternateScreen)?;
+            execute!(self.terminal.backend_mut(), EnableAlternateScroll)?;
+        }
+        // `restore` turned both of these back to the terminal's defaults.
+        if self.mouse_capture {
+            execute!(self.terminal.backend_mut(), EnableMouseCapture)?;
+        }
+        if let Some(shape) = self.cursor_shape {
+            execute!(self.terminal.backend_mut(), shape.style())?;
+        }
+        self.terminal.clear()?;
+        status
+    }
+
     #[cfg(unix)]
     fn suspend() -> Result<()> {
         restore()?;